@@ -0,0 +1,162 @@
+use crate::{CellData, Deci, Milli};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Alarming condition of battery
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum Alarm {
+    /// Voltage difference between cells is too high
+    CellDelta { value: Milli },
+    /// Battery is almost discharged
+    LowCharge { value: u8 },
+    /// Battery temperature is below normal range
+    LowTemperature { sensor: usize, value: Deci },
+    /// Battery temperature is above normal range
+    HighTemperature { sensor: usize, value: Deci },
+    /// MOSFET temperature is above normal
+    HighMosfetTemperature { value: Deci },
+}
+
+impl Alarm {
+    /// Cell voltage delta which is considered high
+    pub const DELTA_WARN: Milli = Milli::from_raw(20);
+
+    /// Cell voltage delta which is considered alarming
+    pub const DELTA_ALARM: Milli = Milli::from_raw(50);
+
+    /// Remain percent which is considered alarming
+    pub const LOW_PERCENT: u8 = 10;
+
+    /// Range of battery temperatures considered normal
+    pub const BATTERY_TEMPERATURE: (Deci, Deci) = (Deci::ZERO, Deci::from_raw(500));
+
+    /// Maximum of MOSFET temperature considered normal
+    pub const MOSFET_TEMPERATURE: Deci = Deci::from_raw(700);
+
+    /// Name of data field which value caused alarm
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::CellDelta { .. } => "delta_cell_voltage",
+            Self::LowCharge { .. } => "remain_percent",
+            Self::LowTemperature { .. } | Self::HighTemperature { .. } => "battery_temperature",
+            Self::HighMosfetTemperature { .. } => "mosfet_temperature",
+        }
+    }
+
+    /// Index of sensor which value caused alarm (for per-cell values)
+    pub fn sensor(&self) -> Option<usize> {
+        match self {
+            Self::LowTemperature { sensor, .. } | Self::HighTemperature { sensor, .. } => {
+                Some(*sensor)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether alarms have the same cause (regardless of value)
+    pub fn is_same(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::LowTemperature { sensor, .. }, Self::LowTemperature { sensor: other, .. })
+            | (Self::HighTemperature { sensor, .. }, Self::HighTemperature { sensor: other, .. }) => {
+                sensor == other
+            }
+            _ => core::mem::discriminant(self) == core::mem::discriminant(other),
+        }
+    }
+}
+
+impl core::fmt::Display for Alarm {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::CellDelta { value } => write!(f, "Cell delta {} mV", value.raw()),
+            Self::LowCharge { value } => write!(f, "Low charge {value} %"),
+            Self::LowTemperature { sensor, value } => {
+                write!(f, "Low temperature #{sensor} {value:.1} ℃")
+            }
+            Self::HighTemperature { sensor, value } => {
+                write!(f, "High temperature #{sensor} {value:.1} ℃")
+            }
+            Self::HighMosfetTemperature { value } => {
+                write!(f, "High MOSFET temperature {value:.1} ℃")
+            }
+        }
+    }
+}
+
+impl CellData {
+    /// Active alarms derived from data
+    pub fn alarms(&self) -> Vec<Alarm> {
+        let mut alarms = Vec::new();
+
+        if self.delta_cell_voltage >= Alarm::DELTA_ALARM {
+            alarms.push(Alarm::CellDelta {
+                value: self.delta_cell_voltage,
+            });
+        }
+
+        if self.remain_percent <= Alarm::LOW_PERCENT {
+            alarms.push(Alarm::LowCharge {
+                value: self.remain_percent,
+            });
+        }
+
+        for (sensor, value) in self.battery_temperature.iter().copied().enumerate() {
+            if value < Alarm::BATTERY_TEMPERATURE.0 {
+                alarms.push(Alarm::LowTemperature { sensor, value });
+            } else if value > Alarm::BATTERY_TEMPERATURE.1 {
+                alarms.push(Alarm::HighTemperature { sensor, value });
+            }
+        }
+
+        if self.mosfet_temperature > Alarm::MOSFET_TEMPERATURE {
+            alarms.push(Alarm::HighMosfetTemperature {
+                value: self.mosfet_temperature,
+            });
+        }
+
+        alarms
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn alarms() {
+        let data = CellData {
+            delta_cell_voltage: Milli::from_raw(60),
+            remain_percent: 50,
+            battery_temperature: [-1.5, 20.0, 55.0].map(Deci::from_f64).to_vec(),
+            ..Default::default()
+        };
+
+        let alarms = data.alarms();
+        assert_eq!(
+            alarms.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "Cell delta 60 mV",
+                "Low temperature #0 -1.5 ℃",
+                "High temperature #2 55.0 ℃"
+            ]
+        );
+
+        assert!(alarms[1].is_same(&Alarm::LowTemperature {
+            sensor: 0,
+            value: Deci::from_f64(-3.0)
+        }));
+        assert!(!alarms[1].is_same(&alarms[2]));
+        assert!(alarms[0].is_same(&Alarm::CellDelta { value: Milli::ZERO }));
+
+        assert_eq!(alarms[0].field(), "delta_cell_voltage");
+        assert_eq!(alarms[0].sensor(), None);
+        assert_eq!(alarms[2].field(), "battery_temperature");
+        assert_eq!(alarms[2].sensor(), Some(2));
+        assert!(alarms
+            .iter()
+            .all(|alarm| CellData::field(alarm.field()).is_some()));
+    }
+}
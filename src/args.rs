@@ -1,23 +1,88 @@
-use crate::{DeviceId, Format, Options};
+use crate::{DeviceConfig, DeviceId, Format, Options};
 use argp::FromArgs;
 use core::time::Duration;
 
+#[cfg(feature = "metrics")]
+use crate::config::{is_label_name, is_namespace};
+#[cfg(feature = "metrics")]
+use crate::MetricsOptions;
+
 #[cfg(feature = "exporter")]
-use crate::Encoding;
+use crate::{DeviceLabel, Encoding, StaleAction, StalePolicy};
 #[cfg(feature = "exporter")]
 use hyper::Uri;
 #[cfg(feature = "exporter")]
 use std::net::{IpAddr, SocketAddr};
 
+#[cfg(feature = "config")]
+use crate::Config;
+#[cfg(any(
+    feature = "config",
+    feature = "push",
+    feature = "watch",
+    feature = "history",
+    all(feature = "pull", feature = "tls")
+))]
+use std::path::PathBuf;
+
+#[cfg(feature = "history")]
+use crate::RetentionPolicy;
+
+#[cfg(feature = "push")]
+use crate::{PushMethod, PushProtocol};
+#[cfg(feature = "push")]
+use hyper::header::{HeaderName, HeaderValue};
+
 #[cfg(feature = "tracing-subscriber")]
 use tracing_subscriber::EnvFilter;
 
+#[cfg(feature = "exporter")]
+const DEFAULT_URL: &str = "http://127.0.0.1:9889/metrics";
+
+#[cfg(feature = "exporter")]
+const DEFAULT_SCRAPE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[cfg(feature = "push")]
+const DEFAULT_PUSH_RETRIES: usize = 3;
+
+#[cfg(feature = "push")]
+const DEFAULT_PUSH_BACKOFF: Duration = Duration::from_secs(1);
+
+#[cfg(feature = "push")]
+const DEFAULT_PUSH_QUEUE_SIZE: usize = 1440;
+
+#[cfg(feature = "mqtt")]
+const DEFAULT_MQTT_PORT: u16 = 1883;
+
+#[cfg(feature = "mqtt")]
+const DEFAULT_MQTT_TOPIC: &str = "ubmsc";
+
+#[cfg(feature = "mqtt")]
+const DEFAULT_MQTT_DISCOVERY: &str = "homeassistant";
+
+#[cfg(feature = "watch")]
+const DEFAULT_ROTATE_KEEP: usize = 5;
+
+#[cfg(feature = "history")]
+const DEFAULT_DOWNSAMPLE_INTERVAL: Duration = Duration::from_secs(3600);
+
+#[cfg(feature = "history")]
+const DEFAULT_QUERY_RANGE: Duration = Duration::from_secs(3600);
+
+#[cfg(feature = "monitor")]
+const DEFAULT_MONITOR_INTERVAL: Duration = Duration::from_secs(2);
+
 /// Battery Management Systems (BMS) interface.
 #[cfg_attr(feature = "push", doc = "")]
 #[cfg_attr(
     feature = "push",
     doc = "When passed both -e and -p options push client will be run in continuous mode with specified interval."
 )]
+#[cfg_attr(all(feature = "mqtt", not(feature = "push")), doc = "")]
+#[cfg_attr(
+    feature = "mqtt",
+    doc = "When passed both -e and -m options MQTT publisher will be run in continuous mode with specified interval."
+)]
 #[derive(FromArgs, Debug)]
 pub struct Args {
     /// Show version and exit
@@ -39,40 +104,69 @@ pub struct Args {
     #[argp(switch, short = 'j')]
     pub journal: bool,
 
+    /// Configuration file (command-line options take precedence)
+    #[cfg(feature = "config")]
+    #[argp(option, short = 'C', arg_name = "path")]
+    pub config: Option<PathBuf>,
+
+    /// Validate configuration file and exit
+    #[cfg(feature = "config")]
+    #[argp(switch)]
+    pub check_config: bool,
+
     /// Bluetooth scanning timeout in seconds (30 by default)
     #[argp(
         option,
         short = 't',
         arg_name = "seconds",
-        default = "Duration::from_secs(30)",
         from_str_fn(Args::parse_duration)
     )]
-    pub scan_timeout: Duration,
+    pub scan_timeout: Option<Duration>,
 
     /// Bluetooth request timeout in seconds (5 by default)
     #[argp(
         option,
         short = 'r',
         arg_name = "seconds",
-        default = "Duration::from_secs(5)",
         from_str_fn(Args::parse_duration)
     )]
-    pub request_timeout: Duration,
+    pub request_timeout: Option<Duration>,
 
-    /// Device addresses or names (will try to scan if nothing passed)
+    /// Device addresses or names with optional aliases (will try to scan if nothing passed)
     #[argp(
         option,
         short = 'd',
-        arg_name = "address",
-        from_str_fn(Args::parse_device_id)
+        arg_name = "[alias=]address",
+        from_str_fn(Args::parse_device)
     )]
-    pub device: Vec<DeviceId>,
+    pub device: Vec<DeviceConfig>,
+
+    /// Extra label for metrics of all devices
+    #[cfg(feature = "metrics")]
+    #[argp(option, arg_name = "name=value", from_str_fn(Args::parse_label))]
+    pub label: Vec<(String, String)>,
+
+    /// Prefix of metric names (ubmsc by default, empty to disable)
+    #[cfg(feature = "metrics")]
+    #[argp(option, arg_name = "prefix", from_str_fn(Args::parse_namespace))]
+    pub namespace: Option<String>,
+
+    /// Use legacy metric names (without namespace, units and `_total` suffixes)
+    #[cfg(feature = "metrics")]
+    #[argp(switch)]
+    pub legacy_names: bool,
 
     /// Data format: rust(r) (by default) rust-pretty(R)
     #[cfg_attr(feature = "json", doc = "json(j) json-pretty(J)")]
     #[cfg_attr(feature = "yaml", doc = "yaml(y)")]
     #[cfg_attr(feature = "toml", doc = "toml(t) toml-pretty(T)")]
+    #[cfg_attr(feature = "cbor", doc = "cbor(b)")]
+    #[cfg_attr(feature = "msgpack", doc = "msgpack(p)")]
     #[cfg_attr(feature = "metrics", doc = "metrics(m)")]
+    #[cfg_attr(feature = "influx", doc = "influx(i)")]
+    #[cfg_attr(feature = "csv", doc = "csv(c)")]
+    #[cfg_attr(feature = "json", doc = "ndjson(n)")]
+    #[cfg_attr(feature = "table", doc = "table(a)")]
     #[argp(
         option,
         short = 'f',
@@ -82,6 +176,11 @@ pub struct Args {
     )]
     pub format: Format,
 
+    /// Serialize values of cell data together with units
+    #[cfg(all(feature = "units", feature = "serde"))]
+    #[argp(switch)]
+    pub units: bool,
+
     /// Show device info
     #[argp(switch, short = 'i')]
     pub device_info: bool,
@@ -90,6 +189,32 @@ pub struct Args {
     #[argp(switch, short = 'c')]
     pub cell_data: bool,
 
+    /// Log cell data of each device with interval until interrupted
+    /// (csv, ndjson, cbor or msgpack format)
+    #[cfg(feature = "watch")]
+    #[argp(
+        option,
+        short = 'w',
+        arg_name = "seconds",
+        from_str_fn(Args::parse_duration)
+    )]
+    pub watch: Option<Duration>,
+
+    /// Append logged records to file instead of stdout
+    #[cfg(feature = "watch")]
+    #[argp(option, short = 'o', arg_name = "path")]
+    pub output: Option<PathBuf>,
+
+    /// Rotate output file when it exceeds size in MiB
+    #[cfg(feature = "watch")]
+    #[argp(option, arg_name = "MiB")]
+    pub rotate_size: Option<u64>,
+
+    /// Number of rotated output files to keep (5 by default)
+    #[cfg(feature = "watch")]
+    #[argp(option, arg_name = "count")]
+    pub rotate_keep: Option<usize>,
+
     /// Run prometheus exporter
     #[cfg(feature = "exporter")]
     #[argp(switch, short = 'e')]
@@ -100,15 +225,16 @@ pub struct Args {
     #[argp(switch, short = 'p')]
     pub push: bool,
 
+    /// Run MQTT publisher (with Home Assistant discovery)
+    #[cfg(feature = "mqtt")]
+    #[argp(switch, short = 'm')]
+    pub mqtt: bool,
+
     /// Prometheus exporter URL to listen/connect
+    #[cfg_attr(feature = "mqtt", doc = "(mqtt://host:port of MQTT broker)")]
     #[cfg(feature = "exporter")]
-    #[argp(
-        option,
-        short = 'u',
-        default = "\"http://127.0.0.1:9889/metrics\".parse().unwrap()",
-        from_str_fn(Args::parse_url)
-    )]
-    pub url: Uri,
+    #[argp(option, short = 'u', from_str_fn(Args::parse_url))]
+    pub url: Option<Uri>,
 
     /// Metrics scraping interval (60s by default)
     #[cfg(feature = "exporter")]
@@ -116,15 +242,226 @@ pub struct Args {
         option,
         short = 's',
         arg_name = "seconds",
-        default = "Duration::from_secs(60)",
         from_str_fn(Args::parse_duration)
     )]
-    pub scrape_interval: Duration,
+    pub scrape_interval: Option<Duration>,
 
-    /// Prefer protobuf data format
+    /// Prefer protobuf data format (same as --encoding protobuf)
     #[cfg(feature = "exporter")]
     #[argp(switch, short = 'b')]
     pub protobuf: bool,
+
+    /// Preferred data format: text (by default) protobuf openmetrics
+    #[cfg(feature = "exporter")]
+    #[argp(option, arg_name = "format", from_str_fn(core::str::FromStr::from_str))]
+    pub encoding: Option<Encoding>,
+
+    /// TLS certificate chain file in PEM format (for https server)
+    #[cfg(all(feature = "pull", feature = "tls"))]
+    #[argp(option, arg_name = "path")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// TLS private key file in PEM format (for https server)
+    #[cfg(all(feature = "pull", feature = "tls"))]
+    #[argp(option, arg_name = "path")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Push protocol: exposition (by default)
+    #[cfg_attr(feature = "remote-write", doc = "remote-write")]
+    #[cfg_attr(feature = "influx", doc = "influx")]
+    #[cfg(feature = "push")]
+    #[argp(
+        option,
+        arg_name = "protocol",
+        from_str_fn(core::str::FromStr::from_str)
+    )]
+    pub push_protocol: Option<PushProtocol>,
+
+    /// Pushgateway job (push to <url>/job/<job>/instance/<instance> group)
+    #[cfg(feature = "push")]
+    #[argp(option, arg_name = "name")]
+    pub push_job: Option<String>,
+
+    /// Pushgateway instance (host name by default)
+    #[cfg(feature = "push")]
+    #[argp(option, arg_name = "name")]
+    pub push_instance: Option<String>,
+
+    /// Push metrics of each device to separate Pushgateway group
+    #[cfg(feature = "push")]
+    #[argp(switch)]
+    pub push_per_device: bool,
+
+    /// HTTP method to push metrics: put (by default) post
+    #[cfg(feature = "push")]
+    #[argp(option, arg_name = "method", from_str_fn(core::str::FromStr::from_str))]
+    pub push_method: Option<PushMethod>,
+
+    /// Number of retries of failed push (3 by default)
+    #[cfg(feature = "push")]
+    #[argp(option, arg_name = "count")]
+    pub push_retries: Option<usize>,
+
+    /// Delay before the first retry of push doubled for next ones (1s by default)
+    #[cfg(feature = "push")]
+    #[argp(option, arg_name = "seconds", from_str_fn(Args::parse_duration))]
+    pub push_backoff: Option<Duration>,
+
+    /// Directory to queue metrics which failed to push (target should accept timestamps)
+    #[cfg(feature = "push")]
+    #[argp(option, arg_name = "path")]
+    pub push_queue: Option<PathBuf>,
+
+    /// Maximum number of queued samples (1440 by default)
+    #[cfg(feature = "push")]
+    #[argp(option, arg_name = "count")]
+    pub push_queue_size: Option<usize>,
+
+    /// Extra HTTP header for push requests
+    #[cfg(feature = "push")]
+    #[argp(option, arg_name = "name: value", from_str_fn(Args::parse_header))]
+    pub header: Vec<(HeaderName, HeaderValue)>,
+
+    /// Basic auth credentials for push requests
+    #[cfg(feature = "push")]
+    #[argp(
+        option,
+        arg_name = "user:password",
+        from_str_fn(Args::parse_basic_auth)
+    )]
+    pub basic_auth: Option<(String, String)>,
+
+    /// Bearer token (InfluxDB API token) for push requests
+    #[cfg(feature = "push")]
+    #[argp(option, arg_name = "token")]
+    pub bearer_token: Option<String>,
+
+    /// Extra CA certificates file in PEM format (for https push target)
+    #[cfg(all(feature = "push", feature = "tls"))]
+    #[argp(option, arg_name = "path")]
+    pub ca_cert: Option<PathBuf>,
+
+    /// MQTT topic prefix (ubmsc by default)
+    #[cfg(feature = "mqtt")]
+    #[argp(option, arg_name = "topic")]
+    pub mqtt_topic: Option<String>,
+
+    /// Home Assistant discovery topic prefix (homeassistant by default)
+    #[cfg(feature = "mqtt")]
+    #[argp(option, arg_name = "prefix")]
+    pub mqtt_discovery: Option<String>,
+
+    /// MQTT broker credentials
+    #[cfg(feature = "mqtt")]
+    #[argp(
+        option,
+        arg_name = "user:password",
+        from_str_fn(Args::parse_basic_auth)
+    )]
+    pub mqtt_auth: Option<(String, String)>,
+
+    /// SQLite database to store history of samples while running -e -p -m
+    #[cfg(feature = "history")]
+    #[argp(option, arg_name = "path")]
+    pub history: Option<PathBuf>,
+
+    /// Remove samples from history after days (keep forever by default)
+    #[cfg(feature = "history")]
+    #[argp(option, arg_name = "days", from_str_fn(Args::parse_days))]
+    pub history_retention: Option<Duration>,
+
+    /// Thin out samples in history after days (disabled by default)
+    #[cfg(feature = "history")]
+    #[argp(option, arg_name = "days", from_str_fn(Args::parse_days))]
+    pub history_downsample: Option<Duration>,
+
+    /// Keep single sample per interval when thinning out (3600s by default)
+    #[cfg(feature = "history")]
+    #[argp(option, arg_name = "seconds", from_str_fn(Args::parse_duration))]
+    pub history_interval: Option<Duration>,
+
+    /// Value of device label: name (alias or address, by default) serial
+    #[cfg(feature = "exporter")]
+    #[argp(option, arg_name = "kind", from_str_fn(core::str::FromStr::from_str))]
+    pub device_label: Option<DeviceLabel>,
+
+    /// Consider metrics of device stale after number of failed scrapes in a row
+    #[cfg(feature = "exporter")]
+    #[argp(option, arg_name = "count")]
+    pub stale_after: Option<usize>,
+
+    /// Consider metrics of device stale after seconds since last successful scrape
+    #[cfg(feature = "exporter")]
+    #[argp(option, arg_name = "seconds", from_str_fn(Args::parse_duration))]
+    pub stale_age: Option<Duration>,
+
+    /// Action on stale metrics: drop (by default) nan
+    #[cfg(feature = "exporter")]
+    #[argp(option, arg_name = "action", from_str_fn(core::str::FromStr::from_str))]
+    pub stale_action: Option<StaleAction>,
+
+    #[cfg(any(feature = "history", feature = "monitor"))]
+    #[argp(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Subcommands
+#[cfg(any(feature = "history", feature = "monitor"))]
+#[derive(FromArgs, Debug)]
+#[argp(subcommand)]
+pub enum Command {
+    #[cfg(feature = "history")]
+    History(HistoryQuery),
+    #[cfg(feature = "monitor")]
+    Monitor(Monitor),
+}
+
+/// Print stored history of cell data (or device info with -i) in specified format.
+#[cfg(feature = "history")]
+#[derive(FromArgs, Debug)]
+#[argp(subcommand, name = "history")]
+pub struct HistoryQuery {
+    /// Start of time range in unix seconds
+    #[argp(option, arg_name = "time")]
+    pub from: Option<f64>,
+
+    /// End of time range in unix seconds (now by default)
+    #[argp(option, arg_name = "time")]
+    pub to: Option<f64>,
+
+    /// Length of time range when start is not specified (3600s by default)
+    #[argp(option, arg_name = "seconds", from_str_fn(Args::parse_duration))]
+    pub last: Option<Duration>,
+}
+
+#[cfg(feature = "history")]
+impl HistoryQuery {
+    /// Time range in unix seconds
+    pub fn range(&self, now: f64) -> (f64, f64) {
+        let to = self.to.unwrap_or(now);
+        let from = self
+            .from
+            .unwrap_or_else(|| to - self.last.unwrap_or(DEFAULT_QUERY_RANGE).as_secs_f64());
+        (from, to)
+    }
+}
+
+/// Show live data of devices in full-screen terminal UI.
+#[cfg(feature = "monitor")]
+#[derive(FromArgs, Debug)]
+#[argp(subcommand, name = "monitor")]
+pub struct Monitor {
+    /// Interval between reads in seconds (2s by default)
+    #[argp(option, arg_name = "seconds", from_str_fn(Args::parse_duration))]
+    pub interval: Option<Duration>,
+}
+
+#[cfg(feature = "monitor")]
+impl Monitor {
+    /// Interval between reads
+    pub fn interval(&self) -> Duration {
+        self.interval.unwrap_or(DEFAULT_MONITOR_INTERVAL)
+    }
 }
 
 impl Args {
@@ -133,6 +470,124 @@ impl Args {
         argp::parse_args_or_exit(argp::DEFAULT)
     }
 
+    /// Load configuration file if specified and apply it
+    #[cfg(feature = "config")]
+    pub fn load_config(&mut self) -> crate::Result<Option<Config>> {
+        let config = if let Some(path) = &self.config {
+            Config::load(path)?
+        } else {
+            return Ok(None);
+        };
+
+        #[cfg(feature = "metrics")]
+        {
+            self.label = config
+                .labels
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .chain(self.label.drain(..))
+                .collect();
+            if self.namespace.is_none() {
+                self.namespace = config.metrics.namespace.clone();
+            }
+            self.legacy_names |= config.metrics.legacy_names.unwrap_or_default();
+        }
+
+        self.scan_timeout = self.scan_timeout.or(config.options.scan_timeout);
+        self.request_timeout = self.request_timeout.or(config.options.request_timeout);
+
+        #[cfg(feature = "exporter")]
+        {
+            if self.url.is_none() {
+                self.url = config.exporter.url.clone();
+            }
+            self.scrape_interval = self.scrape_interval.or(config.exporter.scrape_interval);
+            self.encoding = self.encoding.or(config.exporter.encoding);
+            self.device_label = self.device_label.or(config.exporter.device_label);
+            self.stale_after = self.stale_after.or(config.exporter.stale_after);
+            self.stale_age = self.stale_age.or(config.exporter.stale_age);
+            self.stale_action = self.stale_action.or(config.exporter.stale_action);
+        }
+
+        #[cfg(all(feature = "pull", feature = "tls"))]
+        {
+            if self.tls_cert.is_none() {
+                self.tls_cert = config.server.tls_cert.clone();
+            }
+            if self.tls_key.is_none() {
+                self.tls_key = config.server.tls_key.clone();
+            }
+        }
+
+        #[cfg(feature = "push")]
+        {
+            self.push_protocol = self.push_protocol.or(config.push.protocol);
+            if self.push_job.is_none() {
+                self.push_job = config.push.job.clone();
+            }
+            if self.push_instance.is_none() {
+                self.push_instance = config.push.instance.clone();
+            }
+            self.push_per_device |= config.push.per_device.unwrap_or_default();
+            self.push_method = self.push_method.or(config.push.method);
+            self.push_retries = self.push_retries.or(config.push.retries);
+            self.push_backoff = self.push_backoff.or(config.push.backoff);
+            if self.push_queue.is_none() {
+                self.push_queue = config.push.queue.clone();
+            }
+            self.push_queue_size = self.push_queue_size.or(config.push.queue_size);
+            self.header = config
+                .push
+                .headers
+                .iter()
+                .map(|(name, value)| Args::header(name, value).map_err(crate::Error::BadConfig))
+                .chain(self.header.drain(..).map(Ok))
+                .collect::<crate::Result<_>>()?;
+            if self.basic_auth.is_none() && self.bearer_token.is_none() {
+                self.basic_auth = config
+                    .push
+                    .username
+                    .clone()
+                    .map(|user| (user, config.push.password.clone().unwrap_or_default()));
+                self.bearer_token = config.push.bearer_token.clone();
+            }
+        }
+
+        #[cfg(all(feature = "push", feature = "tls"))]
+        if self.ca_cert.is_none() {
+            self.ca_cert = config.push.ca_cert.clone();
+        }
+
+        #[cfg(feature = "mqtt")]
+        {
+            if self.mqtt_topic.is_none() {
+                self.mqtt_topic = config.mqtt.topic.clone();
+            }
+            if self.mqtt_discovery.is_none() {
+                self.mqtt_discovery = config.mqtt.discovery.clone();
+            }
+            if self.mqtt_auth.is_none() {
+                self.mqtt_auth = config
+                    .mqtt
+                    .username
+                    .clone()
+                    .map(|user| (user, config.mqtt.password.clone().unwrap_or_default()));
+            }
+        }
+
+        #[cfg(feature = "history")]
+        {
+            if self.history.is_none() {
+                self.history = config.history.path.clone();
+            }
+            self.history_retention = self.history_retention.or(config.history.retention);
+            self.history_downsample = self.history_downsample.or(config.history.downsample);
+            self.history_interval = self.history_interval.or(config.history.interval);
+        }
+
+        Ok(Some(config))
+    }
+
     /// Get log filter
     #[cfg(feature = "tracing-subscriber")]
     pub fn log_filter(&self) -> Option<EnvFilter> {
@@ -143,21 +598,70 @@ impl Args {
 
     /// Need to exec command
     pub fn has_command(&self) -> bool {
+        #[cfg(feature = "watch")]
+        if self.has_watch() {
+            return false;
+        }
+
+        #[cfg(feature = "history")]
+        if self.history_query().is_some() {
+            return false;
+        }
+
+        #[cfg(feature = "monitor")]
+        if self.monitor().is_some() {
+            return false;
+        }
+
         self.device_info || self.cell_data
     }
 
+    /// History query to run
+    #[cfg(feature = "history")]
+    pub fn history_query(&self) -> Option<&HistoryQuery> {
+        match &self.command {
+            Some(Command::History(query)) => Some(query),
+            _ => None,
+        }
+    }
+
+    /// Terminal UI to run
+    #[cfg(feature = "monitor")]
+    pub fn monitor(&self) -> Option<&Monitor> {
+        match &self.command {
+            Some(Command::Monitor(monitor)) => Some(monitor),
+            _ => None,
+        }
+    }
+
+    /// Need run time-series logging
+    #[cfg(feature = "watch")]
+    pub fn has_watch(&self) -> bool {
+        self.watch.is_some()
+    }
+
     /// Need run exporter server
     #[cfg(feature = "pull")]
     pub fn has_server(&self) -> bool {
-        #[cfg(not(feature = "push"))]
+        #[cfg(all(not(feature = "push"), not(feature = "mqtt")))]
         {
             self.exporter
         }
 
-        #[cfg(feature = "push")]
+        #[cfg(all(feature = "push", not(feature = "mqtt")))]
         {
             self.exporter && !self.push
         }
+
+        #[cfg(all(not(feature = "push"), feature = "mqtt"))]
+        {
+            self.exporter && !self.mqtt
+        }
+
+        #[cfg(all(feature = "push", feature = "mqtt"))]
+        {
+            self.exporter && !self.push && !self.mqtt
+        }
     }
 
     /// Need run exporter client
@@ -166,43 +670,180 @@ impl Args {
         self.push
     }
 
+    /// Need run MQTT publisher
+    #[cfg(feature = "mqtt")]
+    pub fn has_mqtt(&self) -> bool {
+        self.mqtt
+    }
+
+    /// Need run exporter server, push client or MQTT publisher
+    #[cfg(any(feature = "watch", feature = "monitor"))]
+    pub fn has_service(&self) -> bool {
+        #[cfg(feature = "exporter")]
+        if self.exporter {
+            return true;
+        }
+
+        #[cfg(feature = "push")]
+        if self.push {
+            return true;
+        }
+
+        #[cfg(feature = "mqtt")]
+        if self.mqtt {
+            return true;
+        }
+
+        false
+    }
+
     /// Need to do some action
     pub fn has_action(&self) -> bool {
-        #[cfg(all(not(feature = "pull"), not(feature = "push")))]
-        {
-            self.has_command()
+        #[cfg(feature = "pull")]
+        if self.has_server() {
+            return true;
         }
 
-        #[cfg(all(feature = "pull", not(feature = "push")))]
-        {
-            self.has_command() || self.has_server()
+        #[cfg(feature = "push")]
+        if self.has_client() {
+            return true;
         }
 
-        #[cfg(all(not(feature = "pull"), feature = "push"))]
-        {
-            self.has_command() || self.has_client()
+        #[cfg(feature = "mqtt")]
+        if self.has_mqtt() {
+            return true;
         }
 
-        #[cfg(all(feature = "pull", feature = "push"))]
-        {
-            self.has_command() || self.has_server() || self.has_client()
+        #[cfg(feature = "watch")]
+        if self.has_watch() {
+            return true;
+        }
+
+        #[cfg(feature = "history")]
+        if self.history_query().is_some() {
+            return true;
+        }
+
+        #[cfg(feature = "monitor")]
+        if self.monitor().is_some() {
+            return true;
+        }
+
+        self.has_command()
+    }
+
+    /// Metrics options for all devices
+    #[cfg(feature = "metrics")]
+    pub fn metrics_options(&self) -> MetricsOptions {
+        let defaults = MetricsOptions::default();
+        MetricsOptions {
+            namespace: self.namespace.clone().unwrap_or(defaults.namespace),
+            labels: self.label.iter().cloned().collect(),
+            legacy_names: self.legacy_names,
+        }
+    }
+
+    /// Pushgateway instance
+    #[cfg(feature = "push")]
+    pub fn push_instance(&self) -> String {
+        self.push_instance
+            .clone()
+            .unwrap_or_else(|| gethostname::gethostname().to_string_lossy().into_owned())
+    }
+
+    /// Number of retries of failed push
+    #[cfg(feature = "push")]
+    pub fn push_retries(&self) -> usize {
+        self.push_retries.unwrap_or(DEFAULT_PUSH_RETRIES)
+    }
+
+    /// Delay before the first retry of push
+    #[cfg(feature = "push")]
+    pub fn push_backoff(&self) -> Duration {
+        self.push_backoff.unwrap_or(DEFAULT_PUSH_BACKOFF)
+    }
+
+    /// Maximum number of queued samples
+    #[cfg(feature = "push")]
+    pub fn push_queue_size(&self) -> usize {
+        self.push_queue_size.unwrap_or(DEFAULT_PUSH_QUEUE_SIZE)
+    }
+
+    /// Value of device label
+    #[cfg(feature = "exporter")]
+    pub fn device_label(&self) -> DeviceLabel {
+        self.device_label.unwrap_or_default()
+    }
+
+    /// Stale metrics policy
+    #[cfg(feature = "exporter")]
+    pub fn stale_policy(&self) -> StalePolicy {
+        StalePolicy {
+            max_failures: self.stale_after,
+            max_age: self.stale_age,
+            action: self.stale_action.unwrap_or_default(),
+        }
+    }
+
+    /// MQTT topic prefix
+    #[cfg(feature = "mqtt")]
+    pub fn mqtt_topic(&self) -> &str {
+        self.mqtt_topic.as_deref().unwrap_or(DEFAULT_MQTT_TOPIC)
+    }
+
+    /// Home Assistant discovery topic prefix
+    #[cfg(feature = "mqtt")]
+    pub fn mqtt_discovery(&self) -> &str {
+        self.mqtt_discovery
+            .as_deref()
+            .unwrap_or(DEFAULT_MQTT_DISCOVERY)
+    }
+
+    /// Maximum size of output file in bytes
+    #[cfg(feature = "watch")]
+    pub fn rotate_size(&self) -> Option<u64> {
+        self.rotate_size.map(|size| size << 20)
+    }
+
+    /// Number of rotated output files to keep
+    #[cfg(feature = "watch")]
+    pub fn rotate_keep(&self) -> usize {
+        self.rotate_keep.unwrap_or(DEFAULT_ROTATE_KEEP)
+    }
+
+    /// Retention policy of history
+    #[cfg(feature = "history")]
+    pub fn retention_policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            max_age: self.history_retention,
+            downsample_age: self.history_downsample,
+            downsample_interval: self.history_interval.unwrap_or(DEFAULT_DOWNSAMPLE_INTERVAL),
         }
     }
 
+    /// Exporter URL
+    #[cfg(feature = "exporter")]
+    pub fn url(&self) -> Uri {
+        self.url
+            .clone()
+            .unwrap_or_else(|| Uri::from_static(DEFAULT_URL))
+    }
+
+    /// Metrics scraping interval
+    #[cfg(feature = "exporter")]
+    pub fn scrape_interval(&self) -> Duration {
+        self.scrape_interval.unwrap_or(DEFAULT_SCRAPE_INTERVAL)
+    }
+
     #[cfg(feature = "exporter")]
     pub async fn url_addr(&self) -> crate::Result<SocketAddr> {
-        let host = self.url.host().unwrap_or("127.0.0.1");
-        let port = self.url.port_u16().unwrap_or_else(|| {
-            if self
-                .url
-                .scheme()
-                .map(|scheme| scheme == &http::uri::Scheme::HTTPS)
-                .unwrap_or_default()
-            {
-                443
-            } else {
-                80
-            }
+        let url = self.url();
+        let host = url.host().unwrap_or("127.0.0.1");
+        let port = url.port_u16().unwrap_or_else(|| match url.scheme_str() {
+            Some("https") => 443,
+            #[cfg(feature = "mqtt")]
+            Some("mqtt") => DEFAULT_MQTT_PORT,
+            _ => 80,
         });
 
         Ok(if let Ok(addr) = host.parse::<IpAddr>() {
@@ -220,23 +861,25 @@ impl Args {
         if self.protobuf {
             Encoding::Protobuf
         } else {
-            Encoding::Text
+            self.encoding.unwrap_or_default()
         }
     }
 
     /// Client options
     pub fn client_options(&self) -> Options {
+        let defaults = Options::default();
         Options {
-            scan_timeout: self.scan_timeout,
-            request_timeout: self.request_timeout,
+            scan_timeout: self.scan_timeout.unwrap_or(defaults.scan_timeout),
+            request_timeout: self.request_timeout.unwrap_or(defaults.request_timeout),
         }
     }
 
     #[cfg(feature = "exporter")]
-    fn parse_url(s: &str) -> Result<Uri, String> {
+    pub fn parse_url(s: &str) -> Result<Uri, String> {
         s.parse::<Uri>()
             .map_err(|error| error.to_string())
             .and_then(|url| {
+                #[cfg(not(feature = "mqtt"))]
                 if url
                     .scheme_str()
                     .map(|scheme| scheme != "http" && scheme != "https")
@@ -244,6 +887,14 @@ impl Args {
                 {
                     return Err("Only HTTP(s) protocol is supported".to_string());
                 }
+                #[cfg(feature = "mqtt")]
+                if url
+                    .scheme_str()
+                    .map(|scheme| scheme != "http" && scheme != "https" && scheme != "mqtt")
+                    .unwrap_or_default()
+                {
+                    return Err("Only HTTP(s) and MQTT protocols are supported".to_string());
+                }
                 Ok(url)
             })
     }
@@ -254,8 +905,75 @@ impl Args {
             .map_err(|error| format!("Bad timeout value: {error}"))
     }
 
-    fn parse_device_id(s: &str) -> Result<DeviceId, String> {
-        Ok(s.parse().unwrap())
+    #[cfg(feature = "history")]
+    fn parse_days(s: &str) -> Result<Duration, String> {
+        s.parse::<u32>()
+            .map(|days| Duration::from_secs(days as u64 * 86400))
+            .map_err(|error| format!("Bad number of days: {error}"))
+    }
+
+    fn parse_device(s: &str) -> Result<DeviceConfig, String> {
+        Ok(if let Some((alias, device_id)) = s.split_once('=') {
+            if alias.is_empty() {
+                return Err(format!("Empty alias of device: {device_id}"));
+            }
+            DeviceConfig {
+                alias: Some(alias.into()),
+                ..device_id.parse::<DeviceId>().unwrap().into()
+            }
+        } else {
+            s.parse::<DeviceId>().unwrap().into()
+        })
+    }
+
+    #[cfg(feature = "metrics")]
+    fn parse_label(s: &str) -> Result<(String, String), String> {
+        let (name, value) = s.split_once('=').ok_or_else(|| format!("Bad label: {s}"))?;
+        if !is_label_name(name) {
+            return Err(format!("Bad label name: {name}"));
+        }
+        Ok((name.into(), value.into()))
+    }
+
+    #[cfg(feature = "metrics")]
+    fn parse_namespace(s: &str) -> Result<String, String> {
+        if !is_namespace(s) {
+            return Err(format!("Bad namespace: {s}"));
+        }
+        Ok(s.into())
+    }
+
+    #[cfg(feature = "push")]
+    fn parse_header(s: &str) -> Result<(HeaderName, HeaderValue), String> {
+        let (name, value) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Bad header: {s}"))?;
+        Self::header(name, value)
+    }
+
+    /// Parse HTTP header name and value
+    #[cfg(feature = "push")]
+    pub fn header(name: &str, value: &str) -> Result<(HeaderName, HeaderValue), String> {
+        let name = name
+            .trim()
+            .parse::<HeaderName>()
+            .map_err(|_| format!("Bad header name: {name}"))?;
+        let value = value
+            .trim()
+            .parse::<HeaderValue>()
+            .map_err(|_| format!("Bad value of header: {name}"))?;
+        Ok((name, value))
+    }
+
+    #[cfg(any(feature = "push", feature = "mqtt"))]
+    fn parse_basic_auth(s: &str) -> Result<(String, String), String> {
+        let (user, password) = s
+            .split_once(':')
+            .ok_or_else(|| "Bad credentials (user:password expected)".to_string())?;
+        if user.is_empty() {
+            return Err("Empty user name".into());
+        }
+        Ok((user.into(), password.into()))
     }
 
     #[cfg(feature = "tracing-subscriber")]
@@ -264,3 +982,94 @@ impl Args {
             .map_err(|error| format!("Bad tracing filter: {error}"))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "config")]
+    fn parse(args: &[&str]) -> Args {
+        Args::from_args(&["ubmsc"], args).unwrap()
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn load_config() {
+        let dir = std::env::temp_dir().join(format!("ubmsc-args-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ubmsc.toml");
+        std::fs::write(
+            &path,
+            r#"
+labels = { site = "garage" }
+
+[options]
+scan_timeout = 10
+request_timeout = 3
+
+[push]
+job = "battery"
+instance = "garage"
+"#,
+        )
+        .unwrap();
+        let config = path.to_str().unwrap();
+
+        let mut args = parse(&["-C", config]);
+        assert!(args.load_config().unwrap().is_some());
+        assert_eq!(args.scan_timeout, Some(Duration::from_secs(10)));
+        assert_eq!(args.request_timeout, Some(Duration::from_secs(3)));
+
+        // command-line options take precedence over file
+        let mut args = parse(&[
+            "-C",
+            config,
+            "--scan-timeout",
+            "20",
+            #[cfg(feature = "metrics")]
+            "--label",
+            #[cfg(feature = "metrics")]
+            "site=roof",
+            #[cfg(feature = "push")]
+            "--push-job",
+            #[cfg(feature = "push")]
+            "solar",
+        ]);
+        args.load_config().unwrap();
+        assert_eq!(args.scan_timeout, Some(Duration::from_secs(20)));
+        assert_eq!(args.request_timeout, Some(Duration::from_secs(3)));
+
+        #[cfg(feature = "metrics")]
+        assert_eq!(
+            args.label,
+            [
+                ("site".to_string(), "garage".to_string()),
+                ("site".to_string(), "roof".to_string())
+            ]
+        );
+
+        #[cfg(feature = "push")]
+        {
+            assert_eq!(args.push_job.as_deref(), Some("solar"));
+            assert_eq!(args.push_instance.as_deref(), Some("garage"));
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut args = parse(&[]);
+        assert!(args.load_config().unwrap().is_none());
+    }
+
+    #[test]
+    fn parse_device() {
+        let device = Args::parse_device("C8:47:80:12:34:56").unwrap();
+        assert!(matches!(device.id, DeviceId::Mac(_)));
+        assert_eq!(device.alias, None);
+
+        let device = Args::parse_device("ups=C8:47:80:12:34:56").unwrap();
+        assert!(matches!(device.id, DeviceId::Mac(_)));
+        assert_eq!(device.alias.as_deref(), Some("ups"));
+
+        assert!(Args::parse_device("=C8:47:80:12:34:56").is_err());
+    }
+}
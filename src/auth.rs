@@ -0,0 +1,114 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use std::collections::BTreeMap;
+
+/// Server-side authentication
+#[derive(Clone, Debug, Default)]
+pub struct Auth {
+    /// Users with bcrypt-hashed passwords
+    pub users: BTreeMap<String, String>,
+    /// Bearer tokens
+    pub tokens: Vec<String>,
+}
+
+impl Auth {
+    /// Authentication is required
+    pub fn is_enabled(&self) -> bool {
+        !self.users.is_empty() || !self.tokens.is_empty()
+    }
+
+    /// Check credentials from `Authorization` header
+    ///
+    /// Note that checking of bcrypt hash is expensive so it should not be called in async context.
+    pub fn check(&self, authorization: Option<&[u8]>) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+
+        let Some(authorization) = authorization.and_then(|value| core::str::from_utf8(value).ok())
+        else {
+            return false;
+        };
+
+        if let Some(token) = strip_scheme(authorization, "Bearer") {
+            self.tokens
+                .iter()
+                .any(|known| constant_time_eq(known.as_bytes(), token.as_bytes()))
+        } else if let Some(credentials) = strip_scheme(authorization, "Basic") {
+            BASE64
+                .decode(credentials)
+                .ok()
+                .and_then(|credentials| String::from_utf8(credentials).ok())
+                .and_then(|credentials| {
+                    let (user, password) = credentials.split_once(':')?;
+                    let hash = self.users.get(user)?;
+                    bcrypt::verify(password, hash).ok()
+                })
+                .unwrap_or(false)
+        } else {
+            false
+        }
+    }
+}
+
+/// Check that string is a valid bcrypt hash
+#[cfg(feature = "config")]
+pub fn is_bcrypt_hash(hash: &str) -> bool {
+    hash.parse::<bcrypt::HashParts>().is_ok()
+}
+
+fn strip_scheme<'a>(authorization: &'a str, scheme: &str) -> Option<&'a str> {
+    let (name, value) = authorization.split_once(' ')?;
+    name.eq_ignore_ascii_case(scheme).then(|| value.trim())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn auth() -> Auth {
+        Auth {
+            users: [("admin".into(), bcrypt::hash("secret", 4).unwrap())]
+                .into_iter()
+                .collect(),
+            tokens: vec!["t0ken".into()],
+        }
+    }
+
+    #[test]
+    fn disabled() {
+        assert!(Auth::default().check(None));
+    }
+
+    #[test]
+    fn basic() {
+        let auth = auth();
+        let header = |credentials: &str| format!("Basic {}", BASE64.encode(credentials));
+
+        assert!(auth.check(Some(header("admin:secret").as_bytes())));
+        assert!(!auth.check(Some(header("admin:wrong").as_bytes())));
+        assert!(!auth.check(Some(header("guest:secret").as_bytes())));
+        assert!(!auth.check(Some(b"Basic !!!")));
+        assert!(!auth.check(None));
+    }
+
+    #[test]
+    fn bearer() {
+        let auth = auth();
+
+        assert!(auth.check(Some(b"Bearer t0ken")));
+        assert!(auth.check(Some(b"bearer t0ken")));
+        assert!(!auth.check(Some(b"Bearer t0ke")));
+        assert!(!auth.check(Some(b"t0ken")));
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn hash() {
+        assert!(is_bcrypt_hash(&auth().users["admin"]));
+        assert!(!is_bcrypt_hash("secret"));
+    }
+}
@@ -1,11 +1,46 @@
 use crate::{log, CellData, DeviceInfo, Format, Main, Result};
 
+#[cfg(any(
+    feature = "influx",
+    feature = "csv",
+    feature = "json",
+    feature = "watch",
+    feature = "history"
+))]
+use core::time::Duration;
+#[cfg(any(
+    feature = "influx",
+    feature = "csv",
+    feature = "json",
+    feature = "watch",
+    feature = "history"
+))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
 #[cfg(feature = "metrics")]
 use crate::Metrics;
 
 #[cfg(feature = "metrics")]
 use prometheus::{Encoder, Registry, TextEncoder};
 
+#[cfg(feature = "influx")]
+use crate::Influx;
+
+#[cfg(feature = "csv")]
+use crate::CsvColumns;
+
+#[cfg(feature = "table")]
+use crate::{write_table, TableDevice};
+
+#[cfg(all(feature = "units", feature = "serde"))]
+use crate::WithUnits;
+
+#[cfg(any(feature = "influx", feature = "json"))]
+use std::io::Write;
+
+#[cfg(feature = "table")]
+use std::io::IsTerminal;
+
 #[derive(Clone, Debug, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Outputs {
@@ -15,6 +50,66 @@ pub struct Outputs {
     pub cell_data: Vec<CellData>,
 }
 
+/// Outputs with values of cell data annotated by units
+#[cfg(all(feature = "units", feature = "serde"))]
+#[derive(Debug, serde::Serialize)]
+pub struct UnitOutputs<'a> {
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub device_info: &'a [DeviceInfo],
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cell_data: Vec<WithUnits<'a>>,
+}
+
+#[cfg(all(feature = "units", feature = "serde"))]
+impl<'a> From<&'a Outputs> for UnitOutputs<'a> {
+    fn from(outputs: &'a Outputs) -> Self {
+        Self {
+            device_info: &outputs.device_info,
+            cell_data: outputs.cell_data.iter().map(CellData::with_units).collect(),
+        }
+    }
+}
+
+/// Time-series record of device (single line of NDJSON)
+#[cfg(any(
+    feature = "json",
+    all(feature = "watch", any(feature = "cbor", feature = "msgpack"))
+))]
+#[derive(Debug, serde::Serialize)]
+pub struct Record<'a> {
+    /// Unix time in seconds
+    pub timestamp: f64,
+    pub device: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_info: Option<&'a DeviceInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cell_data: Option<&'a CellData>,
+}
+
+#[cfg(feature = "json")]
+impl Record<'_> {
+    /// Write record followed by newline
+    pub fn write(&self, output: &mut dyn Write) -> Result<()> {
+        serde_json::to_writer(&mut *output, self)?;
+        writeln!(output)?;
+        Ok(())
+    }
+}
+
+/// Time since unix epoch
+#[cfg(any(
+    feature = "influx",
+    feature = "csv",
+    feature = "json",
+    feature = "watch",
+    feature = "history"
+))]
+pub fn unix_time() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
 impl Main {
     pub async fn run_commands(&self) -> Result<()> {
         let mut outputs = Outputs::default();
@@ -22,27 +117,42 @@ impl Main {
         #[cfg(feature = "metrics")]
         let registry = Registry::new();
 
-        for client in &self.clients {
+        #[cfg(feature = "metrics")]
+        let metrics_options = self.metrics_options();
+
+        #[cfg(any(feature = "influx", feature = "json"))]
+        let mut lines = Vec::new();
+
+        #[cfg(feature = "csv")]
+        let mut rows = Vec::new();
+
+        #[cfg(feature = "table")]
+        let mut table = Vec::new();
+
+        for (client, device) in self.clients.iter().zip(self.devices.iter()) {
+            let device_name = device.name();
+
             #[cfg(feature = "metrics")]
-            let metrics = Metrics::new(client.device_id())?;
+            let metrics = Metrics::new(&device_name, &device.metrics_options(&metrics_options))?;
             #[cfg(feature = "metrics")]
             metrics.register(Some(&registry))?;
 
-            let device_id = client.device_id();
-
-            log::info!("Connect to: '{device_id}'");
+            log::info!("Connect to: '{device_name}'");
 
             if let Err(error) = client.open().await {
                 log::error!("Error while connecting to device: {error}");
             } else {
+                let mut device_info = None;
+                let mut cell_data = None;
+
                 if self.device_info {
                     match client.device_info().await {
-                        Ok(device_info) => {
+                        Ok(data) => {
                             #[cfg(feature = "metrics")]
                             if matches!(self.format, Format::Metrics) {
-                                metrics.scrape(&device_info);
+                                metrics.scrape(&data);
                             }
-                            outputs.device_info.push(device_info);
+                            device_info = Some(data);
                         }
                         Err(error) => log::error!("Error while fetching device info: {error}"),
                     }
@@ -50,18 +160,84 @@ impl Main {
 
                 if self.cell_data {
                     match client.cell_data().await {
-                        Ok(cell_data) => {
+                        Ok(data) => {
                             #[cfg(feature = "metrics")]
                             if matches!(self.format, Format::Metrics) {
-                                metrics.scrape(&cell_data);
+                                metrics.scrape(&data);
                             }
-                            outputs.cell_data.push(cell_data);
+                            cell_data = Some(data);
                         }
                         Err(error) => log::error!("Error while fetching cell data: {error}"),
                     }
                 }
 
-                log::info!("Disconnect from: '{device_id}'");
+                #[cfg(any(feature = "influx", feature = "csv", feature = "json"))]
+                let timestamp = unix_time();
+
+                #[cfg(feature = "influx")]
+                if matches!(self.format, Format::Influx) {
+                    let serial = device_info
+                        .as_ref()
+                        .map(|device_info| device_info.serial_number.as_str())
+                        .unwrap_or_default();
+
+                    #[cfg_attr(not(feature = "metrics"), allow(unused_mut))]
+                    let mut tags = vec![("device", device_name.as_str()), ("serial", serial)];
+                    #[cfg(feature = "metrics")]
+                    let labels = device.metrics_options(&metrics_options).labels;
+                    #[cfg(feature = "metrics")]
+                    tags.extend(
+                        labels
+                            .iter()
+                            .map(|(name, value)| (name.as_str(), value.as_str())),
+                    );
+
+                    let timestamp = timestamp.as_nanos() as i64;
+                    if let Some(device_info) = &device_info {
+                        device_info.write_influx(&tags, timestamp, &mut lines)?;
+                    }
+                    if let Some(cell_data) = &cell_data {
+                        cell_data.write_influx(&tags, timestamp, &mut lines)?;
+                    }
+                }
+
+                #[cfg(feature = "json")]
+                if matches!(self.format, Format::NdJson)
+                    && (device_info.is_some() || cell_data.is_some())
+                {
+                    Record {
+                        timestamp: timestamp.as_secs_f64(),
+                        device: &device_name,
+                        device_info: device_info.as_ref(),
+                        cell_data: cell_data.as_ref(),
+                    }
+                    .write(&mut lines)?;
+                }
+
+                #[cfg(feature = "csv")]
+                if matches!(self.format, Format::Csv) && cell_data.is_some() {
+                    rows.push((
+                        timestamp.as_secs_f64(),
+                        device_name.clone(),
+                        outputs.cell_data.len(),
+                    ));
+                }
+
+                #[cfg(feature = "table")]
+                if matches!(self.format, Format::Table)
+                    && (device_info.is_some() || cell_data.is_some())
+                {
+                    table.push((
+                        device_name.clone(),
+                        device_info.is_some().then_some(outputs.device_info.len()),
+                        cell_data.is_some().then_some(outputs.cell_data.len()),
+                    ));
+                }
+
+                outputs.device_info.extend(device_info);
+                outputs.cell_data.extend(cell_data);
+
+                log::info!("Disconnect from: '{device_name}'");
 
                 if let Err(error) = client.close().await {
                     log::error!("Error while disconnecting from device: {error}");
@@ -70,6 +246,16 @@ impl Main {
         }
 
         let mut output = std::io::stdout();
+
+        #[cfg(all(feature = "units", feature = "serde"))]
+        if self.units {
+            self.format
+                .format_value(&UnitOutputs::from(&outputs), &mut output)?;
+        } else {
+            self.format.format_value(&outputs, &mut output)?;
+        }
+
+        #[cfg(not(all(feature = "units", feature = "serde")))]
         self.format.format_value(&outputs, &mut output)?;
 
         #[cfg(feature = "metrics")]
@@ -78,6 +264,38 @@ impl Main {
             encoder.encode(&registry.gather(), &mut output)?;
         }
 
+        #[cfg(any(feature = "influx", feature = "json"))]
+        output.write_all(&lines)?;
+
+        #[cfg(feature = "csv")]
+        if matches!(self.format, Format::Csv) {
+            let columns = CsvColumns::new(&outputs.cell_data);
+            columns.write_header(&mut output)?;
+            for (timestamp, device_name, index) in rows {
+                columns.write_record(
+                    timestamp,
+                    &device_name,
+                    &outputs.cell_data[index],
+                    &mut output,
+                )?;
+            }
+        }
+
+        #[cfg(feature = "table")]
+        if matches!(self.format, Format::Table) {
+            let devices: Vec<_> = table
+                .iter()
+                .map(|(name, device_info, cell_data)| TableDevice {
+                    name,
+                    device_info: device_info.map(|index| &outputs.device_info[index]),
+                    cell_data: cell_data.map(|index| &outputs.cell_data[index]),
+                })
+                .collect();
+            // colorize output on terminals (see https://no-color.org)
+            let color = output.is_terminal() && std::env::var_os("NO_COLOR").is_none();
+            write_table(&devices, color, &mut output)?;
+        }
+
         Ok(())
     }
 }
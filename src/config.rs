@@ -0,0 +1,778 @@
+use crate::{DeviceId, Options};
+use core::time::Duration;
+use std::collections::BTreeMap;
+
+#[cfg(feature = "metrics")]
+use crate::MetricsOptions;
+
+#[cfg(feature = "config")]
+use crate::{Error, Result};
+#[cfg(feature = "config")]
+use serde::Deserialize;
+#[cfg(feature = "config")]
+use std::path::Path;
+
+#[cfg(all(feature = "config", feature = "exporter"))]
+use crate::{DeviceLabel, Encoding, StaleAction};
+#[cfg(all(feature = "config", feature = "exporter"))]
+use hyper::Uri;
+
+#[cfg(all(feature = "config", feature = "push"))]
+use crate::{PushMethod, PushProtocol};
+
+#[cfg(all(feature = "config", feature = "pull", feature = "auth"))]
+use crate::auth::{is_bcrypt_hash, Auth};
+#[cfg(all(
+    feature = "config",
+    any(
+        feature = "push",
+        feature = "history",
+        all(feature = "pull", feature = "tls")
+    )
+))]
+use std::path::PathBuf;
+
+/// Configuration file
+#[cfg(feature = "config")]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Global client options
+    pub options: OptionsConfig,
+    /// Metrics options
+    #[cfg(feature = "metrics")]
+    pub metrics: MetricsConfig,
+    /// Exporter options
+    #[cfg(feature = "exporter")]
+    pub exporter: ExporterConfig,
+    /// Exporter server options
+    #[cfg(feature = "pull")]
+    pub server: ServerConfig,
+    /// Push client options
+    #[cfg(feature = "push")]
+    pub push: PushConfig,
+    /// MQTT publisher options
+    #[cfg(feature = "mqtt")]
+    pub mqtt: MqttConfig,
+    /// History store options
+    #[cfg(feature = "history")]
+    pub history: HistoryConfig,
+    /// Extra labels for metrics of all devices
+    pub labels: BTreeMap<String, String>,
+    /// Devices to interact with
+    pub devices: Vec<DeviceConfig>,
+}
+
+/// Global client options
+#[cfg(feature = "config")]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OptionsConfig {
+    /// Bluetooth scanning timeout in seconds
+    #[serde(deserialize_with = "de::seconds")]
+    pub scan_timeout: Option<Duration>,
+    /// Bluetooth request timeout in seconds
+    #[serde(deserialize_with = "de::seconds")]
+    pub request_timeout: Option<Duration>,
+}
+
+/// Metrics options
+#[cfg(all(feature = "config", feature = "metrics"))]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Prefix of metric names (`ubmsc` by default, empty to disable)
+    pub namespace: Option<String>,
+    /// Use legacy metric names (without namespace, units and `_total` suffixes)
+    pub legacy_names: Option<bool>,
+}
+
+/// Exporter options
+#[cfg(all(feature = "config", feature = "exporter"))]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExporterConfig {
+    /// URL to listen/connect
+    #[serde(deserialize_with = "de::url")]
+    pub url: Option<Uri>,
+    /// Metrics scraping interval in seconds
+    #[serde(deserialize_with = "de::seconds")]
+    pub scrape_interval: Option<Duration>,
+    /// Preferred encoding: text, protobuf or openmetrics
+    #[serde(deserialize_with = "de::parse")]
+    pub encoding: Option<Encoding>,
+    /// Value of device label: name or serial
+    #[serde(deserialize_with = "de::parse")]
+    pub device_label: Option<DeviceLabel>,
+    /// Consider metrics of device stale after number of failed scrapes in a row
+    pub stale_after: Option<usize>,
+    /// Consider metrics of device stale after seconds since last successful scrape
+    #[serde(deserialize_with = "de::seconds")]
+    pub stale_age: Option<Duration>,
+    /// Action on stale metrics: drop or nan
+    #[serde(deserialize_with = "de::parse")]
+    pub stale_action: Option<StaleAction>,
+}
+
+/// Exporter server options
+#[cfg(all(feature = "config", feature = "pull"))]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Users allowed to access with bcrypt-hashed passwords
+    #[cfg(feature = "auth")]
+    pub users: BTreeMap<String, String>,
+    /// Bearer tokens allowed to access
+    #[cfg(feature = "auth")]
+    pub tokens: Vec<String>,
+    /// TLS certificate chain file in PEM format
+    #[cfg(feature = "tls")]
+    pub tls_cert: Option<PathBuf>,
+    /// TLS private key file in PEM format
+    #[cfg(feature = "tls")]
+    pub tls_key: Option<PathBuf>,
+}
+
+/// Push client options
+#[cfg(all(feature = "config", feature = "push"))]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PushConfig {
+    /// Push protocol: exposition, remote-write or influx
+    #[serde(deserialize_with = "de::parse")]
+    pub protocol: Option<PushProtocol>,
+    /// Pushgateway job
+    pub job: Option<String>,
+    /// Pushgateway instance (host name by default)
+    pub instance: Option<String>,
+    /// Push metrics of each device to separate Pushgateway group
+    pub per_device: Option<bool>,
+    /// HTTP method to push metrics: put or post
+    #[serde(deserialize_with = "de::parse")]
+    pub method: Option<PushMethod>,
+    /// Number of retries of failed push
+    pub retries: Option<usize>,
+    /// Delay before the first retry of push in seconds
+    #[serde(deserialize_with = "de::seconds")]
+    pub backoff: Option<Duration>,
+    /// Directory to queue metrics which failed to push
+    pub queue: Option<PathBuf>,
+    /// Maximum number of queued samples
+    pub queue_size: Option<usize>,
+    /// User name for basic auth
+    pub username: Option<String>,
+    /// Password for basic auth
+    pub password: Option<String>,
+    /// Bearer token
+    pub bearer_token: Option<String>,
+    /// Extra HTTP headers
+    pub headers: BTreeMap<String, String>,
+    /// Extra CA certificates file in PEM format
+    #[cfg(feature = "tls")]
+    pub ca_cert: Option<PathBuf>,
+}
+
+/// MQTT publisher options
+#[cfg(all(feature = "config", feature = "mqtt"))]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+    /// Topic prefix
+    pub topic: Option<String>,
+    /// Home Assistant discovery topic prefix
+    pub discovery: Option<String>,
+    /// User name of broker
+    pub username: Option<String>,
+    /// Password of broker
+    pub password: Option<String>,
+}
+
+/// History store options
+#[cfg(all(feature = "config", feature = "history"))]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// SQLite database file
+    pub path: Option<PathBuf>,
+    /// Remove samples after days
+    #[serde(deserialize_with = "de::days")]
+    pub retention: Option<Duration>,
+    /// Thin out samples after days
+    #[serde(deserialize_with = "de::days")]
+    pub downsample: Option<Duration>,
+    /// Keep single sample per interval in seconds when thinning out
+    #[serde(deserialize_with = "de::seconds")]
+    pub interval: Option<Duration>,
+}
+
+#[cfg(all(feature = "config", feature = "pull", feature = "auth"))]
+impl ServerConfig {
+    /// Server-side authentication
+    pub fn auth(&self) -> Auth {
+        Auth {
+            users: self.users.clone(),
+            tokens: self.tokens.clone(),
+        }
+    }
+}
+
+/// Device options
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "config", derive(Deserialize))]
+#[cfg_attr(feature = "config", serde(deny_unknown_fields))]
+pub struct DeviceConfig {
+    /// Device address or name
+    pub id: DeviceId,
+    /// Human-readable device alias
+    #[cfg_attr(feature = "config", serde(default))]
+    pub alias: Option<String>,
+    /// Bluetooth adapter to use (the first one by default)
+    #[cfg_attr(feature = "config", serde(default))]
+    pub adapter: Option<String>,
+    /// Custom labels
+    #[cfg_attr(feature = "config", serde(default))]
+    pub labels: BTreeMap<String, String>,
+    /// Bluetooth scanning timeout in seconds
+    #[cfg_attr(feature = "config", serde(default, deserialize_with = "de::seconds"))]
+    pub scan_timeout: Option<Duration>,
+    /// Bluetooth request timeout in seconds
+    #[cfg_attr(feature = "config", serde(default, deserialize_with = "de::seconds"))]
+    pub request_timeout: Option<Duration>,
+}
+
+impl From<DeviceId> for DeviceConfig {
+    fn from(id: DeviceId) -> Self {
+        Self {
+            id,
+            alias: None,
+            adapter: None,
+            labels: BTreeMap::default(),
+            scan_timeout: None,
+            request_timeout: None,
+        }
+    }
+}
+
+impl DeviceConfig {
+    /// Client options for device using global options as defaults
+    pub fn options(&self, options: &Options) -> Options {
+        Options {
+            scan_timeout: self.scan_timeout.unwrap_or(options.scan_timeout),
+            request_timeout: self.request_timeout.unwrap_or(options.request_timeout),
+        }
+    }
+
+    /// Device name (alias or identifier)
+    pub fn name(&self) -> String {
+        self.alias.clone().unwrap_or_else(|| self.id.to_string())
+    }
+
+    /// Metrics options for device using global options as defaults
+    #[cfg(feature = "metrics")]
+    pub fn metrics_options(&self, options: &MetricsOptions) -> MetricsOptions {
+        let mut options = options.clone();
+        options.labels.extend(
+            self.labels
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+        options
+    }
+
+    /// Check that device matches identifier or alias
+    pub fn matches(&self, device_id: &DeviceId) -> bool {
+        &self.id == device_id
+            || matches!((device_id, &self.alias), (DeviceId::Name(name), Some(alias)) if name == alias)
+    }
+}
+
+#[cfg(feature = "config")]
+impl Config {
+    /// Load configuration from file
+    ///
+    /// The format is determined by extension: `.toml`, `.yaml`/`.yml` or `.json`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path)?;
+
+        let config: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => serde_toml::from_str(&data)?,
+            #[cfg(feature = "yaml")]
+            Some("yaml" | "yml") => serde_yaml::from_str(&data)?,
+            #[cfg(feature = "json")]
+            Some("json") => serde_json::from_str(&data)?,
+            _ => {
+                return Err(Error::BadConfig(format!(
+                    "Unsupported format of '{}'",
+                    path.display()
+                )))
+            }
+        };
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Check configuration consistency
+    pub fn validate(&self) -> Result<()> {
+        #[cfg(feature = "metrics")]
+        if let Some(namespace) = &self.metrics.namespace {
+            if !is_namespace(namespace) {
+                return Err(Error::BadConfig(format!("Invalid namespace '{namespace}'")));
+            }
+        }
+
+        #[cfg(all(feature = "pull", feature = "auth"))]
+        for (user, hash) in &self.server.users {
+            if user.is_empty() || user.contains(':') {
+                return Err(Error::BadConfig(format!("Invalid user name '{user}'")));
+            }
+            if !is_bcrypt_hash(hash) {
+                return Err(Error::BadConfig(format!(
+                    "Password of user '{user}' is not a bcrypt hash"
+                )));
+            }
+        }
+
+        #[cfg(all(feature = "pull", feature = "auth"))]
+        if self.server.tokens.iter().any(|token| token.is_empty()) {
+            return Err(Error::BadConfig("Empty bearer token".into()));
+        }
+
+        #[cfg(feature = "push")]
+        {
+            let push = &self.push;
+            if push.job.as_ref().is_some_and(|job| job.is_empty()) {
+                return Err(Error::BadConfig("Empty Pushgateway job".into()));
+            }
+            if push.queue_size == Some(0) {
+                return Err(Error::BadConfig("Zero push queue size".into()));
+            }
+            if push.username.is_none() && push.password.is_some() {
+                return Err(Error::BadConfig("Push password without username".into()));
+            }
+            if push
+                .username
+                .as_ref()
+                .is_some_and(|user| user.is_empty() || user.contains(':'))
+            {
+                return Err(Error::BadConfig("Invalid push username".into()));
+            }
+            if push.username.is_some() && push.bearer_token.is_some() {
+                return Err(Error::BadConfig(
+                    "Push basic auth and bearer token cannot be used together".into(),
+                ));
+            }
+            for (name, value) in &push.headers {
+                crate::Args::header(name, value).map_err(Error::BadConfig)?;
+            }
+        }
+
+        #[cfg(feature = "mqtt")]
+        {
+            let mqtt = &self.mqtt;
+            for topic in [&mqtt.topic, &mqtt.discovery].into_iter().flatten() {
+                if !crate::mqtt::is_topic(topic) {
+                    return Err(Error::BadConfig(format!("Invalid MQTT topic '{topic}'")));
+                }
+            }
+            if mqtt.username.is_none() && mqtt.password.is_some() {
+                return Err(Error::BadConfig("MQTT password without username".into()));
+            }
+            if mqtt.username.as_ref().is_some_and(|user| user.is_empty()) {
+                return Err(Error::BadConfig("Empty MQTT username".into()));
+            }
+        }
+
+        for name in self.labels.keys() {
+            if !is_label_name(name) {
+                return Err(Error::BadConfig(format!("Invalid label '{name}'")));
+            }
+        }
+
+        for (index, device) in self.devices.iter().enumerate() {
+            let others = &self.devices[..index];
+
+            if others.iter().any(|other| other.id == device.id) {
+                return Err(Error::BadConfig(format!(
+                    "Duplicate device '{}'",
+                    device.id
+                )));
+            }
+
+            if let Some(alias) = &device.alias {
+                if alias.is_empty() {
+                    return Err(Error::BadConfig(format!(
+                        "Empty alias of device '{}'",
+                        device.id
+                    )));
+                }
+                if others
+                    .iter()
+                    .any(|other| other.alias.as_ref() == Some(alias))
+                {
+                    return Err(Error::BadConfig(format!("Duplicate alias '{alias}'")));
+                }
+            }
+
+            if let Some(adapter) = &device.adapter {
+                if adapter.is_empty() {
+                    return Err(Error::BadConfig(format!(
+                        "Empty adapter of device '{}'",
+                        device.id
+                    )));
+                }
+            }
+
+            for name in device.labels.keys() {
+                if !is_label_name(name) {
+                    return Err(Error::BadConfig(format!(
+                        "Invalid label '{name}' of device '{}'",
+                        device.id
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get devices to use
+    ///
+    /// When devices passed via command-line only those will be used with options from config.
+    pub fn devices(&self, devices: &[DeviceConfig]) -> Vec<DeviceConfig> {
+        if devices.is_empty() {
+            return self.devices.clone();
+        }
+
+        devices
+            .iter()
+            .map(|device| {
+                if let Some(config) = self
+                    .devices
+                    .iter()
+                    .find(|config| config.matches(&device.id))
+                {
+                    DeviceConfig {
+                        alias: device.alias.clone().or_else(|| config.alias.clone()),
+                        ..config.clone()
+                    }
+                } else {
+                    device.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Check label name according to Prometheus data model
+#[cfg(any(feature = "config", feature = "metrics"))]
+pub fn is_label_name(name: &str) -> bool {
+    /// Labels used by exporter itself (device and cell identifiers, error
+    /// kinds, device info fields and push grouping labels)
+    const RESERVED: &[&str] = &[
+        "device",
+        "cell",
+        "kind",
+        "device_model",
+        "hardware_version",
+        "software_version",
+        "serial_number",
+        "manufacturing_date",
+        "device_name",
+        "job",
+        "instance",
+        "ubmsc_device",
+    ];
+
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .map(|c| c.is_ascii_alphabetic() || c == '_')
+        .unwrap_or(false)
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with("__")
+        && !RESERVED.contains(&name)
+}
+
+/// Check metrics namespace according to Prometheus data model
+#[cfg(feature = "metrics")]
+pub fn is_namespace(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .map(|c| c.is_ascii_alphabetic() || c == '_')
+        .unwrap_or(true)
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(feature = "config")]
+mod de {
+    use core::time::Duration;
+    use serde::{Deserialize, Deserializer};
+
+    #[cfg(feature = "exporter")]
+    use core::{fmt::Display, str::FromStr};
+    #[cfg(feature = "exporter")]
+    use serde::de::Error;
+
+    pub fn seconds<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<u32>::deserialize(deserializer)?
+            .map(|seconds| Duration::from_secs(seconds as _)))
+    }
+
+    #[cfg(feature = "history")]
+    pub fn days<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<u32>::deserialize(deserializer)?
+            .map(|days| Duration::from_secs(days as u64 * 86400)))
+    }
+
+    #[cfg(feature = "exporter")]
+    pub fn parse<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr,
+        T::Err: Display,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| s.parse().map_err(D::Error::custom))
+            .transpose()
+    }
+
+    #[cfg(feature = "exporter")]
+    pub fn url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<hyper::Uri>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| crate::Args::parse_url(&s).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(all(test, feature = "config"))]
+mod test {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    const TOML: &str = r#"
+labels = { site = "garage" }
+
+[options]
+scan_timeout = 10
+
+[[devices]]
+id = "C8:47:80:12:34:56"
+alias = "ups"
+labels = { rack = "a" }
+
+[[devices]]
+id = "solar"
+request_timeout = 3
+"#;
+
+    #[cfg(feature = "yaml")]
+    const YAML: &str = r#"
+labels:
+  site: garage
+options:
+  scan_timeout: 10
+devices:
+  - id: C8:47:80:12:34:56
+    alias: ups
+    labels:
+      rack: a
+  - id: solar
+    request_timeout: 3
+"#;
+
+    #[cfg(feature = "json")]
+    const JSON: &str = r#"{
+  "labels": { "site": "garage" },
+  "options": { "scan_timeout": 10 },
+  "devices": [
+    { "id": "C8:47:80:12:34:56", "alias": "ups", "labels": { "rack": "a" } },
+    { "id": "solar", "request_timeout": 3 }
+  ]
+}"#;
+
+    fn parse(data: &str) -> core::result::Result<Config, serde_toml::de::Error> {
+        serde_toml::from_str(data)
+    }
+
+    fn device(id: &str, alias: Option<&str>) -> DeviceConfig {
+        DeviceConfig {
+            alias: alias.map(Into::into),
+            ..id.parse::<DeviceId>().unwrap().into()
+        }
+    }
+
+    #[test]
+    fn load() {
+        let dir = std::env::temp_dir().join(format!("ubmsc-config-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+
+        let files: &[(&str, &str)] = &[
+            ("ubmsc.toml", TOML),
+            #[cfg(feature = "yaml")]
+            ("ubmsc.yaml", YAML),
+            #[cfg(feature = "json")]
+            ("ubmsc.json", JSON),
+        ];
+
+        for &(name, data) in files {
+            let path = dir.join(name);
+            write(&path, data).unwrap();
+
+            let config = Config::load(&path).unwrap();
+            assert_eq!(config.labels["site"], "garage", "{name}");
+            assert_eq!(
+                config.options.scan_timeout,
+                Some(Duration::from_secs(10)),
+                "{name}"
+            );
+            assert_eq!(config.options.request_timeout, None, "{name}");
+            assert_eq!(config.devices.len(), 2, "{name}");
+            assert_eq!(config.devices[0].name(), "ups", "{name}");
+            assert!(matches!(config.devices[0].id, DeviceId::Mac(_)), "{name}");
+            assert_eq!(config.devices[0].labels["rack"], "a", "{name}");
+            assert_eq!(config.devices[1].name(), "solar", "{name}");
+            assert_eq!(
+                config.devices[1].request_timeout,
+                Some(Duration::from_secs(3)),
+                "{name}"
+            );
+        }
+
+        let path = dir.join("ubmsc.ini");
+        write(&path, TOML).unwrap();
+        assert!(matches!(Config::load(&path), Err(Error::BadConfig(_))));
+
+        let path = dir.join("invalid.toml");
+        write(&path, "labels = { device = \"ups\" }").unwrap();
+        assert!(matches!(Config::load(&path), Err(Error::BadConfig(_))));
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn validate() {
+        parse(TOML).unwrap().validate().unwrap();
+
+        for (data, error) in [
+            ("labels = { 1rack = \"a\" }", "Invalid label '1rack'"),
+            ("labels = { __name = \"a\" }", "Invalid label '__name'"),
+            ("labels = { cell = \"a\" }", "Invalid label 'cell'"),
+            (
+                "[[devices]]\nid = \"a\"\n[[devices]]\nid = \"a\"",
+                "Duplicate device 'a'",
+            ),
+            (
+                "[[devices]]\nid = \"a\"\nalias = \"ups\"\n[[devices]]\nid = \"b\"\nalias = \"ups\"",
+                "Duplicate alias 'ups'",
+            ),
+            (
+                "[[devices]]\nid = \"a\"\nalias = \"\"",
+                "Empty alias of device 'a'",
+            ),
+            (
+                "[[devices]]\nid = \"a\"\nadapter = \"\"",
+                "Empty adapter of device 'a'",
+            ),
+            (
+                "[[devices]]\nid = \"a\"\nlabels = { kind = \"b\" }",
+                "Invalid label 'kind' of device 'a'",
+            ),
+        ] {
+            match parse(data).unwrap().validate() {
+                Err(Error::BadConfig(message)) => assert_eq!(message, error),
+                result => panic!("Unexpected result of {data:?}: {result:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn devices() {
+        let config = parse(TOML).unwrap();
+
+        let devices = config.devices(&[]);
+        assert_eq!(
+            devices.iter().map(DeviceConfig::name).collect::<Vec<_>>(),
+            ["ups", "solar"]
+        );
+
+        let devices = config.devices(&[
+            device("ups", None),
+            device("solar", Some("roof")),
+            device("C8:47:80:00:00:01", None),
+        ]);
+        assert_eq!(
+            devices.iter().map(DeviceConfig::name).collect::<Vec<_>>(),
+            ["ups", "roof", "C8:47:80:00:00:01"]
+        );
+        // options of matched device are taken from config
+        assert!(matches!(devices[0].id, DeviceId::Mac(_)));
+        assert_eq!(devices[0].labels["rack"], "a");
+        assert_eq!(devices[1].request_timeout, Some(Duration::from_secs(3)));
+        assert!(devices[2].labels.is_empty());
+    }
+
+    #[test]
+    fn label_name() {
+        for name in ["rack", "_rack", "rack_1", "Site"] {
+            assert!(is_label_name(name), "{name}");
+        }
+        for name in ["", "1rack", "rack-1", "__rack", "device", "cell", "job"] {
+            assert!(!is_label_name(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn deserializers() {
+        let config = parse(
+            r#"
+[options]
+scan_timeout = 5
+request_timeout = 2
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.options.scan_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(config.options.request_timeout, Some(Duration::from_secs(2)));
+
+        assert!(parse("[options]\nscan_timeout = -1").is_err());
+
+        #[cfg(feature = "history")]
+        {
+            let config = parse("[history]\nretention = 30\ninterval = 600").unwrap();
+            assert_eq!(
+                config.history.retention,
+                Some(Duration::from_secs(30 * 86400))
+            );
+            assert_eq!(config.history.downsample, None);
+            assert_eq!(config.history.interval, Some(Duration::from_secs(600)));
+        }
+
+        #[cfg(feature = "exporter")]
+        {
+            let config = parse(
+                r#"
+[exporter]
+url = "http://0.0.0.0:9000/metrics"
+stale_action = "nan"
+"#,
+            )
+            .unwrap();
+            assert_eq!(
+                config.exporter.url,
+                Some(Uri::from_static("http://0.0.0.0:9000/metrics"))
+            );
+            assert_eq!(config.exporter.stale_action, Some(StaleAction::Nan));
+
+            assert!(parse("[exporter]\nurl = \"ftp://localhost/\"").is_err());
+            assert!(parse("[exporter]\nstale_action = \"keep\"").is_err());
+        }
+    }
+}
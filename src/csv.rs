@@ -0,0 +1,163 @@
+use crate::{CellData, FieldKind, FieldValue};
+use std::io::{Result, Write};
+
+/// Layout of cell data CSV records
+///
+/// Columns follow field descriptors: single values first, then per-cell
+/// values. The number of per-cell columns is fixed until layout is widened to
+/// fit more values, so header stays the same for all records written with the
+/// same layout. Missing values are written as empty fields and extra values
+/// are dropped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsvColumns {
+    /// Number of columns per field (in order of descriptors)
+    cells: Vec<usize>,
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        Self {
+            cells: vec![0; CellData::FIELDS.len()],
+        }
+    }
+}
+
+impl CsvColumns {
+    /// Create layout which fits all cell data
+    pub fn new<'a>(cell_data: impl IntoIterator<Item = &'a CellData>) -> Self {
+        let mut columns = Self::default();
+        for cell_data in cell_data {
+            columns.fit(cell_data);
+        }
+        columns
+    }
+
+    /// Number of per-cell columns of field
+    pub fn cells(&self, name: &str) -> usize {
+        CellData::FIELDS
+            .iter()
+            .position(|field| field.name == name)
+            .map_or(0, |index| self.cells[index])
+    }
+
+    /// Widen layout to fit cell data, returns true when layout changed
+    pub fn fit(&mut self, cell_data: &CellData) -> bool {
+        let mut changed = false;
+        for (count, (_, value)) in self.cells.iter_mut().zip(cell_data.values()) {
+            if value.cells() > *count {
+                *count = value.cells();
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Write header line
+    pub fn write_header(&self, output: &mut dyn Write) -> Result<()> {
+        let mut line = vec!["timestamp".to_string(), "device".to_string()];
+        line.extend(
+            CellData::FIELDS
+                .iter()
+                .filter(|field| field.kind != FieldKind::Cells)
+                .map(|field| field.name.to_string()),
+        );
+        for (field, count) in CellData::FIELDS.iter().zip(&self.cells) {
+            line.extend((0..*count).map(|index| format!("{}_{index}", field.name)));
+        }
+        writeln!(output, "{}", line.join(","))
+    }
+
+    /// Write record line with timestamp in seconds
+    pub fn write_record(
+        &self,
+        timestamp: f64,
+        device: &str,
+        cell_data: &CellData,
+        output: &mut dyn Write,
+    ) -> Result<()> {
+        let mut line = vec![format!("{timestamp:.3}"), quote(device)];
+        line.extend(
+            cell_data
+                .values()
+                .filter(|(field, _)| field.kind != FieldKind::Cells)
+                .map(|(_, value)| number(value)),
+        );
+        for ((_, value), count) in cell_data.values().zip(&self.cells) {
+            line.extend((0..*count).map(|index| value.cell(index).map(number).unwrap_or_default()));
+        }
+        writeln!(output, "{}", line.join(","))
+    }
+}
+
+/// Format numeric value (NaN as empty field)
+fn number(value: FieldValue) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Quote field if needed
+fn quote(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Deci, Milli};
+
+    #[test]
+    fn cell_data() {
+        let first = CellData {
+            cell_voltage: [3.3, 3.31].map(Milli::from_f64).to_vec(),
+            cell_resistance: [0.138, 0.137].map(Milli::from_f64).to_vec(),
+            battery_voltage: Milli::from_f64(6.61),
+            battery_temperature: [23.5].map(Deci::from_f64).to_vec(),
+            mosfet_temperature: Deci::NAN,
+            remain_percent: 100,
+            cycle_count: 1,
+            ..Default::default()
+        };
+        let second = CellData {
+            cell_voltage: [3.2, 3.21, 3.22].map(Milli::from_f64).to_vec(),
+            battery_voltage: Milli::from_f64(9.63),
+            ..Default::default()
+        };
+
+        let columns = CsvColumns::new([&first]);
+        assert_eq!(columns.cells("cell_voltage"), 2);
+        assert_eq!(columns.cells("cell_resistance"), 2);
+        assert_eq!(columns.cells("battery_temperature"), 1);
+        assert_eq!(columns.cells("battery_voltage"), 0);
+
+        let mut output = Vec::new();
+        columns.write_header(&mut output).unwrap();
+        columns
+            .write_record(1707600000.5, "ups", &first, &mut output)
+            .unwrap();
+        columns
+            .write_record(1707600001.0, "bench, \"left\"", &second, &mut output)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"timestamp,device,average_cell_voltage,delta_cell_voltage,balance_current,battery_voltage,battery_power,battery_current,mosfet_temperature,remain_percent,remain_capacity,nominal_capacity,cycle_count,cycle_capacity,up_time,cell_voltage_0,cell_voltage_1,cell_resistance_0,cell_resistance_1,battery_temperature_0
+1707600000.500,ups,0,0,0,6.61,0,0,,100,0,0,1,0,0,3.3,3.31,0.138,0.137,23.5
+1707600001.000,"bench, ""left""",0,0,0,9.63,0,0,0,0,0,0,0,0,0,3.2,3.21,,,
+"#
+        );
+
+        let mut columns = columns;
+        assert!(!columns.fit(&first));
+        assert!(columns.fit(&second));
+        assert_eq!(columns, CsvColumns::new([&first, &second]));
+        assert_eq!(columns.cells("cell_voltage"), 3);
+        assert_eq!(columns.cells("cell_resistance"), 2);
+    }
+}
@@ -1,12 +1,46 @@
-use crate::{log, Client, Metrics, Result};
-use prometheus::{Encoder, ProtobufEncoder, Registry, TextEncoder};
-use std::io::Write;
+use crate::{
+    log, Alarm, CellData, Client, DeviceInfo, Error, Main, Metrics, MetricsOptions,
+    OpenMetricsEncoder, Result, ScrapeMetrics, Settings,
+};
+use core::time::Duration;
+use prometheus::{
+    proto::{MetricFamily, MetricType},
+    Encoder, ProtobufEncoder, Registry, TextEncoder,
+};
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::sync::broadcast;
+
+#[cfg(all(feature = "push", feature = "influx"))]
+use crate::Influx;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+#[cfg(feature = "history")]
+use crate::History;
+
+#[cfg(feature = "history")]
+use tokio::task::spawn_blocking;
+
+const DEVICE_LABEL: &str = "device";
+
+/// Suffix of info metrics which values are always 1
+const INFO_SUFFIX: &str = "_info";
+
+/// Number of events buffered for slow stream subscribers
+const EVENTS_CAPACITY: usize = 64;
 
 #[derive(Clone, Copy, Default, Debug)]
 pub enum Encoding {
     #[default]
     Text,
     Protobuf,
+    OpenMetrics,
 }
 
 impl Encoding {
@@ -14,6 +48,8 @@ impl Encoding {
         let accept = accept.as_ref();
         if find_seq(accept, b"application/vnd.google.protobuf").is_some() {
             Some(Self::Protobuf)
+        } else if find_seq(accept, b"application/openmetrics-text").is_some() {
+            Some(Self::OpenMetrics)
         } else if find_seq(accept, b"text/plain").is_some() {
             Some(Self::Text)
         } else {
@@ -22,90 +58,1167 @@ impl Encoding {
     }
 }
 
+impl core::str::FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "text" => Self::Text,
+            "protobuf" => Self::Protobuf,
+            "openmetrics" => Self::OpenMetrics,
+            _ => return Err(format!("Unknown encoding: {s}")),
+        })
+    }
+}
+
+/// Value of device label
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum DeviceLabel {
+    /// Device alias or identifier
+    #[default]
+    Name,
+    /// Device serial number (survives renaming)
+    Serial,
+}
+
+impl core::str::FromStr for DeviceLabel {
+    type Err = String;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "name" => Self::Name,
+            "serial" => Self::Serial,
+            _ => return Err(format!("Unknown device label: {s}")),
+        })
+    }
+}
+
+/// Action on metrics of device which stopped responding
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum StaleAction {
+    /// Drop series from output
+    #[default]
+    Drop,
+    /// Report NaN values of gauges (counters and info are dropped)
+    Nan,
+}
+
+impl core::str::FromStr for StaleAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "drop" => Self::Drop,
+            "nan" => Self::Nan,
+            _ => return Err(format!("Unknown stale action: {s}")),
+        })
+    }
+}
+
+/// Policy of handling stale metrics
+#[derive(Clone, Copy, Default, Debug)]
+pub struct StalePolicy {
+    /// Number of failed scrapes in a row to consider metrics stale
+    pub max_failures: Option<usize>,
+    /// Maximum age of metrics since last successful scrape
+    pub max_age: Option<Duration>,
+    /// What to do with stale metrics
+    pub action: StaleAction,
+}
+
+/// Connection state of device
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ConnectionState {
+    /// Not connected yet
+    #[default]
+    Unknown,
+    /// Connected to device
+    Connected,
+    /// Disconnected after scrape
+    Disconnected,
+    /// Connection failed
+    Failed,
+}
+
+/// Result of device scrape
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ScrapeStatus {
+    /// Whether the scrape was successful
+    pub success: bool,
+    /// Unix time of scrape end, S
+    pub timestamp: f64,
+    /// Duration of scrape, S
+    pub duration: f64,
+    /// Last error occured while scraping
+    pub error: Option<String>,
+}
+
+/// Status of device
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DeviceStatus {
+    /// Device alias or identifier
+    pub name: String,
+    /// Connection state
+    pub state: ConnectionState,
+    /// Result of last scrape
+    pub last_scrape: Option<ScrapeStatus>,
+}
+
+/// Data sample with timestamp
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Sample<T> {
+    /// Unix time of sample, S
+    pub timestamp: f64,
+    /// Sample data
+    pub data: T,
+}
+
+impl<T> From<T> for Sample<T> {
+    fn from(data: T) -> Self {
+        Self {
+            timestamp: now(),
+            data,
+        }
+    }
+}
+
+/// Device event for live stream
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Event {
+    /// Device alias or identifier
+    pub device: String,
+    /// Event data
+    pub data: EventData,
+}
+
+/// Data of device event
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum EventData {
+    /// New device info received
+    DeviceInfo(Sample<DeviceInfo>),
+    /// New cell data received
+    CellData(Sample<CellData>),
+    /// Connection state changed
+    State(ConnectionState),
+    /// Error occured
+    Error(String),
+    /// Alarm started or cleared
+    Alarm(Sample<AlarmChange>),
+}
+
+/// Change of alarm state
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct AlarmChange {
+    /// Alarm condition
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub alarm: Alarm,
+    /// Human readable message
+    pub message: String,
+    /// Whether alarm started (or cleared)
+    pub active: bool,
+}
+
+impl Event {
+    /// Name of event
+    pub fn name(&self) -> &'static str {
+        match self.data {
+            EventData::DeviceInfo(_) => "device_info",
+            EventData::CellData(_) => "cell_data",
+            EventData::State(_) => "state",
+            EventData::Error(_) => "error",
+            EventData::Alarm(_) => "alarm",
+        }
+    }
+}
+
+/// Exporter options
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ExporterOptions {
+    /// Encoding to use when not requested explicitly
+    pub default_encoding: Encoding,
+    /// Value of device label
+    pub device_label: DeviceLabel,
+    /// Stale metrics policy
+    pub stale_policy: StalePolicy,
+}
+
 pub struct Exporter {
     registry: Registry,
+    health_registry: Registry,
     text_encoder: TextEncoder,
     protobuf_encoder: ProtobufEncoder,
-    metrics: Vec<Metrics>,
-    default_encoding: Encoding,
+    openmetrics_encoder: OpenMetricsEncoder,
+    devices: Vec<Device>,
+    options: ExporterOptions,
+    events: broadcast::Sender<Event>,
+    #[cfg(feature = "history")]
+    history: Option<Arc<History>>,
+}
+
+struct Device {
+    name: String,
+    options: MetricsOptions,
+    metrics: Mutex<Option<Metrics>>,
+    health: ScrapeMetrics,
+    state: Mutex<DeviceState>,
+    /// Lock to serialize sessions with device
+    session: tokio::sync::Mutex<()>,
+    events: broadcast::Sender<Event>,
+}
+
+struct DeviceState {
+    /// Number of failed scrapes in a row
+    failures: usize,
+    /// Time of last successful scrape (or start time)
+    last_success: Instant,
+    /// Whether device was scraped successfully at least once
+    ever_success: bool,
+    /// Connection state
+    connection: ConnectionState,
+    /// Last error of current scrape
+    error: Option<String>,
+    /// Result of last scrape
+    last_scrape: Option<ScrapeStatus>,
+    /// Last sample of device info
+    device_info: Option<Sample<DeviceInfo>>,
+    /// Last sample of cell data
+    cell_data: Option<Sample<CellData>>,
+    /// Currently active alarms
+    alarms: Vec<Alarm>,
+}
+
+impl Device {
+    /// Update state using result of scrape
+    fn scraped(&self, success: bool, duration: Duration) {
+        self.health.scraped(success, duration);
+
+        let mut state = self.state.lock().unwrap();
+        if success {
+            if state.failures > 0 {
+                log::info!("Device '{}' is responding again", self.name);
+            }
+            state.failures = 0;
+            state.last_success = Instant::now();
+            state.ever_success = true;
+        } else {
+            state.failures += 1;
+        }
+
+        state.last_scrape = Some(ScrapeStatus {
+            success,
+            timestamp: now(),
+            duration: duration.as_secs_f64(),
+            error: state.error.take(),
+        });
+    }
+
+    /// Count error occured while scraping
+    fn error(&self, error: &Error) {
+        self.health.error(error);
+        self.state.lock().unwrap().error = Some(error.to_string());
+        self.emit(EventData::Error(error.to_string()));
+    }
+
+    /// Update connection state
+    fn connection(&self, connection: ConnectionState) {
+        self.state.lock().unwrap().connection = connection;
+        self.emit(EventData::State(connection));
+    }
+
+    /// Update active alarms and notify about started or cleared ones
+    fn alarms(&self, cell_data: &CellData) {
+        let alarms = cell_data.alarms();
+
+        let changes = {
+            let mut state = self.state.lock().unwrap();
+            let cleared = state
+                .alarms
+                .iter()
+                .filter(|alarm| !alarms.iter().any(|other| alarm.is_same(other)))
+                .map(|alarm| (*alarm, false));
+            let started = alarms
+                .iter()
+                .filter(|alarm| !state.alarms.iter().any(|other| alarm.is_same(other)))
+                .map(|alarm| (*alarm, true));
+            let changes = cleared.chain(started).collect::<Vec<_>>();
+            state.alarms = alarms;
+            changes
+        };
+
+        for (alarm, active) in changes {
+            if active {
+                log::warn!("Alarm on '{}': {alarm}", self.name);
+            } else {
+                log::info!("Alarm cleared on '{}': {alarm}", self.name);
+            }
+            self.emit(EventData::Alarm(Sample::from(AlarmChange {
+                alarm,
+                message: alarm.to_string(),
+                active,
+            })));
+        }
+    }
+
+    /// Send event to stream subscribers
+    fn emit(&self, data: EventData) {
+        // fails when nobody subscribed
+        let _ = self.events.send(Event {
+            device: self.name.clone(),
+            data,
+        });
+    }
+
+    /// Get current status
+    fn status(&self) -> DeviceStatus {
+        let state = self.state.lock().unwrap();
+        DeviceStatus {
+            name: self.name.clone(),
+            state: state.connection,
+            last_scrape: state.last_scrape.clone(),
+        }
+    }
+
+    /// Check that metrics of device is stale according to policy
+    fn is_stale(&self, policy: &StalePolicy) -> bool {
+        let state = self.state.lock().unwrap();
+        policy
+            .max_failures
+            .map(|max_failures| state.failures >= max_failures)
+            .unwrap_or(false)
+            || policy
+                .max_age
+                .map(|max_age| state.last_success.elapsed() > max_age)
+                .unwrap_or(false)
+    }
+
+    /// Get device metrics (re)creating it when device label changed
+    fn metrics(
+        &self,
+        registry: &Registry,
+        device_label: DeviceLabel,
+        device_info: Option<&DeviceInfo>,
+    ) -> Result<Option<Metrics>> {
+        let mut metrics = self.metrics.lock().unwrap();
+
+        let device = match (device_label, device_info) {
+            (DeviceLabel::Name, _) => &self.name,
+            (DeviceLabel::Serial, Some(device_info)) if !device_info.serial_number.is_empty() => {
+                &device_info.serial_number
+            }
+            _ => return Ok(metrics.clone()),
+        };
+
+        if metrics
+            .as_ref()
+            .map(|metrics| metrics.device() != device)
+            .unwrap_or(true)
+        {
+            if let Some(metrics) = metrics.take() {
+                log::info!(
+                    "Device label of '{}' changed: '{}' -> '{device}'",
+                    self.name,
+                    metrics.device()
+                );
+                metrics.unregister(Some(registry))?;
+            }
+            let new_metrics = Metrics::new(device, &self.options)?;
+            new_metrics.register(Some(registry))?;
+            *metrics = Some(new_metrics);
+        }
+
+        Ok(metrics.clone())
+    }
 }
 
-impl<'x> Exporter {
-    pub fn new(default_encoding: Encoding, clients: &[Client]) -> Result<Self> {
+impl Exporter {
+    pub fn new(
+        options: ExporterOptions,
+        devices: impl IntoIterator<Item = (String, MetricsOptions)>,
+    ) -> Result<Self> {
         let registry = Registry::new();
-        let metrics = Vec::default();
+        let health_registry = Registry::new();
         let text_encoder = TextEncoder::new();
         let protobuf_encoder = ProtobufEncoder::new();
+        let openmetrics_encoder = OpenMetricsEncoder::new();
+        let (events, _) = broadcast::channel(EVENTS_CAPACITY);
+        let devices = devices
+            .into_iter()
+            .map(|(name, options)| {
+                let health = ScrapeMetrics::new(&name, &options)?;
+                health.register(Some(&health_registry))?;
+                Ok(Device {
+                    name,
+                    options,
+                    metrics: Mutex::new(None),
+                    health,
+                    state: Mutex::new(DeviceState {
+                        failures: 0,
+                        last_success: Instant::now(),
+                        ever_success: false,
+                        connection: ConnectionState::Unknown,
+                        error: None,
+                        last_scrape: None,
+                        device_info: None,
+                        cell_data: None,
+                        alarms: Vec::new(),
+                    }),
+                    session: Default::default(),
+                    events: events.clone(),
+                })
+            })
+            .collect::<Result<_>>()?;
 
-        let mut this = Self {
+        let this = Self {
             registry,
+            health_registry,
             text_encoder,
             protobuf_encoder,
-            metrics,
-            default_encoding,
+            openmetrics_encoder,
+            devices,
+            options,
+            events,
+            #[cfg(feature = "history")]
+            history: None,
         };
 
-        this.metrics(clients)?;
+        for device in &this.devices {
+            device.metrics(&this.registry, this.options.device_label, None)?;
+        }
 
         Ok(this)
     }
 
-    fn metrics(&mut self, clients: &[Client]) -> Result<()> {
-        for client in clients {
-            let metric = Metrics::new(client.device_id())?;
-            metric.register(Some(&self.registry))?;
-            self.metrics.push(metric);
+    pub async fn scrape(&self, clients: &[Arc<Client>]) -> Result<()> {
+        for (client, device) in clients.iter().zip(self.devices.iter()) {
+            let device_id = client.device_id();
+            log::info!("Scrape metrics from: '{device_id}'");
+
+            let start = Instant::now();
+            let success = self.scrape_device(client, device).await;
+            device.scraped(success, start.elapsed());
         }
         Ok(())
     }
 
-    pub async fn scrape(&self, clients: &[Client]) -> Result<()> {
-        for (client, metrics) in clients.iter().zip(self.metrics.iter()) {
-            let device_id = client.device_id();
-            log::info!("Scrape metrics from: '{device_id}'");
+    async fn scrape_device(&self, client: &Client, device: &Device) -> bool {
+        let _session = device.session.lock().await;
 
-            if let Err(error) = client.open().await {
-                log::error!("Error while connecting: {error}");
-            } else {
-                match client.device_info().await {
-                    Ok(device_info) => metrics.scrape(&device_info),
-                    Err(error) => {
-                        log::error!("Error while fetch device info from '{device_id}': {error}")
-                    }
-                }
-                match client.cell_data().await {
-                    Ok(cell_data) => metrics.scrape(&cell_data),
-                    Err(error) => {
-                        log::error!("Error while fetch cell data from '{device_id}': {error}")
+        if self.connect(client, device).await.is_err() {
+            return false;
+        }
+
+        let device_info = self.fetch_device_info(client, device).await;
+        let cell_data = self.fetch_cell_data(client, device).await;
+        let success = device_info.is_ok() && cell_data.is_ok();
+
+        self.disconnect(client, device).await;
+
+        #[cfg(feature = "history")]
+        if let Some(history) = &self.history {
+            let history = history.clone();
+            let name = device.name.clone();
+            let device_info = device_info.ok();
+            let cell_data = cell_data.ok();
+
+            // SQLite is blocking so keep it out of async context
+            if let Err(error) = spawn_blocking(move || {
+                history.store(&name, device_info.as_ref(), cell_data.as_ref())
+            })
+            .await
+            {
+                log::error!("Error while storing history of '{}': {error}", device.name);
+            }
+        }
+
+        success
+    }
+
+    async fn connect(&self, client: &Client, device: &Device) -> Result<()> {
+        if let Err(error) = client.open().await {
+            log::error!("Error while connecting: {error}");
+            device.error(&error);
+            device.connection(ConnectionState::Failed);
+            return Err(error);
+        }
+
+        device.connection(ConnectionState::Connected);
+
+        Ok(())
+    }
+
+    async fn disconnect(&self, client: &Client, device: &Device) {
+        if let Err(error) = client.close().await {
+            log::error!("Error while disconnecting: {error}");
+            device.error(&error);
+        }
+
+        device.connection(ConnectionState::Disconnected);
+    }
+
+    async fn fetch_device_info(
+        &self,
+        client: &Client,
+        device: &Device,
+    ) -> Result<Sample<DeviceInfo>> {
+        let device_id = client.device_id();
+
+        let device_info = client.device_info().await.map_err(|error| {
+            log::error!("Error while fetch device info from '{device_id}': {error}");
+            device.error(&error);
+            error
+        })?;
+
+        Ok(self.store_device_info(device, device_info))
+    }
+
+    /// Update metrics and state using fetched device info
+    fn store_device_info(&self, device: &Device, device_info: DeviceInfo) -> Sample<DeviceInfo> {
+        match device.metrics(
+            &self.registry,
+            self.options.device_label,
+            Some(&device_info),
+        ) {
+            Ok(Some(metrics)) => metrics.scrape(&device_info),
+            Ok(None) => (),
+            Err(error) => {
+                log::error!(
+                    "Error while creating metrics for '{}': {error}",
+                    device.name
+                )
+            }
+        }
+
+        let sample = Sample::from(device_info);
+        device.state.lock().unwrap().device_info = Some(sample.clone());
+        device.emit(EventData::DeviceInfo(sample.clone()));
+
+        sample
+    }
+
+    async fn fetch_cell_data(&self, client: &Client, device: &Device) -> Result<Sample<CellData>> {
+        let device_id = client.device_id();
+
+        let cell_data = client.cell_data().await.map_err(|error| {
+            log::error!("Error while fetch cell data from '{device_id}': {error}");
+            device.error(&error);
+            error
+        })?;
+
+        Ok(self.store_cell_data(device, cell_data))
+    }
+
+    /// Update metrics, state and alarms using fetched cell data
+    fn store_cell_data(&self, device: &Device, cell_data: CellData) -> Sample<CellData> {
+        if let Ok(Some(metrics)) = device.metrics(&self.registry, self.options.device_label, None) {
+            metrics.scrape(&cell_data);
+        }
+
+        let sample = Sample::from(cell_data);
+        device.state.lock().unwrap().cell_data = Some(sample.clone());
+        device.emit(EventData::CellData(sample.clone()));
+        device.alarms(&sample.data);
+
+        sample
+    }
+
+    /// Store data of device as if it was scraped successfully
+    #[cfg(test)]
+    pub fn store(
+        &self,
+        index: usize,
+        device_info: Option<DeviceInfo>,
+        cell_data: Option<CellData>,
+    ) {
+        let device = &self.devices[index];
+        if let Some(device_info) = device_info {
+            self.store_device_info(device, device_info);
+        }
+        if let Some(cell_data) = cell_data {
+            self.store_cell_data(device, cell_data);
+        }
+        device.scraped(true, Duration::ZERO);
+    }
+
+    /// Subscribe to device events
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Find device by name (alias or identifier)
+    pub fn find_device(&self, name: &str) -> Option<usize> {
+        self.devices.iter().position(|device| device.name == name)
+    }
+
+    /// Get last sample of device info
+    pub fn device_info(&self, index: usize) -> Option<Sample<DeviceInfo>> {
+        self.devices[index]
+            .state
+            .lock()
+            .unwrap()
+            .device_info
+            .clone()
+    }
+
+    /// Get last sample of cell data
+    pub fn cell_data(&self, index: usize) -> Option<Sample<CellData>> {
+        self.devices[index].state.lock().unwrap().cell_data.clone()
+    }
+
+    /// Read fresh device info
+    pub async fn read_device_info(
+        &self,
+        index: usize,
+        client: &Client,
+    ) -> Result<Sample<DeviceInfo>> {
+        let device = &self.devices[index];
+        let _session = device.session.lock().await;
+
+        self.connect(client, device).await?;
+        let result = self.fetch_device_info(client, device).await;
+        self.disconnect(client, device).await;
+
+        result
+    }
+
+    /// Read fresh cell data
+    pub async fn read_cell_data(&self, index: usize, client: &Client) -> Result<Sample<CellData>> {
+        let device = &self.devices[index];
+        let _session = device.session.lock().await;
+
+        self.connect(client, device).await?;
+        let result = self.fetch_cell_data(client, device).await;
+        self.disconnect(client, device).await;
+
+        result
+    }
+
+    /// Read device settings
+    ///
+    /// Settings aren't scraped periodically, so they are always read from device.
+    pub async fn read_settings(&self, index: usize, client: &Client) -> Result<Sample<Settings>> {
+        let device = &self.devices[index];
+        let _session = device.session.lock().await;
+
+        self.connect(client, device).await?;
+        let result = client.settings().await.map_err(|error| {
+            log::error!(
+                "Error while fetch settings from '{}': {error}",
+                client.device_id()
+            );
+            device.error(&error);
+            error
+        });
+        self.disconnect(client, device).await;
+
+        result.map(Sample::from)
+    }
+
+    /// Check that at least one device was scraped successfully
+    pub fn is_ready(&self) -> bool {
+        self.devices
+            .iter()
+            .any(|device| device.state.lock().unwrap().ever_success)
+    }
+
+    /// Get status of devices
+    pub fn status(&self) -> Vec<DeviceStatus> {
+        self.devices.iter().map(Device::status).collect()
+    }
+
+    /// Gather metrics applying stale policy
+    pub fn gather(&self) -> Vec<MetricFamily> {
+        let policy = &self.options.stale_policy;
+
+        let stale_devices = self
+            .devices
+            .iter()
+            .filter(|device| device.is_stale(policy))
+            .filter_map(|device| {
+                device
+                    .metrics
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .map(|metrics| metrics.device().to_string())
+            })
+            .collect::<Vec<_>>();
+
+        let mut families = self.registry.gather();
+
+        if !stale_devices.is_empty() {
+            for family in &mut families {
+                let is_gauge = family.get_field_type() == MetricType::GAUGE
+                    && !family.get_name().ends_with(INFO_SUFFIX);
+                let metrics = family.mut_metric();
+
+                if policy.action == StaleAction::Nan && is_gauge {
+                    for metric in metrics
+                        .iter_mut()
+                        .filter(|metric| is_device_metric(metric, &stale_devices))
+                    {
+                        metric.mut_gauge().set_value(f64::NAN);
                     }
+                } else {
+                    metrics.retain(|metric| !is_device_metric(metric, &stale_devices));
                 }
-                if let Err(error) = client.close().await {
-                    log::error!("Error while disconnecting: {error}");
-                }
+            }
+
+            families.retain(|family| !family.get_metric().is_empty());
+        }
+
+        families.extend(self.health_registry.gather());
+        families.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+
+        families
+    }
+
+    /// Gather metrics of single device applying stale policy
+    #[cfg(feature = "push")]
+    pub fn gather_device(&self, index: usize) -> Vec<MetricFamily> {
+        let device = &self.devices[index];
+
+        let mut labels = vec![device.name.clone()];
+        if let Some(metrics) = device.metrics.lock().unwrap().as_ref() {
+            labels.push(metrics.device().to_string());
+        }
+
+        let mut families = self.gather();
+
+        for family in &mut families {
+            family
+                .mut_metric()
+                .retain(|metric| is_device_metric(metric, &labels));
+        }
+        families.retain(|family| !family.get_metric().is_empty());
+
+        families
+    }
+
+    /// Get names of devices
+    #[cfg(any(feature = "push", feature = "mqtt"))]
+    pub fn device_names(&self) -> impl Iterator<Item = &str> {
+        self.devices.iter().map(|device| device.name.as_str())
+    }
+
+    /// Registry of service metrics which are always exported
+    #[cfg(feature = "push")]
+    pub fn service_registry(&self) -> &Registry {
+        &self.health_registry
+    }
+
+    /// Encode metrics timestamped by current time (in text format)
+    #[cfg(feature = "push")]
+    pub fn encode_timestamped(&self, mut output: impl Write) -> Result<&str> {
+        let timestamp = (now() * 1000.0) as i64;
+
+        let mut families = self.gather();
+        for family in &mut families {
+            for metric in family.mut_metric() {
+                metric.set_timestamp_ms(timestamp);
+            }
+        }
+
+        self.text_encoder.encode(&families, &mut output)?;
+        Ok(self.text_encoder.format_type())
+    }
+
+    /// Encode last samples of devices using InfluxDB line protocol
+    ///
+    /// Stale devices are skipped as well as devices which device info isn't known yet
+    /// (the serial number is used as tag so series stay the same).
+    #[cfg(all(feature = "push", feature = "influx"))]
+    pub fn encode_influx(&self, output: &mut dyn Write) -> Result<()> {
+        let policy = &self.options.stale_policy;
+
+        for device in &self.devices {
+            if device.is_stale(policy) {
+                continue;
+            }
+
+            let state = device.state.lock().unwrap();
+
+            let Some(device_info) = &state.device_info else {
+                continue;
+            };
+
+            let tags = [
+                (DEVICE_LABEL, device.name.as_str()),
+                ("serial", device_info.data.serial_number.as_str()),
+            ]
+            .into_iter()
+            .chain(
+                device
+                    .options
+                    .labels
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_str())),
+            )
+            .collect::<Vec<_>>();
+
+            device_info
+                .data
+                .write_influx(&tags, timestamp_ns(device_info.timestamp), output)?;
+            if let Some(sample) = &state.cell_data {
+                sample
+                    .data
+                    .write_influx(&tags, timestamp_ns(sample.timestamp), output)?;
             }
         }
+
         Ok(())
     }
 
-    pub fn encode(&self, encoding: Option<Encoding>, mut output: impl Write) -> Result<&str> {
-        Ok(match encoding.unwrap_or(self.default_encoding) {
+    pub fn encode(&self, encoding: Option<Encoding>, output: impl Write) -> Result<&str> {
+        self.encode_families(&self.gather(), encoding, output)
+    }
+
+    /// Encode metrics of single device
+    #[cfg(feature = "push")]
+    pub fn encode_device(
+        &self,
+        index: usize,
+        encoding: Option<Encoding>,
+        output: impl Write,
+    ) -> Result<&str> {
+        self.encode_families(&self.gather_device(index), encoding, output)
+    }
+
+    fn encode_families(
+        &self,
+        families: &[MetricFamily],
+        encoding: Option<Encoding>,
+        mut output: impl Write,
+    ) -> Result<&str> {
+        Ok(match encoding.unwrap_or(self.options.default_encoding) {
             Encoding::Protobuf => {
-                self.protobuf_encoder
-                    .encode(&self.registry.gather(), &mut output)?;
+                self.protobuf_encoder.encode(families, &mut output)?;
                 self.protobuf_encoder.format_type()
             }
             Encoding::Text => {
-                self.text_encoder
-                    .encode(&self.registry.gather(), &mut output)?;
+                self.text_encoder.encode(families, &mut output)?;
                 self.text_encoder.format_type()
             }
+            Encoding::OpenMetrics => {
+                self.openmetrics_encoder.encode(families, &mut output)?;
+                self.openmetrics_encoder.format_type()
+            }
         })
     }
 }
 
+impl Main {
+    /// Create exporter for devices in use
+    pub fn exporter(&self) -> Result<Exporter> {
+        let metrics_options = self.metrics_options();
+
+        #[cfg_attr(not(feature = "history"), allow(unused_mut))]
+        let mut exporter = Exporter::new(
+            ExporterOptions {
+                default_encoding: self.default_encoding(),
+                device_label: self.device_label(),
+                stale_policy: self.stale_policy(),
+            },
+            self.devices
+                .iter()
+                .map(|device| (device.name(), device.metrics_options(&metrics_options))),
+        )?;
+
+        #[cfg(feature = "history")]
+        {
+            exporter.history = self.history()?.map(Arc::new);
+        }
+
+        Ok(exporter)
+    }
+}
+
+/// Current unix time, S
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+/// Convert unix time in seconds to nanoseconds
+#[cfg(all(feature = "push", feature = "influx"))]
+fn timestamp_ns(timestamp: f64) -> i64 {
+    (timestamp * 1e9) as i64
+}
+
+fn is_device_metric(metric: &prometheus::proto::Metric, devices: &[String]) -> bool {
+    metric.get_label().iter().any(|label| {
+        label.get_name() == DEVICE_LABEL && devices.iter().any(|device| device == label.get_value())
+    })
+}
+
 fn find_seq<T>(seq: &[T], sub: &[T]) -> Option<usize>
 where
     for<'a> &'a [T]: PartialEq,
 {
     seq.windows(sub.len()).position(|win| win == sub)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Milli;
+
+    fn exporter(action: StaleAction) -> Exporter {
+        let exporter = Exporter::new(
+            ExporterOptions {
+                stale_policy: StalePolicy {
+                    max_failures: Some(2),
+                    action,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ["ups", "solar"]
+                .into_iter()
+                .map(|name| (name.into(), Default::default())),
+        )
+        .unwrap();
+
+        for device in &exporter.devices {
+            let metrics = device
+                .metrics(&exporter.registry, DeviceLabel::Name, None)
+                .unwrap()
+                .unwrap();
+            metrics.scrape(&CellData {
+                battery_voltage: Milli::from_f64(14.304),
+                ..Default::default()
+            });
+            device.scraped(true, Duration::from_secs(1));
+        }
+
+        exporter
+    }
+
+    fn encode(exporter: &Exporter) -> String {
+        let mut buffer = Vec::new();
+        exporter.encode(None, &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn stale_drop() {
+        let exporter = exporter(StaleAction::Drop);
+        let solar = &exporter.devices[1];
+
+        solar.scraped(false, Duration::from_secs(1));
+        let text = encode(&exporter);
+        assert!(text.contains(r#"ubmsc_battery_voltage_volts{device="solar"}"#));
+
+        solar.scraped(false, Duration::from_secs(1));
+        let text = encode(&exporter);
+        assert!(text.contains(r#"ubmsc_battery_voltage_volts{device="ups"}"#));
+        assert!(!text.contains(r#"ubmsc_battery_voltage_volts{device="solar"}"#));
+        assert!(text.contains(r#"ubmsc_scrape_success{device="solar"} 0"#));
+
+        solar.scraped(true, Duration::from_secs(1));
+        let text = encode(&exporter);
+        assert!(text.contains(r#"ubmsc_battery_voltage_volts{device="solar"}"#));
+    }
+
+    #[test]
+    fn stale_nan() {
+        let exporter = exporter(StaleAction::Nan);
+        let solar = &exporter.devices[1];
+
+        for device in &exporter.devices {
+            device
+                .metrics
+                .lock()
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .scrape(&DeviceInfo {
+                    serial_number: "40531310629".into(),
+                    poweron_times: 1,
+                    ..Default::default()
+                });
+        }
+
+        solar.scraped(false, Duration::from_secs(1));
+        solar.scraped(false, Duration::from_secs(1));
+        let text = encode(&exporter);
+        assert!(text.contains(r#"ubmsc_battery_voltage_volts{device="ups"} 14.304"#));
+        assert!(text.contains(r#"ubmsc_battery_voltage_volts{device="solar"} NaN"#));
+
+        // info stays 1 and counters never go NaN: those series are dropped
+        assert!(text.contains(r#"ubmsc_poweron_times_total{device="ups"} 1"#));
+        assert!(text.contains(r#"ubmsc_up_time_seconds_total{device="ups"} 0"#));
+        assert!(text.contains(r#"ubmsc_device_info{device="ups","#));
+        assert!(!text.contains(r#"ubmsc_poweron_times_total{device="solar"}"#));
+        assert!(!text.contains(r#"ubmsc_up_time_seconds_total{device="solar"}"#));
+        assert!(!text.contains(r#"ubmsc_device_info{device="solar","#));
+        assert!(text.contains(r#"ubmsc_scrape_success{device="solar"} 0"#));
+    }
+
+    #[cfg(feature = "push")]
+    #[test]
+    fn gather_device() {
+        let exporter = exporter(StaleAction::Drop);
+
+        let mut buffer = Vec::new();
+        exporter.encode_device(1, None, &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+
+        assert!(text.contains(r#"ubmsc_battery_voltage_volts{device="solar"}"#));
+        assert!(text.contains(r#"ubmsc_scrape_success{device="solar"} 1"#));
+        assert!(!text.contains(r#"device="ups""#));
+    }
+
+    #[cfg(all(feature = "push", feature = "influx"))]
+    #[test]
+    fn encode_influx() {
+        let exporter = exporter(StaleAction::Drop);
+
+        let store_cell_data = || {
+            for device in &exporter.devices {
+                device.state.lock().unwrap().cell_data = Some(Sample {
+                    timestamp: 1707600000.5,
+                    data: CellData {
+                        battery_voltage: Milli::from_f64(14.25),
+                        ..Default::default()
+                    },
+                });
+            }
+        };
+        let encode_influx = || {
+            let mut buffer = Vec::new();
+            exporter.encode_influx(&mut buffer).unwrap();
+            String::from_utf8(buffer).unwrap()
+        };
+
+        // nothing written until serial number is known
+        store_cell_data();
+        assert_eq!(encode_influx(), "");
+
+        exporter.store(
+            0,
+            Some(DeviceInfo {
+                serial_number: "40531310629".into(),
+                ..Default::default()
+            }),
+            None,
+        );
+        store_cell_data();
+
+        let solar = &exporter.devices[1];
+        solar.scraped(false, Duration::from_secs(1));
+        solar.scraped(false, Duration::from_secs(1));
+
+        let text = encode_influx();
+        let lines = text.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(r#"bms,device=ups,serial=40531310629 device_model="","#));
+        assert!(lines[1].starts_with("bms,device=ups,serial=40531310629 average_cell_voltage=0,"));
+        assert!(lines[1].contains(",battery_voltage=14.25,"));
+        assert!(text.ends_with(" 1707600000500000000\n"));
+        assert!(!text.contains("device=solar"));
+    }
+
+    #[test]
+    fn status() {
+        let exporter =
+            Exporter::new(Default::default(), [("ups".into(), Default::default())]).unwrap();
+        let ups = &exporter.devices[0];
+
+        assert!(!exporter.is_ready());
+        assert!(exporter.status()[0].last_scrape.is_none());
+        assert_eq!(exporter.find_device("ups"), Some(0));
+        assert_eq!(exporter.find_device("solar"), None);
+        assert!(exporter.cell_data(0).is_none());
+
+        ups.connection(ConnectionState::Failed);
+        ups.error(&Error::Timeout);
+        ups.scraped(false, Duration::from_secs(1));
+        assert!(!exporter.is_ready());
+
+        let status = &exporter.status()[0];
+        assert_eq!(status.state, ConnectionState::Failed);
+        let last_scrape = status.last_scrape.as_ref().unwrap();
+        assert!(!last_scrape.success);
+        assert_eq!(last_scrape.error.as_deref(), Some("Timeout"));
+
+        ups.connection(ConnectionState::Disconnected);
+        ups.scraped(true, Duration::from_secs(1));
+        assert!(exporter.is_ready());
+
+        let status = &exporter.status()[0];
+        assert_eq!(status.state, ConnectionState::Disconnected);
+        assert!(status.last_scrape.as_ref().unwrap().error.is_none());
+    }
+
+    #[test]
+    fn events() {
+        let exporter =
+            Exporter::new(Default::default(), [("ups".into(), Default::default())]).unwrap();
+        let ups = &exporter.devices[0];
+
+        let mut events = exporter.subscribe();
+
+        ups.connection(ConnectionState::Connected);
+        ups.error(&Error::BadCrc);
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.device, "ups");
+        assert_eq!(event.name(), "state");
+        assert!(matches!(
+            event.data,
+            EventData::State(ConnectionState::Connected)
+        ));
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.name(), "error");
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn alarm_events() {
+        let exporter =
+            Exporter::new(Default::default(), [("ups".into(), Default::default())]).unwrap();
+        let ups = &exporter.devices[0];
+
+        let mut events = exporter.subscribe();
+
+        let mut data = CellData {
+            remain_percent: 5,
+            ..Default::default()
+        };
+        ups.alarms(&data);
+        ups.alarms(&data);
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.name(), "alarm");
+        let EventData::Alarm(change) = event.data else {
+            panic!("alarm event expected");
+        };
+        assert!(change.data.active);
+        assert_eq!(change.data.message, "Low charge 5 %");
+        // alarm which is still active isn't repeated
+        assert!(events.try_recv().is_err());
+
+        data.remain_percent = 50;
+        ups.alarms(&data);
+
+        let EventData::Alarm(change) = events.try_recv().unwrap().data else {
+            panic!("alarm event expected");
+        };
+        assert!(!change.data.active);
+        assert!(matches!(change.data.alarm, Alarm::LowCharge { value: 5 }));
+        assert!(events.try_recv().is_err());
+    }
+}
@@ -0,0 +1,288 @@
+use crate::{CellData, Deci, DeviceInfo, Milli};
+use core::fmt;
+
+/// Kind of data field
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FieldKind {
+    /// Value which can go up and down (i.e. voltage)
+    Gauge,
+    /// Value which only increases until device reset (i.e. number of cycles)
+    Counter,
+    /// Vector of gauges with value per cell (or per sensor)
+    Cells,
+    /// Text which identifies device (i.e. serial number)
+    Label,
+}
+
+/// Descriptor of data field
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Field {
+    /// Field name (the same as in structure and serialized data)
+    pub name: &'static str,
+    /// Unit symbol (i.e. `V` or `A·h`, empty for dimensionless values)
+    pub unit: &'static str,
+    /// Unit name used as suffix of metric names (i.e. `volts` or `ampere_hours`)
+    pub unit_name: &'static str,
+    /// Human readable description
+    pub description: &'static str,
+    /// Kind of value (also defines type of metric)
+    pub kind: FieldKind,
+    /// Number of fractional digits reported by device (i.e. 3 for millivolts)
+    pub scale: u32,
+    /// Minimal change of value which is considered significant
+    pub sensitivity: f64,
+}
+
+impl Field {
+    /// Description with unit (i.e. `Voltages of cells, V`)
+    pub fn help(&self) -> String {
+        help(self.description, self.unit)
+    }
+}
+
+/// Value of data field
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldValue<'a> {
+    /// Text of label
+    Label(&'a str),
+    /// Whole number
+    Integer(u64),
+    /// Value with three fractional digits
+    Milli(Milli),
+    /// Value with one fractional digit
+    Deci(Deci),
+    /// Values of cells with three fractional digits
+    MilliCells(&'a [Milli]),
+    /// Values of cells with one fractional digit
+    DeciCells(&'a [Deci]),
+}
+
+impl FieldValue<'_> {
+    /// Number of per-cell values (zero for single value)
+    pub fn cells(&self) -> usize {
+        match self {
+            Self::MilliCells(values) => values.len(),
+            Self::DeciCells(values) => values.len(),
+            _ => 0,
+        }
+    }
+
+    /// Value of cell by index
+    pub fn cell(&self, index: usize) -> Option<FieldValue<'static>> {
+        match self {
+            Self::MilliCells(values) => values.get(index).copied().map(FieldValue::Milli),
+            Self::DeciCells(values) => values.get(index).copied().map(FieldValue::Deci),
+            _ => None,
+        }
+    }
+
+    /// Numeric value (NaN for labels, cells and unknown values)
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Self::Integer(value) => *value as _,
+            Self::Milli(value) => value.to_f64(),
+            Self::Deci(value) => value.to_f64(),
+            _ => f64::NAN,
+        }
+    }
+
+    /// Whether value is known number
+    pub fn is_finite(&self) -> bool {
+        self.to_f64().is_finite()
+    }
+}
+
+impl fmt::Display for FieldValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Label(value) => fmt::Display::fmt(value, f),
+            Self::Integer(value) => fmt::Display::fmt(value, f),
+            Self::Milli(value) => fmt::Display::fmt(value, f),
+            Self::Deci(value) => fmt::Display::fmt(value, f),
+            Self::MilliCells(values) => write!(f, "{values:?}"),
+            Self::DeciCells(values) => write!(f, "{values:?}"),
+        }
+    }
+}
+
+/// Make description with unit
+pub(crate) fn help(description: &str, unit: &str) -> String {
+    if unit.is_empty() {
+        description.into()
+    } else {
+        format!("{description}, {unit}")
+    }
+}
+
+/// Declarations of data fields
+///
+/// The single source of field metadata: passes declarations to `$callback`
+/// macro, which generates field descriptors, metrics and so on. Label fields
+/// are grouped into info metric (with legacy name in parentheses).
+macro_rules! data_fields {
+    ($callback:ident) => {
+        $callback! {
+            DeviceInfo {
+                [device_info (bms_device_info): "Device identity information"] {
+                    device_model: "Model name";
+                    hardware_version: "Hardware version";
+                    software_version: "Firmware version";
+                    serial_number: "Serial number";
+                    manufacturing_date: "Manufacturing date";
+                    device_name: "Device name";
+                }
+                poweron_times: counter: usize: "": "": 1.0: "Number of poweron cycles";
+            }
+            CellData {
+                cell_voltage: cells: milli: "V": "volts": 0.005: "Voltages of cells";
+                average_cell_voltage: gauge: milli: "V": "volts": 0.005: "Average voltage of cells";
+                delta_cell_voltage: gauge: milli: "V": "volts": 0.005: "Delta voltage of cells";
+                balance_current: gauge: milli: "A": "amperes": 0.01: "Cells balance current";
+                cell_resistance: cells: milli: "Ω": "ohms": 0.001: "Resistances of cells";
+                battery_voltage: gauge: milli: "V": "volts": 0.01: "Voltage of battery";
+                battery_power: gauge: milli: "W": "watts": 0.1: "Power of battery";
+                battery_current: gauge: milli: "A": "amperes": 0.01: "Current of battery";
+                battery_temperature: cells: deci: "℃": "celsius": 0.5: "Temperatures of battery";
+                mosfet_temperature: gauge: deci: "℃": "celsius": 0.5: "Temperature of mosfet";
+                remain_percent: gauge: u8: "%": "percent": 1.0: "Remain capacity of battery";
+                remain_capacity: gauge: milli: "A·h": "ampere_hours": 0.01: "Remain capacity of battery";
+                nominal_capacity: gauge: milli: "A·h": "ampere_hours": 0.01: "Nominal capacity of battery";
+                cycle_count: counter: usize: "": "": 1.0: "Number of battery cycles";
+                cycle_capacity: counter: milli: "A·h": "ampere_hours": 0.01: "Cycle capacity";
+                up_time: counter: usize: "s": "seconds": 60.0: "Time since last poweron";
+            }
+        }
+    };
+}
+
+macro_rules! fields_impl {
+    ( $($class:ident {
+        $([$info:ident ($info_legacy:ident): $info_help:literal] {
+            $($label:ident: $label_description:literal;)*
+        })?
+        $($name:ident: $kind:ident: $type:ident: $unit:literal: $unit_name:literal:
+          $sensitivity:literal: $description:literal;)*
+    })* ) => {
+        $(impl $class {
+            /// Descriptors of fields
+            pub const FIELDS: &'static [Field] = &[
+                $($(Field {
+                    name: stringify!($label),
+                    unit: "",
+                    unit_name: "",
+                    description: $label_description,
+                    kind: FieldKind::Label,
+                    scale: 0,
+                    sensitivity: 0.0,
+                },)*)?
+                $(Field {
+                    name: stringify!($name),
+                    unit: $unit,
+                    unit_name: $unit_name,
+                    description: $description,
+                    kind: fields_impl!(@kind $kind),
+                    scale: fields_impl!(@scale $type),
+                    sensitivity: $sensitivity,
+                },)*
+            ];
+
+            /// Descriptor of field by name
+            pub fn field(name: &str) -> Option<&'static Field> {
+                Self::FIELDS.iter().find(|field| field.name == name)
+            }
+
+            /// Descriptors of fields with values (in the same order)
+            pub fn values(&self) -> impl Iterator<Item = (&'static Field, FieldValue<'_>)> {
+                Self::FIELDS.iter().zip([
+                    $($(FieldValue::Label(&self.$label),)*)?
+                    $(fields_impl!(@value $kind, $type, self.$name),)*
+                ])
+            }
+        })*
+    };
+
+    (@kind gauge) => { FieldKind::Gauge };
+    (@kind counter) => { FieldKind::Counter };
+    (@kind cells) => { FieldKind::Cells };
+
+    (@value cells, milli, $value:expr) => { FieldValue::MilliCells(&$value) };
+    (@value cells, deci, $value:expr) => { FieldValue::DeciCells(&$value) };
+    (@value $kind:ident, milli, $value:expr) => { FieldValue::Milli($value) };
+    (@value $kind:ident, deci, $value:expr) => { FieldValue::Deci($value) };
+    (@value $kind:ident, $type:ident, $value:expr) => { FieldValue::Integer($value as _) };
+
+    (@scale milli) => { 3 };
+    (@scale deci) => { 1 };
+    (@scale $type:ident) => { 0 };
+}
+
+data_fields!(fields_impl);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fields() {
+        let field = CellData::field("cell_voltage").unwrap();
+        assert_eq!(field.kind, FieldKind::Cells);
+        assert_eq!(field.unit, "V");
+        assert_eq!(field.scale, 3);
+        assert_eq!(field.help(), "Voltages of cells, V");
+
+        let field = CellData::field("mosfet_temperature").unwrap();
+        assert_eq!(field.scale, 1);
+        assert_eq!(field.sensitivity, 0.5);
+
+        let field = CellData::field("cycle_count").unwrap();
+        assert_eq!(field.kind, FieldKind::Counter);
+        assert_eq!(field.help(), "Number of battery cycles");
+
+        assert!(CellData::field("unknown").is_none());
+        assert_eq!(CellData::FIELDS.len(), 16);
+        assert!(DeviceInfo::FIELDS[..6]
+            .iter()
+            .all(|field| field.kind == FieldKind::Label));
+    }
+
+    #[test]
+    fn values() {
+        let cell_data = CellData {
+            cell_voltage: [3.3, 3.31].map(Milli::from_f64).to_vec(),
+            mosfet_temperature: Deci::NAN,
+            cycle_count: 2,
+            ..Default::default()
+        };
+        let values: Vec<_> = cell_data.values().collect();
+        assert_eq!(values.len(), CellData::FIELDS.len());
+
+        let (field, value) = values[0];
+        assert_eq!(field.name, "cell_voltage");
+        assert_eq!(value.cells(), 2);
+        assert_eq!(
+            value.cell(1),
+            Some(FieldValue::Milli(Milli::from_f64(3.31)))
+        );
+        assert_eq!(value.cell(2), None);
+        assert!(!value.is_finite());
+
+        let value = |name| {
+            values
+                .iter()
+                .find(|(field, _)| field.name == name)
+                .unwrap()
+                .1
+        };
+        assert!(!value("mosfet_temperature").is_finite());
+        assert_eq!(value("cycle_count"), FieldValue::Integer(2));
+        assert_eq!(value("cycle_count").cells(), 0);
+
+        let device_info = DeviceInfo {
+            serial_number: "40531310629".into(),
+            ..Default::default()
+        };
+        let (field, value) = device_info.values().nth(3).unwrap();
+        assert_eq!(field.name, "serial_number");
+        assert_eq!(value.to_string(), "40531310629");
+    }
+}
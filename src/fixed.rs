@@ -0,0 +1,229 @@
+use core::{cmp::Ordering, fmt, str::FromStr};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Fixed-point decimal number with `DIGITS` fractional digits
+///
+/// Keeps raw integer value reported by device (i.e. millivolts for volts with
+/// three digits), so values are formatted and serialized exactly: `2.384`
+/// instead of `2.384000062942505`. Missing value is represented by
+/// [`Fixed::NAN`], which is serialized like `f64::NAN`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Fixed<const DIGITS: u32>(i32);
+
+/// Value with three fractional digits (millivolts, milliamperes, milliohms and so on)
+pub type Milli = Fixed<3>;
+
+/// Value with single fractional digit (tenths of degree)
+pub type Deci = Fixed<1>;
+
+impl<const DIGITS: u32> Fixed<DIGITS> {
+    /// Zero value
+    pub const ZERO: Self = Self(0);
+
+    /// Not a number (missing value)
+    pub const NAN: Self = Self(i32::MIN);
+
+    /// Number of raw units in one
+    pub const SCALE: i32 = 10i32.pow(DIGITS);
+
+    /// Create value from raw integer (i.e. millivolts)
+    pub const fn from_raw(raw: i32) -> Self {
+        Self(raw)
+    }
+
+    /// Raw integer value (i.e. millivolts)
+    pub const fn raw(self) -> i32 {
+        self.0
+    }
+
+    /// Create value rounding float to nearest raw unit (NaN and infinity as NaN)
+    pub fn from_f64(value: f64) -> Self {
+        if value.is_finite() {
+            Self(
+                (value * Self::SCALE as f64)
+                    .round()
+                    .clamp(i32::MIN as f64 + 1.0, i32::MAX as f64) as i32,
+            )
+        } else {
+            Self::NAN
+        }
+    }
+
+    /// Value is missing
+    pub const fn is_nan(self) -> bool {
+        self.0 == i32::MIN
+    }
+
+    /// Value is present
+    pub const fn is_finite(self) -> bool {
+        !self.is_nan()
+    }
+
+    /// Value as float (exact for decimal representation)
+    pub fn to_f64(self) -> f64 {
+        if self.is_nan() {
+            f64::NAN
+        } else {
+            self.0 as f64 / Self::SCALE as f64
+        }
+    }
+
+    /// Value as single precision float
+    pub fn to_f32(self) -> f32 {
+        self.to_f64() as f32
+    }
+}
+
+impl<const DIGITS: u32> From<Fixed<DIGITS>> for f64 {
+    fn from(value: Fixed<DIGITS>) -> Self {
+        value.to_f64()
+    }
+}
+
+impl<const DIGITS: u32> From<Fixed<DIGITS>> for f32 {
+    fn from(value: Fixed<DIGITS>) -> Self {
+        value.to_f32()
+    }
+}
+
+impl<const DIGITS: u32> PartialOrd for Fixed<DIGITS> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.is_nan() || other.is_nan() {
+            None
+        } else {
+            Some(self.0.cmp(&other.0))
+        }
+    }
+}
+
+impl<const DIGITS: u32> fmt::Display for Fixed<DIGITS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_nan() || f.precision().is_some() {
+            // rounding to requested precision
+            return fmt::Display::fmt(&self.to_f64(), f);
+        }
+
+        let raw = self.0.unsigned_abs();
+        let scale = Self::SCALE.unsigned_abs();
+        let mut text = format!("{}{}", if self.0 < 0 { "-" } else { "" }, raw / scale);
+        let fraction = raw % scale;
+        if fraction != 0 {
+            let fraction = format!("{fraction:0width$}", width = DIGITS as usize);
+            text.push('.');
+            text.push_str(fraction.trim_end_matches('0'));
+        }
+        f.pad(&text)
+    }
+}
+
+impl<const DIGITS: u32> fmt::Debug for Fixed<DIGITS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl<const DIGITS: u32> FromStr for Fixed<DIGITS> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("nan") {
+            return Ok(Self::NAN);
+        }
+
+        let error = || format!("Bad decimal value: {s}");
+
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+        if (integer.is_empty() && fraction.is_empty())
+            || fraction.len() > DIGITS as usize
+            || !integer
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(error());
+        }
+
+        let raw = format!("{integer}{fraction:0<width$}", width = DIGITS as usize)
+            .parse::<i64>()
+            .map_err(|_| error())?;
+        let raw = if negative { -raw } else { raw };
+
+        if raw <= i32::MIN as i64 || raw > i32::MAX as i64 {
+            return Err(error());
+        }
+
+        Ok(Self(raw as i32))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<const DIGITS: u32> Serialize for Fixed<DIGITS> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const DIGITS: u32> Deserialize<'de> for Fixed<DIGITS> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Option::<f64>::deserialize(deserializer)?
+            .map(Self::from_f64)
+            .unwrap_or(Self::NAN))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format() {
+        assert_eq!(Milli::from_raw(2384).to_string(), "2.384");
+        assert_eq!(Milli::from_raw(9540).to_string(), "9.54");
+        assert_eq!(Milli::from_raw(-78).to_string(), "-0.078");
+        assert_eq!(Milli::from_raw(3000).to_string(), "3");
+        assert_eq!(Deci::from_raw(-259).to_string(), "-25.9");
+        assert_eq!(Milli::NAN.to_string(), "NaN");
+        assert_eq!(format!("{:.2}", Milli::from_raw(14306)), "14.31");
+        assert_eq!(format!("{:>7}", Milli::from_raw(2384)), "  2.384");
+        assert_eq!(format!("{:?}", [Milli::from_raw(138)]), "[0.138]");
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("2.384".parse(), Ok(Milli::from_raw(2384)));
+        assert_eq!("-0.5".parse(), Ok(Milli::from_raw(-500)));
+        assert_eq!("12".parse(), Ok(Deci::from_raw(120)));
+        assert!("NaN".parse::<Milli>().unwrap().is_nan());
+        assert!("2.3845".parse::<Milli>().is_err());
+        assert!("2,384".parse::<Milli>().is_err());
+        assert!(".".parse::<Milli>().is_err());
+    }
+
+    #[test]
+    fn convert() {
+        assert_eq!(Milli::from_raw(2383).to_f64(), 2.383);
+        assert_eq!(Milli::from_f64(3.301), Milli::from_raw(3301));
+        assert!(Milli::from_f64(f64::NAN).is_nan());
+        assert!(Milli::NAN.to_f64().is_nan());
+        assert!(Milli::NAN.partial_cmp(&Milli::ZERO).is_none());
+        assert!(Milli::from_raw(1) > Milli::ZERO);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn serde() {
+        let values = [Milli::from_raw(2384), Milli::from_raw(14306), Milli::NAN];
+        let json = serde_json::to_string(&values).unwrap();
+        assert_eq!(json, "[2.384,14.306,null]");
+        let decoded: Vec<Milli> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, values);
+    }
+}
@@ -19,8 +19,20 @@ pub enum Format {
     Toml,
     #[cfg(feature = "toml")]
     TomlPretty,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "msgpack")]
+    MsgPack,
     #[cfg(feature = "metrics")]
     Metrics,
+    #[cfg(feature = "influx")]
+    Influx,
+    #[cfg(feature = "csv")]
+    Csv,
+    #[cfg(feature = "json")]
+    NdJson,
+    #[cfg(feature = "table")]
+    Table,
 }
 
 impl core::str::FromStr for Format {
@@ -40,14 +52,95 @@ impl core::str::FromStr for Format {
             "t" | "toml" => Self::Toml,
             #[cfg(feature = "toml")]
             "T" | "toml-pretty" => Self::TomlPretty,
+            #[cfg(feature = "cbor")]
+            "b" | "cbor" => Self::Cbor,
+            #[cfg(feature = "msgpack")]
+            "p" | "msgpack" => Self::MsgPack,
             #[cfg(feature = "metrics")]
             "m" | "metrics" => Self::Metrics,
+            #[cfg(feature = "influx")]
+            "i" | "influx" => Self::Influx,
+            #[cfg(feature = "csv")]
+            "c" | "csv" => Self::Csv,
+            #[cfg(feature = "json")]
+            "n" | "ndjson" => Self::NdJson,
+            #[cfg(feature = "table")]
+            "a" | "table" => Self::Table,
             _ => return Err(format!("Unknown data format: {s}")),
         })
     }
 }
 
 impl Format {
+    /// Select data format by HTTP `Accept` header
+    ///
+    /// The supported media range with the highest quality wins (the first one when equal).
+    #[cfg(feature = "json")]
+    pub fn from_accept(accept: impl AsRef<[u8]>) -> Option<Self> {
+        let accept = core::str::from_utf8(accept.as_ref()).ok()?;
+        let mut best: Option<(Self, f32)> = None;
+
+        for range in accept.split(',') {
+            let mut params = range.split(';');
+            let Some(format) = params.next().and_then(Self::from_media_range) else {
+                continue;
+            };
+            let quality = params
+                .filter_map(|param| param.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+                .map(|(_, value)| value.trim().parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+
+            if quality > 0.0 && best.map(|(_, best)| quality > best).unwrap_or(true) {
+                best = Some((format, quality));
+            }
+        }
+
+        best.map(|(format, _)| format)
+    }
+
+    /// Select data format by media range of HTTP `Accept` header
+    #[cfg(feature = "json")]
+    fn from_media_range(range: &str) -> Option<Self> {
+        Some(match range.trim().to_ascii_lowercase().as_str() {
+            "application/json" | "application/*" | "*/*" => Self::Json,
+            #[cfg(feature = "cbor")]
+            "application/cbor" => Self::Cbor,
+            #[cfg(feature = "msgpack")]
+            "application/msgpack" | "application/vnd.msgpack" | "application/x-msgpack" => {
+                Self::MsgPack
+            }
+            _ => return None,
+        })
+    }
+
+    /// Media type of formatted values
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Self::Rust | Self::RustPretty => "text/plain",
+            #[cfg(feature = "json")]
+            Self::Json | Self::JsonPretty => "application/json",
+            #[cfg(feature = "yaml")]
+            Self::Yaml => "application/yaml",
+            #[cfg(feature = "toml")]
+            Self::Toml | Self::TomlPretty => "application/toml",
+            #[cfg(feature = "cbor")]
+            Self::Cbor => "application/cbor",
+            #[cfg(feature = "msgpack")]
+            Self::MsgPack => "application/msgpack",
+            #[cfg(feature = "metrics")]
+            Self::Metrics => "text/plain; version=0.0.4",
+            #[cfg(feature = "influx")]
+            Self::Influx => "text/plain",
+            #[cfg(feature = "csv")]
+            Self::Csv => "text/csv",
+            #[cfg(feature = "json")]
+            Self::NdJson => "application/x-ndjson",
+            #[cfg(feature = "table")]
+            Self::Table => "text/plain",
+        }
+    }
+
     #[cfg(not(feature = "serde"))]
     pub fn format_value<T: Debug>(&self, value: &T, output: &mut dyn Write) -> Result<()> {
         match self {
@@ -55,6 +148,14 @@ impl Format {
             Self::RustPretty => write!(output, "{value:#?}")?,
             #[cfg(feature = "metrics")]
             Self::Metrics => {}
+            #[cfg(feature = "influx")]
+            Self::Influx => {}
+            #[cfg(feature = "csv")]
+            Self::Csv => {}
+            #[cfg(feature = "json")]
+            Self::NdJson => {}
+            #[cfg(feature = "table")]
+            Self::Table => {}
         }
         Ok(())
     }
@@ -78,9 +179,102 @@ impl Format {
             Self::Toml => write!(output, "{}", serde_toml::to_string(value)?)?,
             #[cfg(feature = "toml")]
             Self::TomlPretty => write!(output, "{}", serde_toml::to_string_pretty(value)?)?,
+            #[cfg(feature = "cbor")]
+            Self::Cbor => ciborium::into_writer(value, output)?,
+            #[cfg(feature = "msgpack")]
+            Self::MsgPack => rmp_serde::encode::write_named(output, value)?,
             #[cfg(feature = "metrics")]
             Self::Metrics => {}
+            #[cfg(feature = "influx")]
+            Self::Influx => {}
+            #[cfg(feature = "csv")]
+            Self::Csv => {}
+            #[cfg(feature = "json")]
+            Self::NdJson => {}
+            #[cfg(feature = "table")]
+            Self::Table => {}
         }
         Ok(())
     }
 }
+
+#[cfg(all(test, feature = "json"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn accept() {
+        assert!(matches!(
+            Format::from_accept("application/json"),
+            Some(Format::Json)
+        ));
+        assert!(matches!(Format::from_accept("*/*"), Some(Format::Json)));
+        assert!(matches!(
+            Format::from_accept("text/html, */*;q=0.8"),
+            Some(Format::Json)
+        ));
+        assert!(Format::from_accept("text/html").is_none());
+        assert!(Format::from_accept("application/json;q=0").is_none());
+        assert!(Format::from_accept("application/jsonp").is_none());
+        #[cfg(feature = "cbor")]
+        {
+            assert!(matches!(
+                Format::from_accept("application/cbor, application/json;q=0.5"),
+                Some(Format::Cbor)
+            ));
+            assert!(matches!(
+                Format::from_accept("application/json, application/cbor;q=0.1"),
+                Some(Format::Json)
+            ));
+            assert!(matches!(
+                Format::from_accept("application/json;q=0.2, application/cbor;Q=0.9"),
+                Some(Format::Cbor)
+            ));
+        }
+        #[cfg(feature = "msgpack")]
+        {
+            assert!(matches!(
+                Format::from_accept("application/vnd.msgpack"),
+                Some(Format::MsgPack)
+            ));
+            assert!(Format::from_accept("application/x-msgpack-foo").is_none());
+        }
+    }
+
+    #[cfg(any(feature = "cbor", feature = "msgpack"))]
+    #[test]
+    fn binary() {
+        use crate::{CellData, Milli};
+
+        let cell_data = CellData {
+            cell_voltage: [2.384, 2.386].map(Milli::from_f64).to_vec(),
+            battery_voltage: Milli::from_f64(4.77),
+            remain_percent: 100,
+            ..Default::default()
+        };
+
+        let mut json = Vec::new();
+        Format::Json.format_value(&cell_data, &mut json).unwrap();
+
+        #[cfg(feature = "cbor")]
+        {
+            let mut cbor = Vec::new();
+            Format::Cbor.format_value(&cell_data, &mut cbor).unwrap();
+            assert!(cbor.len() < json.len());
+            let decoded: CellData = ciborium::from_reader(cbor.as_slice()).unwrap();
+            assert_eq!(decoded.cell_voltage, cell_data.cell_voltage);
+            assert_eq!(decoded.remain_percent, 100);
+        }
+
+        #[cfg(feature = "msgpack")]
+        {
+            let mut msgpack = Vec::new();
+            Format::MsgPack
+                .format_value(&cell_data, &mut msgpack)
+                .unwrap();
+            let decoded: CellData = rmp_serde::from_slice(&msgpack).unwrap();
+            assert_eq!(decoded.cell_voltage, cell_data.cell_voltage);
+            assert_eq!(decoded.battery_voltage, cell_data.battery_voltage);
+        }
+    }
+}
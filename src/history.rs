@@ -0,0 +1,811 @@
+use crate::{
+    cmdline::unix_time, exporter::Sample, log, CellData, DeviceInfo, Error, Fixed, Format,
+    HistoryQuery, Main, Result,
+};
+use core::time::Duration;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Row, Transaction};
+use std::{path::Path, sync::Mutex, time::Instant};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+#[cfg(feature = "csv")]
+use crate::CsvColumns;
+
+#[cfg(feature = "influx")]
+use crate::Influx;
+
+#[cfg(feature = "json")]
+use crate::cmdline::Record;
+
+#[cfg(any(feature = "influx", feature = "csv", feature = "json"))]
+use std::io::Write;
+
+/// Version of database schema
+const SCHEMA_VERSION: i32 = 2;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS devices (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS device_info (
+    device INTEGER NOT NULL REFERENCES devices (id) ON DELETE CASCADE,
+    time REAL NOT NULL,
+    device_model TEXT NOT NULL,
+    hardware_version TEXT NOT NULL,
+    software_version TEXT NOT NULL,
+    up_time INTEGER NOT NULL,
+    poweron_times INTEGER NOT NULL,
+    device_name TEXT NOT NULL,
+    manufacturing_date TEXT NOT NULL,
+    serial_number TEXT NOT NULL,
+    PRIMARY KEY (device, time)
+);
+
+CREATE TABLE IF NOT EXISTS cell_data (
+    id INTEGER PRIMARY KEY,
+    device INTEGER NOT NULL REFERENCES devices (id) ON DELETE CASCADE,
+    time REAL NOT NULL,
+    battery_voltage REAL,
+    battery_current REAL,
+    battery_power REAL,
+    remain_percent INTEGER NOT NULL,
+    remain_capacity REAL,
+    nominal_capacity REAL,
+    cycle_count INTEGER NOT NULL,
+    cycle_capacity REAL,
+    average_cell_voltage REAL,
+    delta_cell_voltage REAL,
+    balance_current REAL,
+    mosfet_temperature REAL,
+    up_time INTEGER NOT NULL,
+    cell_voltage_count INTEGER,
+    cell_resistance_count INTEGER,
+    battery_temperature_count INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS cell_data_time ON cell_data (device, time);
+
+CREATE TABLE IF NOT EXISTS cells (
+    sample INTEGER NOT NULL REFERENCES cell_data (id) ON DELETE CASCADE,
+    cell INTEGER NOT NULL,
+    voltage REAL,
+    resistance REAL,
+    temperature REAL,
+    PRIMARY KEY (sample, cell)
+) WITHOUT ROWID;
+";
+
+/// Upgrade of schema version 1 which had no numbers of per-cell values
+const MIGRATION_V2: &str = "
+ALTER TABLE cell_data ADD COLUMN cell_voltage_count INTEGER;
+ALTER TABLE cell_data ADD COLUMN cell_resistance_count INTEGER;
+ALTER TABLE cell_data ADD COLUMN battery_temperature_count INTEGER;
+";
+
+/// Minimum interval between removals of old samples
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Retention policy of stored samples
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetentionPolicy {
+    /// Remove samples older than this age
+    pub max_age: Option<Duration>,
+    /// Thin out samples older than this age
+    pub downsample_age: Option<Duration>,
+    /// Keep single sample per this interval when thinning out
+    pub downsample_interval: Duration,
+}
+
+/// Stored sample of device
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Entry<T> {
+    /// Unix time of sample, S
+    pub timestamp: f64,
+    /// Device alias or identifier
+    pub device: String,
+    /// Sample data
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub data: T,
+}
+
+/// Result of history query
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct HistoryOutputs {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub device_info: Vec<Entry<DeviceInfo>>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub cell_data: Vec<Entry<CellData>>,
+}
+
+/// SQLite store of samples
+///
+/// Device info is stored only when it changes (excluding up time), cell data
+/// is stored on each scrape with per-cell values in separate table.
+pub struct History {
+    connection: Mutex<Connection>,
+    policy: RetentionPolicy,
+    /// Time of last removal of old samples
+    last_prune: Mutex<Option<Instant>>,
+}
+
+impl History {
+    /// Open database for writing (create when missing)
+    pub fn open(path: &Path, policy: RetentionPolicy) -> Result<Self> {
+        Self::init(Connection::open(path)?, policy)
+    }
+
+    /// Open existing database for reading
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+        match schema_version(&connection)? {
+            SCHEMA_VERSION => (),
+            0 => {
+                return Err(Error::BadConfig(format!(
+                    "No history found in '{}'",
+                    path.display()
+                )))
+            }
+            version => {
+                return Err(Error::BadConfig(format!(
+                    "Unsupported version of history schema: {version} (store samples to upgrade)"
+                )))
+            }
+        }
+
+        Ok(Self::new(connection, Default::default()))
+    }
+
+    fn init(connection: Connection, policy: RetentionPolicy) -> Result<Self> {
+        let version = schema_version(&connection)?;
+        if version > SCHEMA_VERSION {
+            return Err(Error::BadConfig(format!(
+                "Unsupported version of history schema: {version}"
+            )));
+        }
+
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "foreign_keys", true)?;
+        connection.execute_batch(SCHEMA)?;
+        if version == 1 {
+            connection.execute_batch(MIGRATION_V2)?;
+        }
+        connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+
+        Ok(Self::new(connection, policy))
+    }
+
+    fn new(connection: Connection, policy: RetentionPolicy) -> Self {
+        Self {
+            connection: Mutex::new(connection),
+            policy,
+            last_prune: Mutex::new(None),
+        }
+    }
+
+    /// Store samples of device (errors are logged)
+    pub fn store(
+        &self,
+        device: &str,
+        device_info: Option<&Sample<DeviceInfo>>,
+        cell_data: Option<&Sample<CellData>>,
+    ) {
+        if let Err(error) = self.try_store(device, device_info, cell_data) {
+            log::error!("Error while storing history of '{device}': {error}");
+        }
+
+        let mut last_prune = self.last_prune.lock().unwrap();
+        if last_prune
+            .map(|last_prune| last_prune.elapsed() >= PRUNE_INTERVAL)
+            .unwrap_or(true)
+        {
+            *last_prune = Some(Instant::now());
+            if let Err(error) = self.prune(unix_time().as_secs_f64()) {
+                log::error!("Error while removing old history: {error}");
+            }
+        }
+    }
+
+    fn try_store(
+        &self,
+        device: &str,
+        device_info: Option<&Sample<DeviceInfo>>,
+        cell_data: Option<&Sample<CellData>>,
+    ) -> Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+
+        transaction.execute("INSERT OR IGNORE INTO devices (name) VALUES (?1)", [device])?;
+        let device: i64 =
+            transaction.query_row("SELECT id FROM devices WHERE name = ?1", [device], |row| {
+                row.get(0)
+            })?;
+
+        if let Some(sample) = device_info {
+            store_device_info(&transaction, device, sample)?;
+        }
+
+        if let Some(sample) = cell_data {
+            store_cell_data(&transaction, device, sample)?;
+        }
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Remove and thin out old samples according to retention policy
+    fn prune(&self, now: f64) -> Result<()> {
+        let connection = self.connection.lock().unwrap();
+
+        if let Some(max_age) = self.policy.max_age {
+            let time = now - max_age.as_secs_f64();
+            let removed = connection.execute("DELETE FROM cell_data WHERE time < ?1", [time])?;
+            // keep the latest device info to know identity of device
+            connection.execute(
+                "DELETE FROM device_info WHERE time < ?1 AND time < (
+                    SELECT MAX(time) FROM device_info AS latest
+                    WHERE latest.device = device_info.device
+                )",
+                [time],
+            )?;
+            log::debug!("Removed {removed} old samples");
+        }
+
+        if let Some(downsample_age) = self.policy.downsample_age {
+            let time = now - downsample_age.as_secs_f64();
+            let interval = self.policy.downsample_interval.as_secs_f64().max(1.0);
+            // keep the first sample in each interval
+            let removed = connection.execute(
+                "DELETE FROM cell_data WHERE time < ?1 AND id NOT IN (
+                    SELECT MIN(id) FROM cell_data WHERE time < ?1
+                    GROUP BY device, CAST(time / ?2 AS INTEGER)
+                )",
+                params![time, interval],
+            )?;
+            log::debug!("Thinned out {removed} old samples");
+        }
+
+        Ok(())
+    }
+
+    /// Names of stored devices
+    pub fn devices(&self) -> Result<Vec<String>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT name FROM devices ORDER BY name")?;
+        let names = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(names)
+    }
+
+    /// Device info in time range (including one in effect at the start)
+    pub fn device_info(&self, device: &str, from: f64, to: f64) -> Result<Vec<Entry<DeviceInfo>>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare_cached(
+            "SELECT time, device_model, hardware_version, software_version, up_time,
+                poweron_times, device_name, manufacturing_date, serial_number
+            FROM device_info JOIN devices ON devices.id = device_info.device
+            WHERE devices.name = ?1 AND time <= ?3 AND time >= IFNULL((
+                SELECT MAX(time) FROM device_info AS start
+                WHERE start.device = devices.id AND start.time <= ?2
+            ), ?2)
+            ORDER BY time",
+        )?;
+        let entries = statement
+            .query_map(params![device, from, to], |row| {
+                Ok(Entry {
+                    timestamp: row.get(0)?,
+                    device: device.into(),
+                    data: DeviceInfo {
+                        device_model: row.get(1)?,
+                        hardware_version: row.get(2)?,
+                        software_version: row.get(3)?,
+                        up_time: row.get(4)?,
+                        poweron_times: row.get(5)?,
+                        device_name: row.get(6)?,
+                        manufacturing_date: row.get(7)?,
+                        serial_number: row.get(8)?,
+                        ..Default::default()
+                    },
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(entries)
+    }
+
+    /// Cell data in time range
+    pub fn cell_data(&self, device: &str, from: f64, to: f64) -> Result<Vec<Entry<CellData>>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare_cached(
+            "SELECT cell_data.id, time, battery_voltage, battery_current, battery_power,
+                remain_percent, remain_capacity, nominal_capacity, cycle_count, cycle_capacity,
+                average_cell_voltage, delta_cell_voltage, balance_current, mosfet_temperature,
+                up_time, cell_voltage_count, cell_resistance_count, battery_temperature_count
+            FROM cell_data JOIN devices ON devices.id = cell_data.device
+            WHERE devices.name = ?1 AND time >= ?2 AND time <= ?3
+            ORDER BY time",
+        )?;
+        let mut cells = connection.prepare_cached(
+            "SELECT cell, voltage, resistance, temperature FROM cells
+            WHERE sample = ?1 ORDER BY cell",
+        )?;
+
+        let mut entries = Vec::new();
+        let mut rows = statement.query(params![device, from, to])?;
+
+        while let Some(row) = rows.next()? {
+            let mut data = CellData {
+                battery_voltage: fixed(row, 2)?,
+                battery_current: fixed(row, 3)?,
+                battery_power: fixed(row, 4)?,
+                remain_percent: row.get(5)?,
+                remain_capacity: fixed(row, 6)?,
+                nominal_capacity: fixed(row, 7)?,
+                cycle_count: row.get(8)?,
+                cycle_capacity: fixed(row, 9)?,
+                average_cell_voltage: fixed(row, 10)?,
+                delta_cell_voltage: fixed(row, 11)?,
+                balance_current: fixed(row, 12)?,
+                mosfet_temperature: fixed(row, 13)?,
+                up_time: row.get(14)?,
+                ..Default::default()
+            };
+
+            let mut values = cells.query([row.get::<_, i64>(0)?])?;
+            while let Some(values) = values.next()? {
+                let cell: usize = values.get(0)?;
+                for (index, vector) in [&mut data.cell_voltage, &mut data.cell_resistance]
+                    .into_iter()
+                    .enumerate()
+                {
+                    cell_value(vector, cell, values.get(index + 1)?);
+                }
+                cell_value(&mut data.battery_temperature, cell, values.get(3)?);
+            }
+
+            // keep trailing missing values (unknown for samples stored by older versions)
+            for (index, vector) in [&mut data.cell_voltage, &mut data.cell_resistance]
+                .into_iter()
+                .enumerate()
+            {
+                cell_count(vector, row.get(index + 15)?);
+            }
+            cell_count(&mut data.battery_temperature, row.get(17)?);
+
+            entries.push(Entry {
+                timestamp: row.get(1)?,
+                device: device.into(),
+                data,
+            });
+        }
+
+        Ok(entries)
+    }
+}
+
+fn schema_version(connection: &Connection) -> Result<i32> {
+    Ok(connection.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+/// SQLite has no NaN so store it as NULL
+fn float<const DIGITS: u32>(value: Fixed<DIGITS>) -> Option<f64> {
+    value.is_finite().then(|| value.to_f64())
+}
+
+/// Read nullable column (NULL as NaN)
+fn fixed<const DIGITS: u32>(row: &Row, index: usize) -> rusqlite::Result<Fixed<DIGITS>> {
+    Ok(row
+        .get::<_, Option<f64>>(index)?
+        .map(Fixed::from_f64)
+        .unwrap_or(Fixed::NAN))
+}
+
+/// Put value of cell to vector filling gaps with NaN
+fn cell_value<const DIGITS: u32>(vector: &mut Vec<Fixed<DIGITS>>, cell: usize, value: Option<f64>) {
+    if let Some(value) = value {
+        vector.resize(cell, Fixed::NAN);
+        vector.push(Fixed::from_f64(value));
+    }
+}
+
+/// Resize vector to stored number of values filling gaps with NaN
+fn cell_count<const DIGITS: u32>(vector: &mut Vec<Fixed<DIGITS>>, count: Option<usize>) {
+    if let Some(count) = count {
+        vector.resize(count, Fixed::NAN);
+    }
+}
+
+fn store_device_info(
+    transaction: &Transaction,
+    device: i64,
+    sample: &Sample<DeviceInfo>,
+) -> Result<()> {
+    let info = &sample.data;
+
+    let unchanged = transaction
+        .query_row(
+            "SELECT device_model = ?2 AND hardware_version = ?3 AND software_version = ?4
+                AND poweron_times = ?5 AND device_name = ?6 AND manufacturing_date = ?7
+                AND serial_number = ?8
+            FROM device_info WHERE device = ?1 ORDER BY time DESC LIMIT 1",
+            params![
+                device,
+                info.device_model,
+                info.hardware_version,
+                info.software_version,
+                info.poweron_times,
+                info.device_name,
+                info.manufacturing_date,
+                info.serial_number,
+            ],
+            |row| row.get::<_, bool>(0),
+        )
+        .optional()?
+        .unwrap_or(false);
+
+    if !unchanged {
+        transaction.execute(
+            "INSERT OR REPLACE INTO device_info (device, time, device_model, hardware_version,
+                software_version, up_time, poweron_times, device_name, manufacturing_date,
+                serial_number)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                device,
+                sample.timestamp,
+                info.device_model,
+                info.hardware_version,
+                info.software_version,
+                info.up_time,
+                info.poweron_times,
+                info.device_name,
+                info.manufacturing_date,
+                info.serial_number,
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn store_cell_data(
+    transaction: &Transaction,
+    device: i64,
+    sample: &Sample<CellData>,
+) -> Result<()> {
+    let data = &sample.data;
+
+    transaction.execute(
+        "INSERT INTO cell_data (device, time, battery_voltage, battery_current, battery_power,
+            remain_percent, remain_capacity, nominal_capacity, cycle_count, cycle_capacity,
+            average_cell_voltage, delta_cell_voltage, balance_current, mosfet_temperature,
+            up_time, cell_voltage_count, cell_resistance_count, battery_temperature_count)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17,
+            ?18)",
+        params![
+            device,
+            sample.timestamp,
+            float(data.battery_voltage),
+            float(data.battery_current),
+            float(data.battery_power),
+            data.remain_percent,
+            float(data.remain_capacity),
+            float(data.nominal_capacity),
+            data.cycle_count,
+            float(data.cycle_capacity),
+            float(data.average_cell_voltage),
+            float(data.delta_cell_voltage),
+            float(data.balance_current),
+            float(data.mosfet_temperature),
+            data.up_time,
+            data.cell_voltage.len(),
+            data.cell_resistance.len(),
+            data.battery_temperature.len(),
+        ],
+    )?;
+    let sample = transaction.last_insert_rowid();
+
+    let cells = data
+        .cell_voltage
+        .len()
+        .max(data.cell_resistance.len())
+        .max(data.battery_temperature.len());
+
+    let mut statement = transaction.prepare_cached(
+        "INSERT INTO cells (sample, cell, voltage, resistance, temperature)
+        VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for cell in 0..cells {
+        statement.execute(params![
+            sample,
+            cell,
+            data.cell_voltage.get(cell).copied().and_then(float),
+            data.cell_resistance.get(cell).copied().and_then(float),
+            data.battery_temperature.get(cell).copied().and_then(float),
+        ])?;
+    }
+
+    Ok(())
+}
+
+impl Main {
+    /// Open history store when configured
+    pub fn history(&self) -> Result<Option<History>> {
+        self.history
+            .as_deref()
+            .map(|path| History::open(path, self.retention_policy()))
+            .transpose()
+    }
+
+    pub fn run_history_query(&self, query: &HistoryQuery) -> Result<()> {
+        let path = self.history.as_deref().ok_or_else(|| {
+            Error::BadConfig("History database is not specified: --history".into())
+        })?;
+
+        #[cfg(feature = "metrics")]
+        if matches!(self.format, Format::Metrics) {
+            return Err(Error::BadConfig(
+                "Metrics format is not supported for history".into(),
+            ));
+        }
+
+        #[cfg(feature = "table")]
+        if matches!(self.format, Format::Table) {
+            return Err(Error::BadConfig(
+                "Table format is not supported for history".into(),
+            ));
+        }
+
+        let history = History::open_read_only(path)?;
+
+        let (from, to) = query.range(unix_time().as_secs_f64());
+
+        let devices = if self.devices.is_empty() {
+            history.devices()?
+        } else {
+            self.devices.iter().map(|device| device.name()).collect()
+        };
+
+        let mut outputs = HistoryOutputs::default();
+
+        for device in &devices {
+            if self.device_info {
+                outputs
+                    .device_info
+                    .extend(history.device_info(device, from, to)?);
+            }
+            if self.cell_data || !self.device_info {
+                outputs
+                    .cell_data
+                    .extend(history.cell_data(device, from, to)?);
+            }
+        }
+
+        let mut output = std::io::stdout();
+
+        match self.format {
+            #[cfg(feature = "influx")]
+            Format::Influx => {
+                for entry in &outputs.device_info {
+                    let tags = [("device", entry.device.as_str())];
+                    let timestamp = (entry.timestamp * 1e9) as i64;
+                    entry.data.write_influx(&tags, timestamp, &mut output)?;
+                }
+                for entry in &outputs.cell_data {
+                    let tags = [("device", entry.device.as_str())];
+                    let timestamp = (entry.timestamp * 1e9) as i64;
+                    entry.data.write_influx(&tags, timestamp, &mut output)?;
+                }
+            }
+            #[cfg(feature = "csv")]
+            Format::Csv => {
+                let columns = CsvColumns::new(outputs.cell_data.iter().map(|entry| &entry.data));
+                columns.write_header(&mut output)?;
+                for entry in &outputs.cell_data {
+                    columns.write_record(
+                        entry.timestamp,
+                        &entry.device,
+                        &entry.data,
+                        &mut output,
+                    )?;
+                }
+            }
+            #[cfg(feature = "json")]
+            Format::NdJson => {
+                for entry in &outputs.device_info {
+                    Record {
+                        timestamp: entry.timestamp,
+                        device: &entry.device,
+                        device_info: Some(&entry.data),
+                        cell_data: None,
+                    }
+                    .write(&mut output)?;
+                }
+                for entry in &outputs.cell_data {
+                    Record {
+                        timestamp: entry.timestamp,
+                        device: &entry.device,
+                        device_info: None,
+                        cell_data: Some(&entry.data),
+                    }
+                    .write(&mut output)?;
+                }
+            }
+            _ => self.format.format_value(&outputs, &mut output)?,
+        }
+
+        #[cfg(any(feature = "influx", feature = "csv", feature = "json"))]
+        output.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Deci, Milli};
+
+    fn history(policy: RetentionPolicy) -> History {
+        History::init(Connection::open_in_memory().unwrap(), policy).unwrap()
+    }
+
+    fn cell_data(timestamp: f64, cycle_count: usize) -> Sample<CellData> {
+        Sample {
+            timestamp,
+            data: CellData {
+                cell_voltage: [3.3, f64::NAN, 3.31].map(Milli::from_f64).to_vec(),
+                cell_resistance: [0.138, 0.137, f64::NAN].map(Milli::from_f64).to_vec(),
+                battery_temperature: [23.5].map(Deci::from_f64).to_vec(),
+                battery_voltage: Milli::from_f64(6.61),
+                mosfet_temperature: Deci::NAN,
+                remain_percent: 98,
+                cycle_count,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn device_info(timestamp: f64, poweron_times: usize) -> Sample<DeviceInfo> {
+        Sample {
+            timestamp,
+            data: DeviceInfo {
+                device_model: "JK_BD4A8S4P".into(),
+                serial_number: "40531310629".into(),
+                up_time: timestamp as _,
+                poweron_times,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn store() {
+        let history = history(Default::default());
+
+        history.store(
+            "ups",
+            Some(&device_info(100.0, 1)),
+            Some(&cell_data(100.0, 1)),
+        );
+        history.store(
+            "ups",
+            Some(&device_info(110.0, 1)),
+            Some(&cell_data(110.0, 2)),
+        );
+        history.store("ups", Some(&device_info(120.0, 2)), None);
+        history.store("solar", None, Some(&cell_data(115.0, 3)));
+
+        assert_eq!(history.devices().unwrap(), ["solar", "ups"]);
+
+        let entries = history.cell_data("ups", 105.0, 200.0).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].timestamp, 110.0);
+        assert_eq!(entries[0].device, "ups");
+        let data = &entries[0].data;
+        assert_eq!(data.cycle_count, 2);
+        assert_eq!(data.remain_percent, 98);
+        assert_eq!(data.battery_voltage.to_string(), "6.61");
+        assert!(data.mosfet_temperature.is_nan());
+        assert_eq!(data.cell_voltage.len(), 3);
+        assert_eq!(data.cell_voltage[0].to_string(), "3.3");
+        assert!(data.cell_voltage[1].is_nan());
+        assert_eq!(data.cell_voltage[2].to_string(), "3.31");
+        assert_eq!(data.cell_resistance.len(), 3);
+        assert_eq!(data.cell_resistance[1].to_string(), "0.137");
+        assert!(data.cell_resistance[2].is_nan());
+        assert_eq!(format!("{:?}", data.battery_temperature), "[23.5]");
+
+        // unchanged device info is not stored again
+        let entries = history.device_info("ups", 0.0, 200.0).unwrap();
+        let timestamps: Vec<_> = entries.iter().map(|entry| entry.timestamp).collect();
+        assert_eq!(timestamps, [100.0, 120.0]);
+        assert_eq!(entries[1].data.poweron_times, 2);
+        assert_eq!(entries[1].data.serial_number, "40531310629");
+
+        // device info in effect at the start of range
+        let entries = history.device_info("ups", 115.0, 116.0).unwrap();
+        let timestamps: Vec<_> = entries.iter().map(|entry| entry.timestamp).collect();
+        assert_eq!(timestamps, [100.0]);
+
+        assert!(history.cell_data("unknown", 0.0, 200.0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn migration() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(&SCHEMA.replace(
+                ",\n    cell_voltage_count INTEGER,\n    cell_resistance_count INTEGER,\n    battery_temperature_count INTEGER",
+                "",
+            ))
+            .unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO devices (id, name) VALUES (1, 'ups');
+                INSERT INTO cell_data (id, device, time, remain_percent, cycle_count, up_time)
+                VALUES (1, 1, 100.0, 98, 1, 0);
+                INSERT INTO cells (sample, cell, voltage) VALUES (1, 0, 3.3), (1, 1, NULL);
+                PRAGMA user_version = 1;",
+            )
+            .unwrap();
+
+        let history = History::init(connection, Default::default()).unwrap();
+        history.store("ups", None, Some(&cell_data(110.0, 2)));
+
+        let entries = history.cell_data("ups", 0.0, 200.0).unwrap();
+        // number of values is unknown for old samples
+        assert_eq!(format!("{:?}", entries[0].data.cell_voltage), "[3.3]");
+        assert_eq!(entries[1].data.cell_resistance.len(), 3);
+    }
+
+    #[test]
+    fn retention() {
+        let history = history(RetentionPolicy {
+            max_age: Some(Duration::from_secs(1000)),
+            downsample_age: Some(Duration::from_secs(100)),
+            downsample_interval: Duration::from_secs(60),
+        });
+
+        history.store("ups", Some(&device_info(0.0, 1)), None);
+        history.store("ups", Some(&device_info(500.0, 2)), None);
+        for timestamp in (0..1200).step_by(20) {
+            history.store("ups", None, Some(&cell_data(timestamp as _, 1)));
+        }
+
+        history.prune(1200.0).unwrap();
+
+        let entries = history.cell_data("ups", 0.0, 2000.0).unwrap();
+        let samples = entries.len();
+        let timestamps: Vec<_> = entries
+            .iter()
+            .map(|entry| entry.timestamp as usize)
+            .collect();
+        assert_eq!(
+            timestamps,
+            [
+                // older than max age removed, older than downsample age thinned out
+                200, 240, 300, 360, 420, 480, 540, 600, 660, 720, 780, 840, 900, 960, 1020, 1080,
+                // newer than downsample age kept
+                1100, 1120, 1140, 1160, 1180
+            ]
+        );
+
+        // the latest device info is kept
+        let entries = history.device_info("ups", 0.0, 2000.0).unwrap();
+        let timestamps: Vec<_> = entries.iter().map(|entry| entry.timestamp).collect();
+        assert_eq!(timestamps, [500.0]);
+
+        // cells of removed samples are removed too
+        let connection = history.connection.lock().unwrap();
+        let cells: usize = connection
+            .query_row("SELECT COUNT(*) FROM cells", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(cells, samples * 3);
+    }
+}
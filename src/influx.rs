@@ -0,0 +1,191 @@
+use crate::{CellData, DeviceInfo, FieldKind, FieldValue};
+use std::io::{Result, Write};
+
+/// Measurement name of InfluxDB line protocol
+pub const INFLUX_MEASUREMENT: &str = "bms";
+
+/// The content type of InfluxDB line protocol
+pub const INFLUX_FORMAT: &str = "text/plain; charset=utf-8";
+
+/// Data which can be written using InfluxDB line protocol
+pub trait Influx {
+    /// Write lines with specified tags and timestamp in nanoseconds
+    fn write_influx(
+        &self,
+        tags: &[(&str, &str)],
+        timestamp: i64,
+        output: &mut dyn Write,
+    ) -> Result<()>;
+}
+
+impl Influx for DeviceInfo {
+    fn write_influx(
+        &self,
+        tags: &[(&str, &str)],
+        timestamp: i64,
+        output: &mut dyn Write,
+    ) -> Result<()> {
+        let mut line = Line::new(tags, None);
+        for (field, value) in self.values() {
+            line.value(field.name, value);
+        }
+        line.write(timestamp, output)
+    }
+}
+
+impl Influx for CellData {
+    fn write_influx(
+        &self,
+        tags: &[(&str, &str)],
+        timestamp: i64,
+        output: &mut dyn Write,
+    ) -> Result<()> {
+        let mut line = Line::new(tags, None);
+        for (field, value) in self.values() {
+            if field.kind != FieldKind::Cells {
+                line.value(field.name, value);
+            }
+        }
+        line.write(timestamp, output)?;
+
+        let cells = self.values().map(|(_, value)| value.cells()).max();
+        for cell in 0..cells.unwrap_or_default() {
+            let mut line = Line::new(tags, Some(cell));
+            for (field, value) in self.values() {
+                if let Some(value) = value.cell(cell) {
+                    line.value(field.name, value);
+                }
+            }
+            line.write(timestamp, output)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Line of line protocol
+struct Line {
+    /// Measurement with tags
+    series: String,
+    fields: Vec<String>,
+}
+
+impl Line {
+    fn new(tags: &[(&str, &str)], cell: Option<usize>) -> Self {
+        let mut series = escape(INFLUX_MEASUREMENT, ", ");
+        for (name, value) in tags {
+            // empty tag values are not allowed
+            if !value.is_empty() {
+                series.push_str(&format!(
+                    ",{}={}",
+                    escape(name, ",= "),
+                    escape(value, ",= ")
+                ));
+            }
+        }
+        if let Some(cell) = cell {
+            series.push_str(&format!(",cell={cell}"));
+        }
+        Self {
+            series,
+            fields: Vec::new(),
+        }
+    }
+
+    fn value(&mut self, name: &str, value: FieldValue) {
+        let name = escape(name, ",= ");
+        match value {
+            FieldValue::Label(value) => {
+                self.fields
+                    .push(format!("{name}=\"{}\"", escape(value, "\"")));
+            }
+            FieldValue::Integer(value) => self.fields.push(format!("{name}={value}i")),
+            // NaN is not supported
+            value if value.is_finite() => self.fields.push(format!("{name}={value}")),
+            _ => {}
+        }
+    }
+
+    fn write(self, timestamp: i64, output: &mut dyn Write) -> Result<()> {
+        if self.fields.is_empty() {
+            return Ok(());
+        }
+        writeln!(
+            output,
+            "{} {} {timestamp}",
+            self.series,
+            self.fields.join(",")
+        )
+    }
+}
+
+/// Escape special characters (and backslash)
+fn escape(value: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '\\' || special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Deci, Milli};
+
+    #[test]
+    fn cell_data() {
+        let cell_data = CellData {
+            cell_voltage: [3.3, 3.31].map(Milli::from_f64).to_vec(),
+            cell_resistance: [0.138, 0.137].map(Milli::from_f64).to_vec(),
+            battery_voltage: Milli::from_f64(6.61),
+            battery_temperature: [23.5].map(Deci::from_f64).to_vec(),
+            mosfet_temperature: Deci::NAN,
+            remain_percent: 100,
+            cycle_count: 1,
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        cell_data
+            .write_influx(
+                &[("device", "ups"), ("serial", ""), ("site", "my home")],
+                1707600000000000000,
+                &mut output,
+            )
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"bms,device=ups,site=my\ home average_cell_voltage=0,delta_cell_voltage=0,balance_current=0,battery_voltage=6.61,battery_power=0,battery_current=0,remain_percent=100i,remain_capacity=0,nominal_capacity=0,cycle_count=1i,cycle_capacity=0,up_time=0i 1707600000000000000
+bms,device=ups,site=my\ home,cell=0 cell_voltage=3.3,cell_resistance=0.138,battery_temperature=23.5 1707600000000000000
+bms,device=ups,site=my\ home,cell=1 cell_voltage=3.31,cell_resistance=0.137 1707600000000000000
+"#
+        );
+    }
+
+    #[test]
+    fn device_info() {
+        let device_info = DeviceInfo {
+            device_model: "JK_BD4A8S4P".into(),
+            serial_number: "40531310629".into(),
+            device_name: "UPS \"BMS\"".into(),
+            poweron_times: 1,
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        device_info
+            .write_influx(&[("device", "ups")], 1, &mut output)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"bms,device=ups device_model="JK_BD4A8S4P",hardware_version="",software_version="",serial_number="40531310629",manufacturing_date="",device_name="UPS \"BMS\"",poweron_times=1i 1
+"#
+        );
+    }
+}
@@ -1,4 +1,8 @@
 #![doc = include_str!("../README.md")]
+#[macro_use]
+mod fields;
+mod alarm;
+mod fixed;
 mod format;
 mod protocol;
 mod result;
@@ -6,8 +10,20 @@ mod types;
 mod utils;
 mod uuids;
 
+#[cfg(feature = "csv")]
+mod csv;
+#[cfg(feature = "influx")]
+mod influx;
 #[cfg(feature = "metrics")]
 mod metrics;
+#[cfg(feature = "metrics")]
+mod openmetrics;
+#[cfg(feature = "remote-write")]
+mod remote_write;
+#[cfg(feature = "table")]
+mod table;
+#[cfg(feature = "units")]
+mod units;
 
 use btleplug::{
     api::{
@@ -22,18 +38,40 @@ use pretty_hex::PrettyHex;
 use std::sync::Arc;
 use tokio::{
     sync::{Mutex, RwLock},
-    time::timeout,
+    time::{interval, timeout, MissedTickBehavior},
 };
 use tracing as log;
 use uuid::Uuid;
 
+pub use alarm::Alarm;
+pub use fields::{Field, FieldKind, FieldValue};
+pub use fixed::{Deci, Fixed, Milli};
 pub use format::Format;
 pub use macaddr::MacAddr6 as MacAddr;
 pub use result::{Error, Result};
-pub use types::{CellData, DeviceId, DeviceInfo};
+pub use types::{CellData, DeviceId, DeviceInfo, Settings};
 
+#[cfg(feature = "csv")]
+pub use csv::CsvColumns;
+#[cfg(feature = "influx")]
+pub use influx::{Influx, INFLUX_FORMAT, INFLUX_MEASUREMENT};
+#[cfg(feature = "metrics")]
+pub use metrics::{Metrics, MetricsOptions, QueueMetrics, ScrapeMetrics, Scrapeable};
 #[cfg(feature = "metrics")]
-pub use metrics::{Metrics, Scrapeable};
+pub use openmetrics::{OpenMetricsEncoder, OPENMETRICS_FORMAT};
+#[cfg(feature = "remote-write")]
+pub use remote_write::{RemoteWriteEncoder, REMOTE_WRITE_FORMAT, REMOTE_WRITE_VERSION};
+#[cfg(feature = "table")]
+pub use table::{write_table, TableDevice};
+#[cfg(all(feature = "units", feature = "serde"))]
+pub use units::WithUnits;
+#[cfg(feature = "units")]
+pub use units::{
+    ElectricCharge, ElectricCurrent, ElectricPotential, ElectricalResistance, Power, Quantities,
+    ThermodynamicTemperature, Time,
+};
+#[cfg(feature = "units")]
+pub use uom;
 
 use protocol::{MessageIter, MessageType, RawRecord, RawRequest, RawResponse};
 use utils::checksum;
@@ -278,6 +316,42 @@ impl Client {
         data_buffer.data_as::<CellData>()
     }
 
+    /// Get device settings
+    pub async fn settings(&self) -> Result<Settings> {
+        let mut data_buffer = self.make_request(&0x96.into()).await;
+
+        self.send_request(&mut data_buffer, 0x01.into()).await?;
+
+        data_buffer.data_as::<Settings>()
+    }
+
+    /// Get device data continuously with specified period
+    ///
+    /// Connection is kept open between reads and reestablished when lost,
+    /// so errors are yielded without terminating stream.
+    pub fn cell_data_stream(&self, period: Duration) -> impl Stream<Item = Result<CellData>> + '_ {
+        let mut ticker = interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        futures::stream::unfold(ticker, move |mut ticker| async move {
+            ticker.tick().await;
+
+            let result = match self.open().await {
+                Ok(()) => self.cell_data().await,
+                Err(error) => Err(error),
+            };
+
+            Some((result, ticker))
+        })
+    }
+
+    /// Get received signal strength of device in dBm (when known)
+    pub async fn rssi(&self) -> Result<Option<i16>> {
+        let periphery = self.get_periphery().await?;
+
+        Ok(periphery.properties().await?.and_then(|props| props.rssi))
+    }
+
     async fn make_request(&self, cmd: &RawRequest) -> tokio::sync::MutexGuard<'_, DataBuffer> {
         let mut data_buffer = self.data_buffer.lock().await;
         data_buffer.init();
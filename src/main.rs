@@ -1,5 +1,9 @@
 mod args;
 mod cmdline;
+mod config;
+
+#[cfg(all(feature = "pull", feature = "auth"))]
+mod auth;
 
 #[cfg(feature = "exporter")]
 mod exporter;
@@ -10,8 +14,30 @@ mod pull;
 #[cfg(feature = "push")]
 mod push;
 
+#[cfg(feature = "push")]
+mod queue;
+
+#[cfg(feature = "mqtt")]
+mod mqtt;
+
+#[cfg(feature = "watch")]
+mod watch;
+
+#[cfg(feature = "history")]
+mod history;
+
+#[cfg(feature = "monitor")]
+mod monitor;
+
+#[cfg(all(any(feature = "pull", feature = "push"), feature = "tls"))]
+mod tls;
+
 use args::Args;
-use btleplug::{api::Manager as _, platform::Manager};
+use btleplug::{
+    api::{Central as _, Manager as _},
+    platform::{Adapter, Manager},
+};
+use config::DeviceConfig;
 use std::sync::Arc;
 use tokio::{
     signal::ctrl_c,
@@ -21,16 +47,80 @@ use tokio::{
 use tracing as log;
 use ubmsc::{CellData, Client, DeviceId, DeviceInfo, Error, Format, Options, Result};
 
+#[cfg(feature = "config")]
+use config::Config;
+
 #[cfg(feature = "exporter")]
-use exporter::{Encoding, Exporter};
+use exporter::{DeviceLabel, Encoding, StaleAction, StalePolicy};
+
+#[cfg(any(feature = "pull", feature = "push", feature = "mqtt"))]
+use exporter::Exporter;
+
+#[cfg(feature = "pull")]
+use exporter::Event;
+
+#[cfg(feature = "history")]
+use args::HistoryQuery;
+
+#[cfg(feature = "history")]
+use history::{History, RetentionPolicy};
+
+#[cfg(feature = "history")]
+use ubmsc::Fixed;
+
+#[cfg(feature = "monitor")]
+use args::Monitor;
+
+#[cfg(all(test, any(feature = "monitor", feature = "mqtt", feature = "history")))]
+use ubmsc::Deci;
+
+#[cfg(any(feature = "monitor", feature = "exporter"))]
+use ubmsc::Alarm;
+
+#[cfg(any(feature = "monitor", all(test, feature = "exporter")))]
+use ubmsc::Milli;
+
+#[cfg(feature = "push")]
+use push::{PushMethod, PushProtocol};
 
 #[cfg(feature = "metrics")]
-use ubmsc::Metrics;
+use ubmsc::{Metrics, MetricsOptions};
+
+#[cfg(feature = "exporter")]
+use ubmsc::Settings;
+
+#[cfg(feature = "influx")]
+use ubmsc::Influx;
+
+#[cfg(feature = "csv")]
+use ubmsc::CsvColumns;
+
+#[cfg(feature = "mqtt")]
+use ubmsc::{Field, FieldKind, FieldValue};
+
+#[cfg(feature = "table")]
+use ubmsc::{write_table, TableDevice};
+
+#[cfg(all(feature = "units", feature = "serde"))]
+use ubmsc::WithUnits;
+
+#[cfg(feature = "exporter")]
+use ubmsc::{OpenMetricsEncoder, ScrapeMetrics};
+
+#[cfg(feature = "push")]
+use ubmsc::QueueMetrics;
+
+#[cfg(all(feature = "push", feature = "influx"))]
+use ubmsc::INFLUX_FORMAT;
+
+#[cfg(all(feature = "push", feature = "remote-write"))]
+use ubmsc::{RemoteWriteEncoder, REMOTE_WRITE_FORMAT, REMOTE_WRITE_VERSION};
 
 #[cfg_attr(feature = "multi-thread", tokio::main)]
 #[cfg_attr(not(feature = "multi-thread"), tokio::main(flavor = "current_thread"))]
 async fn main() -> Result<()> {
-    let args = Args::from_cmdline();
+    #[allow(unused_mut)]
+    let mut args = Args::from_cmdline();
 
     if args.version {
         println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
@@ -67,6 +157,23 @@ async fn main() -> Result<()> {
         registry.init();
     }
 
+    #[cfg(feature = "config")]
+    let config = args.load_config().map_err(|error| {
+        log::error!("Error while loading config: {error}");
+        error
+    })?;
+
+    #[cfg(feature = "config")]
+    if args.check_config {
+        if config.is_none() {
+            return Err(Error::BadConfig(
+                "Please specify the configuration file: -C".into(),
+            ));
+        }
+        println!("Configuration is valid");
+        return Ok(());
+    }
+
     log::info!("Start...");
     log::trace!("{args:?}");
 
@@ -75,7 +182,21 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
-    let mut main = Main::new(args);
+    #[cfg(feature = "config")]
+    let config = config.unwrap_or_default();
+
+    #[cfg(feature = "config")]
+    let devices = config.devices(&args.device);
+
+    #[cfg(not(feature = "config"))]
+    let devices = args.device.clone();
+
+    let mut main = Main::new(args, devices);
+
+    #[cfg(all(feature = "config", feature = "pull", feature = "auth"))]
+    {
+        main.auth = config.server.auth();
+    }
 
     main.run().await.map_err(|error| {
         log::error!("Exit with error: {error}");
@@ -90,7 +211,10 @@ async fn main() -> Result<()> {
 pub struct Main {
     args: Args,
     intr: Arc<Notify>,
-    clients: Vec<Client>,
+    devices: Vec<DeviceConfig>,
+    clients: Vec<Arc<Client>>,
+    #[cfg(all(feature = "pull", feature = "auth"))]
+    auth: auth::Auth,
 }
 
 impl core::ops::Deref for Main {
@@ -101,18 +225,43 @@ impl core::ops::Deref for Main {
 }
 
 impl Main {
-    pub fn new(args: Args) -> Self {
+    pub fn new(args: Args, devices: Vec<DeviceConfig>) -> Self {
         let intr = Self::intr_notify();
         let clients = Vec::default();
 
         Self {
             args,
             intr,
+            devices,
             clients,
+            #[cfg(all(feature = "pull", feature = "auth"))]
+            auth: Default::default(),
         }
     }
 
     pub async fn run(&mut self) -> Result<()> {
+        #[cfg(feature = "history")]
+        if let Some(query) = self.history_query() {
+            return self.run_history_query(query);
+        }
+
+        #[cfg(all(feature = "push", feature = "mqtt"))]
+        if self.has_client() && self.has_mqtt() {
+            return Err(Error::BadConfig(
+                "Push client and MQTT publisher cannot be run together".into(),
+            ));
+        }
+
+        #[cfg(feature = "watch")]
+        if self.has_watch() {
+            self.check_watch()?;
+        }
+
+        #[cfg(feature = "monitor")]
+        if self.monitor().is_some() {
+            self.check_monitor()?;
+        }
+
         let manager = Manager::new().await?;
 
         self.open_clients(&manager).await?;
@@ -121,6 +270,16 @@ impl Main {
             self.run_commands().await?;
         }
 
+        #[cfg(feature = "watch")]
+        if self.has_watch() {
+            self.run_watch().await?;
+        }
+
+        #[cfg(feature = "monitor")]
+        if let Some(monitor) = self.monitor() {
+            self.run_monitor(monitor).await?;
+        }
+
         #[cfg(feature = "pull")]
         if self.has_server() {
             self.run_exporter_server().await?;
@@ -131,6 +290,11 @@ impl Main {
             self.run_exporter_client().await?;
         }
 
+        #[cfg(feature = "mqtt")]
+        if self.has_mqtt() {
+            self.run_mqtt_client().await?;
+        }
+
         self.close_clients().await?;
 
         Ok(())
@@ -156,36 +320,37 @@ impl Main {
     async fn open_clients(&mut self, manager: &Manager) -> Result<()> {
         let options = self.client_options();
 
-        let adapter = manager
-            .adapters()
-            .await?
-            .into_iter()
-            .next()
-            .ok_or_else(|| {
-                log::error!("No Bluetooth adapters found");
-                Error::NotFound
-            })?;
+        let adapters = manager.adapters().await?;
 
-        let found_devices: Vec<_>;
+        let default_adapter = adapters.first().ok_or_else(|| {
+            log::error!("No Bluetooth adapters found");
+            Error::NotFound
+        })?;
 
-        let devices = if self.device.is_empty() {
+        if self.devices.is_empty() {
             log::warn!("No devices passed. Scan to find all...");
-            found_devices = Client::find(&adapter, &options).await?;
-            &found_devices
-        } else {
-            &self.args.device
-        };
+            self.devices = Client::find(default_adapter, &options)
+                .await?
+                .into_iter()
+                .map(From::from)
+                .collect();
+        }
 
-        log::debug!("Use {} devices", devices.len());
+        log::debug!("Use {} devices", self.devices.len());
 
-        if devices.is_empty() {
+        if self.devices.is_empty() {
             println!("No BMS devices found!");
             return Err(Error::NotFound);
         }
 
-        for device_id in devices {
-            let client = Client::new(&adapter, device_id, &options);
-            self.clients.push(client);
+        for device in &self.devices {
+            let adapter = if let Some(name) = &device.adapter {
+                find_adapter(&adapters, name).await?
+            } else {
+                default_adapter
+            };
+            let client = Client::new(adapter, &device.id, &device.options(&options));
+            self.clients.push(Arc::new(client));
         }
 
         Ok(())
@@ -207,3 +372,13 @@ impl Main {
         Ok(())
     }
 }
+
+async fn find_adapter<'a>(adapters: &'a [Adapter], name: &str) -> Result<&'a Adapter> {
+    for adapter in adapters {
+        if adapter.adapter_info().await?.contains(name) {
+            return Ok(adapter);
+        }
+    }
+    log::error!("Bluetooth adapter '{name}' not found");
+    Err(Error::NotFound)
+}
@@ -1,5 +1,34 @@
-use crate::{CellData, DeviceId, DeviceInfo, Result};
-use prometheus::{default_registry, Counter, Gauge, GaugeVec, Opts, Registry};
+use crate::{fields::help, CellData, DeviceInfo, Error, Result};
+use core::time::Duration;
+use prometheus::{default_registry, Counter, CounterVec, Gauge, GaugeVec, Opts, Registry};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Default namespace of metrics
+pub const DEFAULT_NAMESPACE: &str = "ubmsc";
+
+/// Metrics options
+#[derive(Clone, Debug)]
+pub struct MetricsOptions {
+    /// Prefix of metric names (without trailing `_`, empty to disable)
+    pub namespace: String,
+    /// Extra constant labels (i.e. site, rack, bank)
+    pub labels: BTreeMap<String, String>,
+    /// Use legacy metric names (without namespace, units and `_total` suffixes)
+    pub legacy_names: bool,
+}
+
+impl Default for MetricsOptions {
+    fn default() -> Self {
+        Self {
+            namespace: DEFAULT_NAMESPACE.into(),
+            labels: BTreeMap::default(),
+            legacy_names: false,
+        }
+    }
+}
 
 pub trait Scrapeable {
     fn scrape(&self, _metrics: &Metrics) {}
@@ -7,33 +36,69 @@ pub trait Scrapeable {
 
 macro_rules! metrics_impl {
     ( $($class:ident {
-        $($name:ident: $kind:ident: $type:ident: $help:literal;)*
+        $([$info:ident ($info_legacy:ident): $info_help:literal] {
+            $($label:ident: $label_description:literal;)*
+        })?
+        $($name:ident: $kind:ident: $type:ident: $unit:literal: $unit_name:literal:
+          $sensitivity:literal: $description:literal;)*
     })* ) => {
         /// Metrics for Prometheus exporter
         #[derive(Clone)]
         pub struct Metrics {
+            device: String,
+            $($($info: GaugeVec,)?)*
             $($($name: metrics_impl!(@type $kind),)*)*
         }
 
         impl Metrics {
             /// Instantiate metrics for cell data of specified device
-            pub fn new(device_id: &DeviceId) -> Result<Self> {
-                let device_id = device_id.to_string();
+            ///
+            /// The device name (i.e. alias, address or serial number) is used as `device` label.
+            pub fn new(device: impl Into<String>, options: &MetricsOptions) -> Result<Self> {
+                let device = device.into();
+                let labels = create::labels(&device, options);
+
+                $($(let $info = create::info(
+                    &labels,
+                    &create::info_name(options, stringify!($info), stringify!($info_legacy)),
+                    $info_help,
+                    &[$(stringify!($label)),*],
+                )?;)?)*
 
-                $($(let $name = create::$kind(&device_id, stringify!($name), $help)?;)*)*
+                $($(let $name = metrics_impl!(
+                    @create $kind, &labels,
+                    &create::name(options, stringify!($name), $unit_name, metrics_impl!(@total $kind)),
+                    &help($description, $unit)
+                )?;)*)*
 
                 Ok(Self {
+                    device,
+                    $($($info,)?)*
                     $($($name,)*)*
                 })
             }
 
+            /// Device name used as `device` label
+            pub fn device(&self) -> &str {
+                &self.device
+            }
+
             /// Register metrics
             pub fn register(&self, registry: Option<&Registry>) -> Result<()> {
                 let registry = registry.unwrap_or(default_registry());
+                $($(registry.register(Box::new(self.$info.clone()))?;)?)*
                 $($(registry.register(Box::new(self.$name.clone()))?;)*)*
                 Ok(())
             }
 
+            /// Unregister metrics
+            pub fn unregister(&self, registry: Option<&Registry>) -> Result<()> {
+                let registry = registry.unwrap_or(default_registry());
+                $($(registry.unregister(Box::new(self.$info.clone()))?;)?)*
+                $($(registry.unregister(Box::new(self.$name.clone()))?;)*)*
+                Ok(())
+            }
+
             /// Update metrics using provided data
             pub fn scrape<T: Scrapeable>(&self, data: &T) {
                 data.scrape(self);
@@ -42,76 +107,250 @@ macro_rules! metrics_impl {
 
         $(impl Scrapeable for $class {
             fn scrape(&self, metrics: &Metrics) {
-                $(update::$kind(&metrics.$name, metrics_impl!(@conv $kind, $type, self.$name));)*
+                $(update::info(&metrics.$info, &[$(self.$label.as_str()),*]);)?
+                $(update::$kind(&metrics.$name, metrics_impl!(@conv $kind, $type, self, $name));)*
             }
         })*
     };
 
     (@type counter) => { Counter };
     (@type gauge) => { Gauge };
-    (@type gauges) => { GaugeVec };
+    (@type cells) => { GaugeVec };
+
+    (@total counter) => { true };
+    (@total $kind:ident) => { false };
 
-    (@conv counter, f32, $val:expr) => { $val };
-    (@conv counter, usize, $val:expr) => { $val as _ };
-    (@conv gauge, f32, $val:expr) => { $val };
-    (@conv gauge, u8, $val:expr) => { $val as _ };
-    (@conv gauges, f32, $val:expr) => { &$val[..] };
+    (@create cells, $labels:expr, $name:expr, $help:expr) => {
+        create::gauges($labels, $name, $help)
+    };
+    (@create $kind:ident, $labels:expr, $name:expr, $help:expr) => {
+        create::$kind($labels, $name, $help)
+    };
+
+    (@conv $kind:ident, usize, $self:ident, $name:ident) => { $self.$name as _ };
+    (@conv $kind:ident, u8, $self:ident, $name:ident) => { $self.$name as _ };
+    (@conv cells, $type:ident, $self:ident, $name:ident) => {
+        $self.$name.iter().map(|value| value.to_f64())
+    };
+    (@conv $kind:ident, $type:ident, $self:ident, $name:ident) => { $self.$name.to_f64() };
 }
 
-metrics_impl! {
-    DeviceInfo {
-        poweron_times: counter: usize: "Number of poweron cicles";
+data_fields!(metrics_impl);
+
+/// Scrape health metrics for Prometheus exporter
+#[derive(Clone)]
+pub struct ScrapeMetrics {
+    success: Gauge,
+    duration: Gauge,
+    last_success: Gauge,
+    errors: CounterVec,
+}
+
+impl ScrapeMetrics {
+    /// Instantiate scrape health metrics of specified device
+    pub fn new(device: impl Into<String>, options: &MetricsOptions) -> Result<Self> {
+        let labels = create::labels(&device.into(), options);
+
+        Ok(Self {
+            success: create::gauge(
+                &labels,
+                &create::prefixed(options, "scrape_success"),
+                "Whether the last scrape of device was successful",
+            )?,
+            duration: create::gauge(
+                &labels,
+                &create::prefixed(options, "scrape_duration_seconds"),
+                "Duration of the last scrape of device, S",
+            )?,
+            last_success: create::gauge(
+                &labels,
+                &create::prefixed(options, "last_successful_scrape_timestamp_seconds"),
+                "Unix time of the last successful scrape of device, S",
+            )?,
+            errors: create::counters(
+                &labels,
+                &create::prefixed(options, "scrape_errors_total"),
+                "Number of scrape errors by kind",
+                &[ERROR_KIND_LABEL],
+            )?,
+        })
+    }
+
+    /// Register metrics
+    pub fn register(&self, registry: Option<&Registry>) -> Result<()> {
+        let registry = registry.unwrap_or(default_registry());
+        registry.register(Box::new(self.success.clone()))?;
+        registry.register(Box::new(self.duration.clone()))?;
+        registry.register(Box::new(self.last_success.clone()))?;
+        registry.register(Box::new(self.errors.clone()))?;
+        Ok(())
+    }
+
+    /// Update metrics using result of scrape
+    pub fn scraped(&self, success: bool, duration: Duration) {
+        self.success.set(if success { 1.0 } else { 0.0 });
+        self.duration.set(duration.as_secs_f64());
+        if success {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            self.last_success.set(now.as_secs_f64());
+        }
+    }
+
+    /// Count scrape error
+    pub fn error(&self, error: &Error) {
+        self.errors.with_label_values(&[error.kind()]).inc();
+    }
+}
+
+/// Push queue metrics
+#[derive(Clone)]
+pub struct QueueMetrics {
+    depth: Gauge,
+    dropped: Counter,
+}
+
+impl QueueMetrics {
+    /// Instantiate push queue metrics
+    pub fn new(options: &MetricsOptions) -> Result<Self> {
+        let labels = options
+            .labels
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+
+        Ok(Self {
+            depth: create::gauge(
+                &labels,
+                &create::prefixed(options, "push_queue_depth"),
+                "Number of samples in push queue",
+            )?,
+            dropped: create::counter(
+                &labels,
+                &create::prefixed(options, "push_queue_dropped_total"),
+                "Number of samples dropped from push queue",
+            )?,
+        })
+    }
+
+    /// Register metrics
+    pub fn register(&self, registry: Option<&Registry>) -> Result<()> {
+        let registry = registry.unwrap_or(default_registry());
+        registry.register(Box::new(self.depth.clone()))?;
+        registry.register(Box::new(self.dropped.clone()))?;
+        Ok(())
+    }
+
+    /// Update number of queued samples
+    pub fn depth(&self, depth: usize) {
+        self.depth.set(depth as _);
     }
-    CellData {
-        cell_voltage: gauges: f32: "Voltages of cells, V";
-        average_cell_voltage: gauge: f32: "Average voltage of cells, V";
-        delta_cell_voltage: gauge: f32: "Delta voltage of cells, V";
-        balance_current: gauge: f32: "Cells balance current, A";
-        cell_resistance: gauges: f32: "Resistances of cells, Ω";
-        battery_voltage: gauge: f32: "Voltage of battery, V";
-        battery_power: gauge: f32: "Power of battery, W";
-        battery_current: gauge: f32: "Current of battery, A";
-        battery_temperature: gauges: f32: "Temperatures of battery, ℃";
-        mosfet_temperature: gauge: f32: "Temperature of mosfet, ℃";
-        remain_percent: gauge: u8: "Remain capacity of battery, %";
-        remain_capacity: gauge: f32: "Remain capacity of battery, A·h";
-        cycle_count: counter: usize: "Number of battery cicles";
-        cycle_capacity: counter: f32: "Cycle capacity, A·h";
-        up_time: counter: usize: "Time since last poweron, S";
+
+    /// Count dropped samples
+    pub fn dropped(&self, count: usize) {
+        self.dropped.inc_by(count as _);
     }
 }
 
+const ERROR_KIND_LABEL: &str = "kind";
+
 mod create {
     use super::*;
 
     const DEVICE_ID_LABEL: &str = "device";
     const CELL_INDEX_LABEL: &str = "cell";
 
-    pub fn counter(device_id: &str, name: &str, help: &str) -> Result<Counter> {
+    /// Make metric name with namespace prefix
+    pub fn prefixed(options: &MetricsOptions, name: &str) -> String {
+        if options.legacy_names || options.namespace.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}_{name}", options.namespace)
+        }
+    }
+
+    /// Make name of info metric with namespace prefix or legacy name
+    pub fn info_name(options: &MetricsOptions, name: &str, legacy: &str) -> String {
+        if options.legacy_names {
+            legacy.to_string()
+        } else {
+            prefixed(options, name)
+        }
+    }
+
+    /// Make metric name with namespace prefix and unit and `_total` suffixes
+    pub fn name(options: &MetricsOptions, name: &str, unit: &str, total: bool) -> String {
+        let mut name = prefixed(options, name);
+        if !options.legacy_names {
+            if !unit.is_empty() && !name.ends_with(unit) {
+                name.push('_');
+                name.push_str(unit);
+            }
+            if total {
+                name.push_str("_total");
+            }
+        }
+        name
+    }
+
+    pub fn labels(device: &str, options: &MetricsOptions) -> HashMap<String, String> {
+        options
+            .labels
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .chain([(DEVICE_ID_LABEL.into(), device.into())])
+            .collect()
+    }
+
+    pub fn counter(labels: &HashMap<String, String>, name: &str, help: &str) -> Result<Counter> {
         Ok(Counter::with_opts(
-            Opts::new(name, help).const_label(DEVICE_ID_LABEL, device_id),
+            Opts::new(name, help).const_labels(labels.clone()),
         )?)
     }
 
-    pub fn gauge(device_id: &str, name: &str, help: &str) -> Result<Gauge> {
+    pub fn gauge(labels: &HashMap<String, String>, name: &str, help: &str) -> Result<Gauge> {
         Ok(Gauge::with_opts(
-            Opts::new(name, help).const_label(DEVICE_ID_LABEL, device_id),
+            Opts::new(name, help).const_labels(labels.clone()),
         )?)
     }
 
-    pub fn gauges(device_id: &str, name: &str, help: &str) -> Result<GaugeVec> {
+    pub fn gauges(labels: &HashMap<String, String>, name: &str, help: &str) -> Result<GaugeVec> {
         Ok(GaugeVec::new(
-            Opts::new(name, help).const_label(DEVICE_ID_LABEL, device_id),
+            Opts::new(name, help).const_labels(labels.clone()),
             &[CELL_INDEX_LABEL],
         )?)
     }
+
+    pub fn counters(
+        labels: &HashMap<String, String>,
+        name: &str,
+        help: &str,
+        variable_labels: &[&str],
+    ) -> Result<CounterVec> {
+        Ok(CounterVec::new(
+            Opts::new(name, help).const_labels(labels.clone()),
+            variable_labels,
+        )?)
+    }
+
+    pub fn info(
+        labels: &HashMap<String, String>,
+        name: &str,
+        help: &str,
+        fields: &[&str],
+    ) -> Result<GaugeVec> {
+        Ok(GaugeVec::new(
+            Opts::new(name, help).const_labels(labels.clone()),
+            fields,
+        )?)
+    }
 }
 
 mod update {
     use super::*;
 
-    pub fn counter(counter: &Counter, value: f32) {
-        let value = value as f64;
+    pub fn counter(counter: &Counter, value: f64) {
         let old_value = counter.get();
         if value > old_value {
             counter.inc_by(value - old_value);
@@ -121,16 +360,22 @@ mod update {
         }
     }
 
-    pub fn gauge(gauge: &Gauge, value: f32) {
-        gauge.set(value as _);
+    pub fn gauge(gauge: &Gauge, value: f64) {
+        gauge.set(value);
     }
 
-    pub fn gauges(gauges: &GaugeVec, values: &[f32]) {
-        for (index, value) in values.iter().enumerate() {
-            gauges.with_label_values(&[idx2str(index)]).set(*value as _);
+    pub fn cells(gauges: &GaugeVec, values: impl IntoIterator<Item = f64>) {
+        for (index, value) in values.into_iter().enumerate() {
+            gauges.with_label_values(&[idx2str(index)]).set(value);
         }
     }
 
+    pub fn info(info: &GaugeVec, values: &[&str]) {
+        // drop outdated label values (i.e. after firmware update)
+        info.reset();
+        info.with_label_values(values).set(1.0);
+    }
+
     fn idx2str(index: usize) -> &'static str {
         match index {
             0 => "0",
@@ -173,6 +418,7 @@ mod update {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{Deci, Milli};
     use prometheus::{Encoder, Registry, TextEncoder};
 
     #[test]
@@ -181,7 +427,7 @@ mod test {
         let encoder = TextEncoder::new();
         let mut buffer = Vec::new();
 
-        let metrics = Metrics::new(&DeviceId::Name("UPS_BMS".into())).unwrap();
+        let metrics = Metrics::new("UPS_BMS", &Default::default()).unwrap();
         metrics.register(Some(&registry)).unwrap();
 
         let device_info = DeviceInfo {
@@ -201,21 +447,25 @@ mod test {
         };
 
         let cell_data = CellData {
-            cell_voltage: vec![2.384, 2.384, 2.383, 2.384, 2.384, 2.384],
-            average_cell_voltage: 2.384,
-            delta_cell_voltage: 0.001,
-            balance_current: 1.024,
-            cell_resistance: vec![0.138, 0.137, 0.14, 0.138, 0.139, 0.139],
-            battery_voltage: 14.304,
-            battery_power: 2.231,
-            battery_current: 0.156,
-            battery_temperature: vec![23.2, 23.6],
-            mosfet_temperature: 25.4,
+            cell_voltage: [2.384, 2.384, 2.383, 2.384, 2.384, 2.384]
+                .map(Milli::from_f64)
+                .to_vec(),
+            average_cell_voltage: Milli::from_f64(2.384),
+            delta_cell_voltage: Milli::from_f64(0.001),
+            balance_current: Milli::from_f64(1.024),
+            cell_resistance: [0.138, 0.137, 0.14, 0.138, 0.139, 0.139]
+                .map(Milli::from_f64)
+                .to_vec(),
+            battery_voltage: Milli::from_f64(14.304),
+            battery_power: Milli::from_f64(2.231),
+            battery_current: Milli::from_f64(0.156),
+            battery_temperature: [23.2, 23.6].map(Deci::from_f64).to_vec(),
+            mosfet_temperature: Deci::from_f64(25.4),
             remain_percent: 100,
-            remain_capacity: 12.0,
-            nominal_capacity: 12.0,
+            remain_capacity: Milli::from_f64(12.0),
+            nominal_capacity: Milli::from_f64(12.0),
             cycle_count: 1,
-            cycle_capacity: 18.464,
+            cycle_capacity: Milli::from_f64(18.464),
             up_time: 1707600,
         };
 
@@ -228,67 +478,263 @@ mod test {
         println!("{text}");
         assert_eq!(
             text,
-            r#"# HELP average_cell_voltage Average voltage of cells, V
-# TYPE average_cell_voltage gauge
-average_cell_voltage{device="UPS_BMS"} 2.384000062942505
-# HELP balance_current Cells balance current, A
-# TYPE balance_current gauge
-balance_current{device="UPS_BMS"} 1.0240000486373901
-# HELP battery_current Current of battery, A
-# TYPE battery_current gauge
-battery_current{device="UPS_BMS"} 0.15600000321865082
-# HELP battery_power Power of battery, W
-# TYPE battery_power gauge
-battery_power{device="UPS_BMS"} 2.2309999465942383
-# HELP battery_temperature Temperatures of battery, ℃
-# TYPE battery_temperature gauge
-battery_temperature{cell="0",device="UPS_BMS"} 23.200000762939453
-battery_temperature{cell="1",device="UPS_BMS"} 23.600000381469727
-# HELP battery_voltage Voltage of battery, V
-# TYPE battery_voltage gauge
-battery_voltage{device="UPS_BMS"} 14.303999900817871
-# HELP cell_resistance Resistances of cells, Ω
-# TYPE cell_resistance gauge
-cell_resistance{cell="0",device="UPS_BMS"} 0.1379999965429306
-cell_resistance{cell="1",device="UPS_BMS"} 0.13699999451637268
-cell_resistance{cell="2",device="UPS_BMS"} 0.14000000059604645
-cell_resistance{cell="3",device="UPS_BMS"} 0.1379999965429306
-cell_resistance{cell="4",device="UPS_BMS"} 0.13899999856948853
-cell_resistance{cell="5",device="UPS_BMS"} 0.13899999856948853
-# HELP cell_voltage Voltages of cells, V
-# TYPE cell_voltage gauge
-cell_voltage{cell="0",device="UPS_BMS"} 2.384000062942505
-cell_voltage{cell="1",device="UPS_BMS"} 2.384000062942505
-cell_voltage{cell="2",device="UPS_BMS"} 2.382999897003174
-cell_voltage{cell="3",device="UPS_BMS"} 2.384000062942505
-cell_voltage{cell="4",device="UPS_BMS"} 2.384000062942505
-cell_voltage{cell="5",device="UPS_BMS"} 2.384000062942505
-# HELP cycle_capacity Cycle capacity, A·h
-# TYPE cycle_capacity counter
-cycle_capacity{device="UPS_BMS"} 18.464000701904297
-# HELP cycle_count Number of battery cicles
-# TYPE cycle_count counter
-cycle_count{device="UPS_BMS"} 1
-# HELP delta_cell_voltage Delta voltage of cells, V
-# TYPE delta_cell_voltage gauge
-delta_cell_voltage{device="UPS_BMS"} 0.0010000000474974513
-# HELP mosfet_temperature Temperature of mosfet, ℃
-# TYPE mosfet_temperature gauge
-mosfet_temperature{device="UPS_BMS"} 25.399999618530273
-# HELP poweron_times Number of poweron cicles
-# TYPE poweron_times counter
-poweron_times{device="UPS_BMS"} 1
-# HELP remain_capacity Remain capacity of battery, A·h
-# TYPE remain_capacity gauge
-remain_capacity{device="UPS_BMS"} 12
-# HELP remain_percent Remain capacity of battery, %
-# TYPE remain_percent gauge
-remain_percent{device="UPS_BMS"} 100
-# HELP up_time Time since last poweron, S
-# TYPE up_time counter
-up_time{device="UPS_BMS"} 1707600
+            r#"# HELP ubmsc_average_cell_voltage_volts Average voltage of cells, V
+# TYPE ubmsc_average_cell_voltage_volts gauge
+ubmsc_average_cell_voltage_volts{device="UPS_BMS"} 2.384
+# HELP ubmsc_balance_current_amperes Cells balance current, A
+# TYPE ubmsc_balance_current_amperes gauge
+ubmsc_balance_current_amperes{device="UPS_BMS"} 1.024
+# HELP ubmsc_battery_current_amperes Current of battery, A
+# TYPE ubmsc_battery_current_amperes gauge
+ubmsc_battery_current_amperes{device="UPS_BMS"} 0.156
+# HELP ubmsc_battery_power_watts Power of battery, W
+# TYPE ubmsc_battery_power_watts gauge
+ubmsc_battery_power_watts{device="UPS_BMS"} 2.231
+# HELP ubmsc_battery_temperature_celsius Temperatures of battery, ℃
+# TYPE ubmsc_battery_temperature_celsius gauge
+ubmsc_battery_temperature_celsius{cell="0",device="UPS_BMS"} 23.2
+ubmsc_battery_temperature_celsius{cell="1",device="UPS_BMS"} 23.6
+# HELP ubmsc_battery_voltage_volts Voltage of battery, V
+# TYPE ubmsc_battery_voltage_volts gauge
+ubmsc_battery_voltage_volts{device="UPS_BMS"} 14.304
+# HELP ubmsc_cell_resistance_ohms Resistances of cells, Ω
+# TYPE ubmsc_cell_resistance_ohms gauge
+ubmsc_cell_resistance_ohms{cell="0",device="UPS_BMS"} 0.138
+ubmsc_cell_resistance_ohms{cell="1",device="UPS_BMS"} 0.137
+ubmsc_cell_resistance_ohms{cell="2",device="UPS_BMS"} 0.14
+ubmsc_cell_resistance_ohms{cell="3",device="UPS_BMS"} 0.138
+ubmsc_cell_resistance_ohms{cell="4",device="UPS_BMS"} 0.139
+ubmsc_cell_resistance_ohms{cell="5",device="UPS_BMS"} 0.139
+# HELP ubmsc_cell_voltage_volts Voltages of cells, V
+# TYPE ubmsc_cell_voltage_volts gauge
+ubmsc_cell_voltage_volts{cell="0",device="UPS_BMS"} 2.384
+ubmsc_cell_voltage_volts{cell="1",device="UPS_BMS"} 2.384
+ubmsc_cell_voltage_volts{cell="2",device="UPS_BMS"} 2.383
+ubmsc_cell_voltage_volts{cell="3",device="UPS_BMS"} 2.384
+ubmsc_cell_voltage_volts{cell="4",device="UPS_BMS"} 2.384
+ubmsc_cell_voltage_volts{cell="5",device="UPS_BMS"} 2.384
+# HELP ubmsc_cycle_capacity_ampere_hours_total Cycle capacity, A·h
+# TYPE ubmsc_cycle_capacity_ampere_hours_total counter
+ubmsc_cycle_capacity_ampere_hours_total{device="UPS_BMS"} 18.464
+# HELP ubmsc_cycle_count_total Number of battery cycles
+# TYPE ubmsc_cycle_count_total counter
+ubmsc_cycle_count_total{device="UPS_BMS"} 1
+# HELP ubmsc_delta_cell_voltage_volts Delta voltage of cells, V
+# TYPE ubmsc_delta_cell_voltage_volts gauge
+ubmsc_delta_cell_voltage_volts{device="UPS_BMS"} 0.001
+# HELP ubmsc_device_info Device identity information
+# TYPE ubmsc_device_info gauge
+ubmsc_device_info{device="UPS_BMS",device_model="JK_BD4A8S4P",device_name="UPS_BMS",hardware_version="15A",manufacturing_date="240818",serial_number="40531310629",software_version="15.26"} 1
+# HELP ubmsc_mosfet_temperature_celsius Temperature of mosfet, ℃
+# TYPE ubmsc_mosfet_temperature_celsius gauge
+ubmsc_mosfet_temperature_celsius{device="UPS_BMS"} 25.4
+# HELP ubmsc_nominal_capacity_ampere_hours Nominal capacity of battery, A·h
+# TYPE ubmsc_nominal_capacity_ampere_hours gauge
+ubmsc_nominal_capacity_ampere_hours{device="UPS_BMS"} 12
+# HELP ubmsc_poweron_times_total Number of poweron cycles
+# TYPE ubmsc_poweron_times_total counter
+ubmsc_poweron_times_total{device="UPS_BMS"} 1
+# HELP ubmsc_remain_capacity_ampere_hours Remain capacity of battery, A·h
+# TYPE ubmsc_remain_capacity_ampere_hours gauge
+ubmsc_remain_capacity_ampere_hours{device="UPS_BMS"} 12
+# HELP ubmsc_remain_percent Remain capacity of battery, %
+# TYPE ubmsc_remain_percent gauge
+ubmsc_remain_percent{device="UPS_BMS"} 100
+# HELP ubmsc_up_time_seconds_total Time since last poweron, s
+# TYPE ubmsc_up_time_seconds_total counter
+ubmsc_up_time_seconds_total{device="UPS_BMS"} 1707600
 "#
         );
         //assert!(false);
     }
+
+    #[test]
+    fn labels() {
+        let registry = Registry::new();
+        let encoder = TextEncoder::new();
+        let mut buffer = Vec::new();
+
+        let options = MetricsOptions {
+            labels: [("site", "home"), ("rack", "1")]
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+            ..Default::default()
+        };
+
+        let metrics = Metrics::new("ups", &options).unwrap();
+        metrics.register(Some(&registry)).unwrap();
+
+        assert_eq!(metrics.device(), "ups");
+
+        metrics.scrape(&CellData {
+            cell_voltage: [2.384].map(Milli::from_f64).to_vec(),
+            battery_voltage: Milli::from_f64(14.304),
+            ..Default::default()
+        });
+
+        encoder.encode(&registry.gather(), &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+
+        assert!(text
+            .contains(r#"ubmsc_battery_voltage_volts{device="ups",rack="1",site="home"} 14.304"#));
+        assert!(text.contains(
+            r#"ubmsc_cell_voltage_volts{cell="0",device="ups",rack="1",site="home"} 2.384"#
+        ));
+
+        metrics.unregister(Some(&registry)).unwrap();
+        assert!(registry.gather().is_empty());
+    }
+
+    #[test]
+    fn device_info_update() {
+        let registry = Registry::new();
+        let encoder = TextEncoder::new();
+
+        let metrics = Metrics::new("UPS_BMS", &Default::default()).unwrap();
+        metrics.register(Some(&registry)).unwrap();
+
+        let mut device_info = DeviceInfo {
+            device_model: "JK_BD4A8S4P".into(),
+            software_version: "15.26".into(),
+            device_passcode: "1234".into(),
+            passcode: "000".into(),
+            setup_passcode: "123456789".into(),
+            ..Default::default()
+        };
+
+        metrics.scrape(&device_info);
+
+        device_info.software_version = "15.27".into();
+
+        metrics.scrape(&device_info);
+
+        let mut buffer = Vec::new();
+        encoder.encode(&registry.gather(), &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+
+        assert!(!text.contains("15.26"));
+        assert!(text.contains(r#"software_version="15.27""#));
+        assert!(!text.contains("1234"));
+        assert!(!text.contains("123456789"));
+    }
+
+    #[test]
+    fn legacy_names() {
+        let registry = Registry::new();
+
+        let options = MetricsOptions {
+            legacy_names: true,
+            ..Default::default()
+        };
+
+        let metrics = Metrics::new("ups", &options).unwrap();
+        metrics.register(Some(&registry)).unwrap();
+        metrics.scrape(&DeviceInfo::default());
+
+        let names = registry
+            .gather()
+            .iter()
+            .map(|family| family.get_name().to_string())
+            .collect::<Vec<_>>();
+
+        assert!(names.iter().any(|name| name == "bms_device_info"));
+        assert!(names.iter().any(|name| name == "battery_voltage"));
+        assert!(names.iter().any(|name| name == "cycle_capacity"));
+        assert!(names.iter().any(|name| name == "up_time"));
+    }
+
+    #[test]
+    fn namespace() {
+        let names = |namespace: &str| {
+            let registry = Registry::new();
+            let options = MetricsOptions {
+                namespace: namespace.into(),
+                ..Default::default()
+            };
+            Metrics::new("ups", &options)
+                .unwrap()
+                .register(Some(&registry))
+                .unwrap();
+            registry
+                .gather()
+                .iter()
+                .map(|family| family.get_name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let names_bms = names("bms");
+        assert!(names_bms
+            .iter()
+            .any(|name| name == "bms_battery_voltage_volts"));
+        assert!(names_bms
+            .iter()
+            .any(|name| name == "bms_up_time_seconds_total"));
+
+        let names_bare = names("");
+        assert!(names_bare
+            .iter()
+            .any(|name| name == "battery_voltage_volts"));
+    }
+
+    #[test]
+    fn scrape_health() {
+        let registry = Registry::new();
+        let encoder = TextEncoder::new();
+
+        let metrics = ScrapeMetrics::new("UPS_BMS", &Default::default()).unwrap();
+        metrics.register(Some(&registry)).unwrap();
+
+        metrics.error(&Error::Timeout);
+        metrics.error(&Error::Timeout);
+        metrics.error(&Error::BadCrc);
+        metrics.scraped(false, Duration::from_millis(1500));
+
+        let mut buffer = Vec::new();
+        encoder.encode(&registry.gather(), &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+
+        assert!(text.contains(r#"ubmsc_scrape_success{device="UPS_BMS"} 0"#));
+        assert!(text.contains(r#"ubmsc_scrape_duration_seconds{device="UPS_BMS"} 1.5"#));
+        assert!(
+            text.contains(r#"ubmsc_last_successful_scrape_timestamp_seconds{device="UPS_BMS"} 0"#)
+        );
+        assert!(text.contains(r#"ubmsc_scrape_errors_total{device="UPS_BMS",kind="timeout"} 2"#));
+        assert!(text.contains(r#"ubmsc_scrape_errors_total{device="UPS_BMS",kind="bad_crc"} 1"#));
+
+        metrics.scraped(true, Duration::from_secs(2));
+
+        let mut buffer = Vec::new();
+        encoder.encode(&registry.gather(), &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+
+        assert!(text.contains(r#"ubmsc_scrape_success{device="UPS_BMS"} 1"#));
+        assert!(
+            !text.contains(r#"ubmsc_last_successful_scrape_timestamp_seconds{device="UPS_BMS"} 0"#)
+        );
+    }
+
+    #[test]
+    fn queue() {
+        let registry = Registry::new();
+
+        let metrics = QueueMetrics::new(&MetricsOptions {
+            labels: [("site".into(), "home".into())].into_iter().collect(),
+            ..Default::default()
+        })
+        .unwrap();
+        metrics.register(Some(&registry)).unwrap();
+
+        metrics.depth(3);
+        metrics.dropped(2);
+
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&registry.gather(), &mut buffer)
+            .unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+
+        assert!(text.contains(r#"ubmsc_push_queue_depth{site="home"} 3"#));
+        assert!(text.contains(r#"ubmsc_push_queue_dropped_total{site="home"} 2"#));
+    }
 }
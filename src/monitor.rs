@@ -0,0 +1,645 @@
+use crate::{log, Alarm, CellData, Error, Main, Milli, Monitor, Result};
+use core::{pin::pin, time::Duration};
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use futures::StreamExt;
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Bar, BarChart, BarGroup, Block, Paragraph, Sparkline, Tabs},
+    DefaultTerminal, Frame,
+};
+use std::{collections::VecDeque, time::Instant};
+use tokio::{select, sync::mpsc, task::JoinSet, time::interval};
+
+/// Number of samples shown in sparklines
+const HISTORY_LENGTH: usize = 256;
+
+/// Number of missed reads after which data is considered stale
+const STALE_READS: u32 = 3;
+
+/// Resolution of sparklines
+const SPARKLINE_MAX: u64 = 64;
+
+/// Connection state of device
+#[derive(Clone, Debug, Default)]
+enum Status {
+    #[default]
+    Connecting,
+    Connected,
+    Failed(String),
+}
+
+/// Live state of device
+#[derive(Default)]
+struct DeviceState {
+    name: String,
+    status: Status,
+    rssi: Option<i16>,
+    cell_data: Option<CellData>,
+    updated: Option<Instant>,
+    current: VecDeque<f32>,
+    temperature: VecDeque<f32>,
+}
+
+impl DeviceState {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+
+    fn push_sample(&mut self, cell_data: CellData, now: Instant) {
+        for (history, value) in [
+            (&mut self.current, cell_data.battery_current.to_f32()),
+            (
+                &mut self.temperature,
+                cell_data
+                    .battery_temperature
+                    .iter()
+                    .map(|value| value.to_f32())
+                    .fold(f32::NAN, f32::max),
+            ),
+        ] {
+            if history.len() == HISTORY_LENGTH {
+                history.pop_front();
+            }
+            history.push_back(value);
+        }
+        self.cell_data = Some(cell_data);
+        self.updated = Some(now);
+    }
+
+    /// Active alarms derived from data and connection state
+    fn alarms(&self, stale_after: Duration, now: Instant) -> Vec<String> {
+        let mut alarms = Vec::new();
+
+        if let Status::Failed(error) = &self.status {
+            alarms.push(format!("Connection: {error}"));
+        }
+
+        if let Some(updated) = self.updated {
+            let age = now.duration_since(updated);
+            if age > stale_after {
+                alarms.push(format!("No data for {}s", age.as_secs()));
+            }
+        }
+
+        if let Some(data) = &self.cell_data {
+            alarms.extend(data.alarms().iter().map(ToString::to_string));
+        }
+
+        alarms
+    }
+}
+
+/// State of terminal UI
+struct App {
+    devices: Vec<DeviceState>,
+    selected: usize,
+    paused: bool,
+    stale_after: Duration,
+}
+
+impl App {
+    fn new(names: impl IntoIterator<Item = String>, period: Duration) -> Self {
+        Self {
+            devices: names.into_iter().map(DeviceState::new).collect(),
+            selected: 0,
+            paused: false,
+            stale_after: period * STALE_READS,
+        }
+    }
+
+    /// Apply result of read (data is kept when paused)
+    fn update(&mut self, index: usize, result: Result<CellData>, rssi: Option<i16>, now: Instant) {
+        let device = &mut self.devices[index];
+
+        if rssi.is_some() {
+            device.rssi = rssi;
+        }
+
+        match result {
+            Ok(cell_data) => {
+                device.status = Status::Connected;
+                if !self.paused {
+                    device.push_sample(cell_data, now);
+                }
+            }
+            Err(error) => {
+                device.status = Status::Failed(error.to_string());
+            }
+        }
+    }
+
+    /// Handle key press (returns true to quit)
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let count = self.devices.len();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return true,
+            KeyCode::Char(' ') | KeyCode::Char('p') => self.paused = !self.paused,
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => {
+                self.selected = (self.selected + 1) % count;
+            }
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => {
+                self.selected = (self.selected + count - 1) % count;
+            }
+            KeyCode::Char(digit @ '1'..='9') => {
+                let index = digit as usize - '1' as usize;
+                if index < count {
+                    self.selected = index;
+                }
+            }
+            _ => (),
+        }
+        false
+    }
+
+    fn draw(&self, frame: &mut Frame, now: Instant) {
+        let [tabs_area, main_area, lines_area, help_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(10),
+            Constraint::Length(6),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let device = &self.devices[self.selected];
+
+        let mut title = vec![Span::raw(" ubmsc monitor ")];
+        if self.paused {
+            title.push(Span::raw(" PAUSED ").black().on_yellow());
+        }
+
+        frame.render_widget(
+            Tabs::new(self.devices.iter().map(|device| {
+                Line::from(vec![
+                    status_symbol(&device.status),
+                    Span::raw(" "),
+                    Span::raw(device.name.as_str()),
+                ])
+            }))
+            .select(self.selected)
+            .highlight_style(Style::new().add_modifier(Modifier::BOLD | Modifier::REVERSED))
+            .block(Block::bordered().title(Line::from(title))),
+            tabs_area,
+        );
+
+        let [pack_area, cells_area] =
+            Layout::horizontal([Constraint::Length(36), Constraint::Min(20)]).areas(main_area);
+
+        let [values_area, alarms_area] =
+            Layout::vertical([Constraint::Min(14), Constraint::Length(6)]).areas(pack_area);
+
+        frame.render_widget(
+            Paragraph::new(self.pack_lines(device, now)).block(Block::bordered().title(" Pack ")),
+            values_area,
+        );
+
+        let alarms = device.alarms(self.stale_after, now);
+        let alarm_lines: Vec<_> = if alarms.is_empty() {
+            vec![Line::from("OK".green())]
+        } else {
+            alarms
+                .into_iter()
+                .map(|alarm| Line::from(alarm.red()))
+                .collect()
+        };
+        frame.render_widget(
+            Paragraph::new(alarm_lines).block(Block::bordered().title(" Alarms ")),
+            alarms_area,
+        );
+
+        self.draw_cells(frame, device, cells_area);
+
+        let [current_area, temperature_area] =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(lines_area);
+
+        for (area, history, name, unit, precision, color) in [
+            (
+                current_area,
+                &device.current,
+                "Current",
+                "A",
+                3,
+                Color::Cyan,
+            ),
+            (
+                temperature_area,
+                &device.temperature,
+                "Max temperature",
+                "℃",
+                1,
+                Color::Magenta,
+            ),
+        ] {
+            let title = match (range(history), history.back()) {
+                (Some((min, max)), Some(last)) if last.is_finite() => format!(
+                    " {name} {last:.precision$} {unit} ({min:.precision$} … {max:.precision$}) "
+                ),
+                _ => format!(" {name} "),
+            };
+            frame.render_widget(
+                Sparkline::default()
+                    .data(sparkline_data(history))
+                    .max(SPARKLINE_MAX)
+                    .style(Style::new().fg(color))
+                    .block(Block::bordered().title(title)),
+                area,
+            );
+        }
+
+        frame.render_widget(
+            Line::from(
+                " q: quit  tab/←/→/1-9: switch device  space: pause".add_modifier(Modifier::DIM),
+            ),
+            help_area,
+        );
+    }
+
+    fn pack_lines<'a>(&self, device: &'a DeviceState, now: Instant) -> Vec<Line<'a>> {
+        let mut lines = vec![
+            value_line("Status", status_text(&device.status)),
+            value_line(
+                "RSSI",
+                device
+                    .rssi
+                    .map(|rssi| format!("{rssi} dBm"))
+                    .unwrap_or_else(|| "-".into())
+                    .into(),
+            ),
+            value_line(
+                "Updated",
+                device
+                    .updated
+                    .map(|updated| format!("{}s ago", now.duration_since(updated).as_secs()))
+                    .unwrap_or_else(|| "never".into())
+                    .into(),
+            ),
+        ];
+
+        let Some(data) = &device.cell_data else {
+            return lines;
+        };
+
+        let temperatures: Vec<_> = data
+            .battery_temperature
+            .iter()
+            .map(|value| format!("{value:.1}"))
+            .collect();
+
+        lines.extend([
+            Line::default(),
+            value_line("Voltage", format!("{:.2} V", data.battery_voltage).into()),
+            value_line("Current", format!("{:.3} A", data.battery_current).into()),
+            value_line("Power", format!("{:.2} W", data.battery_power).into()),
+            value_line(
+                "SoC",
+                if data.remain_percent <= Alarm::LOW_PERCENT {
+                    format!("{} %", data.remain_percent).red()
+                } else {
+                    format!("{} %", data.remain_percent).into()
+                },
+            ),
+            value_line(
+                "Capacity",
+                format!(
+                    "{:.2} / {:.2} Ah",
+                    data.remain_capacity, data.nominal_capacity
+                )
+                .into(),
+            ),
+            value_line(
+                "Temperature",
+                format!("{} ℃", temperatures.join(", ")).into(),
+            ),
+            value_line("MOSFET", format!("{:.1} ℃", data.mosfet_temperature).into()),
+            value_line(
+                "Balance",
+                if data.balance_current != Milli::ZERO && data.balance_current.is_finite() {
+                    format!("{:.3} A", data.balance_current).yellow()
+                } else {
+                    "off".into()
+                },
+            ),
+            value_line("Cycles", data.cycle_count.to_string().into()),
+        ]);
+
+        lines
+    }
+
+    fn draw_cells(&self, frame: &mut Frame, device: &DeviceState, area: Rect) {
+        let block = Block::bordered().title(" Cells ");
+
+        let Some(data) = device
+            .cell_data
+            .as_ref()
+            .filter(|data| !data.cell_voltage.is_empty())
+        else {
+            frame.render_widget(block, area);
+            return;
+        };
+
+        let millivolts: Vec<_> = data
+            .cell_voltage
+            .iter()
+            .map(|value| value.raw().max(0) as u64)
+            .collect();
+        let min = millivolts.iter().copied().min().unwrap_or_default();
+        let max = millivolts.iter().copied().max().unwrap_or_default();
+        // show bars relative to baseline to keep small differences visible
+        let baseline = min.saturating_sub((max - min).max(10));
+
+        let delta = data.delta_cell_voltage;
+        let delta_style = if delta >= Alarm::DELTA_ALARM {
+            Style::new().red()
+        } else if delta >= Alarm::DELTA_WARN {
+            Style::new().yellow()
+        } else {
+            Style::new().green()
+        };
+
+        let block = block.title(Line::from(vec![
+            Span::raw(" Δ "),
+            Span::styled(format!("{:.0} mV", delta.to_f64() * 1e3), delta_style),
+            Span::raw(format!(
+                ", avg {:.3} V, baseline {:.3} V ",
+                data.average_cell_voltage,
+                baseline as f32 / 1e3
+            )),
+        ]));
+
+        let bars: Vec<_> = millivolts
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let style = if min == max {
+                    Style::new().green()
+                } else if *value == min {
+                    Style::new().blue()
+                } else if *value == max {
+                    Style::new().red()
+                } else {
+                    Style::new().green()
+                };
+                Bar::default()
+                    .value(value - baseline)
+                    .text_value(format!("{:.3}", *value as f32 / 1e3))
+                    .label(Line::from(index.to_string()))
+                    .style(style)
+                    .value_style(style.reversed())
+            })
+            .collect();
+
+        let inner = area.width.saturating_sub(2) as usize;
+        let width = (inner / bars.len()).saturating_sub(1).clamp(1, 6) as u16;
+
+        frame.render_widget(
+            BarChart::default()
+                .block(block)
+                .bar_width(width)
+                .bar_gap(1)
+                .max(max - baseline)
+                .data(BarGroup::default().bars(&bars)),
+            area,
+        );
+    }
+}
+
+fn value_line<'a>(name: &'a str, value: Span<'a>) -> Line<'a> {
+    Line::from(vec![Span::raw(format!("{name:<12}")).dim(), value])
+}
+
+fn status_symbol(status: &Status) -> Span<'static> {
+    match status {
+        Status::Connecting => "○".yellow(),
+        Status::Connected => "●".green(),
+        Status::Failed(_) => "●".red(),
+    }
+}
+
+fn status_text(status: &Status) -> Span<'static> {
+    match status {
+        Status::Connecting => "connecting".yellow(),
+        Status::Connected => "connected".green(),
+        Status::Failed(_) => "failed".red(),
+    }
+}
+
+/// Min and max of finite values
+fn range(values: &VecDeque<f32>) -> Option<(f32, f32)> {
+    values
+        .iter()
+        .filter(|value| value.is_finite())
+        .fold(None, |range, value| {
+            Some(match range {
+                Some((min, max)) => (value.min(min), value.max(max)),
+                None => (*value, *value),
+            })
+        })
+}
+
+/// Scale values to sparkline resolution (constant values are shown at middle)
+fn sparkline_data(values: &VecDeque<f32>) -> Vec<u64> {
+    let Some((min, max)) = range(values) else {
+        return vec![0; values.len()];
+    };
+    let span = max - min;
+    values
+        .iter()
+        .map(|value| {
+            if !value.is_finite() {
+                0
+            } else if span > 0.0 {
+                1 + ((value - min) / span * (SPARKLINE_MAX - 1) as f32) as u64
+            } else {
+                SPARKLINE_MAX / 2
+            }
+        })
+        .collect()
+}
+
+impl Main {
+    /// Check that monitor can be run
+    pub fn check_monitor(&self) -> Result<()> {
+        if self.has_service() {
+            return Err(Error::BadConfig(
+                "Monitor cannot be run together with exporter, push client or MQTT publisher"
+                    .into(),
+            ));
+        }
+
+        #[cfg(feature = "watch")]
+        if self.has_watch() {
+            return Err(Error::BadConfig(
+                "Monitor cannot be run together with watch mode".into(),
+            ));
+        }
+
+        if self
+            .monitor()
+            .is_some_and(|monitor| monitor.interval().is_zero())
+        {
+            return Err(Error::BadConfig("Zero monitor interval".into()));
+        }
+
+        Ok(())
+    }
+
+    pub async fn run_monitor(&self, monitor: &Monitor) -> Result<()> {
+        let period = monitor.interval();
+
+        let (sender, mut receiver) = mpsc::channel(self.clients.len().max(1));
+
+        let mut readers = JoinSet::new();
+
+        for (index, client) in self.clients.iter().enumerate() {
+            let client = client.clone();
+            let sender = sender.clone();
+            readers.spawn(async move {
+                let mut samples = pin!(client.cell_data_stream(period));
+                while let Some(result) = samples.next().await {
+                    let rssi = client.rssi().await.ok().flatten();
+                    if sender.send((index, result, rssi)).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        let mut app = App::new(self.devices.iter().map(|device| device.name()), period);
+
+        log::info!("Start monitor of {} devices", self.clients.len());
+
+        let mut terminal = ratatui::try_init()?;
+        let result = self
+            .monitor_loop(&mut app, &mut terminal, &mut receiver)
+            .await;
+        ratatui::restore();
+
+        readers.abort_all();
+
+        log::info!("Stop monitor");
+
+        result
+    }
+
+    async fn monitor_loop(
+        &self,
+        app: &mut App,
+        terminal: &mut DefaultTerminal,
+        receiver: &mut mpsc::Receiver<(usize, Result<CellData>, Option<i16>)>,
+    ) -> Result<()> {
+        let mut events = EventStream::new();
+
+        // redraw periodically to keep age of data actual
+        let mut ticker = interval(Duration::from_secs(1));
+
+        loop {
+            terminal.draw(|frame| app.draw(frame, Instant::now()))?;
+
+            select! {
+                Some((index, result, rssi)) = receiver.recv() => {
+                    app.update(index, result, rssi, Instant::now());
+                }
+                event = events.next() => match event {
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+                        if app.handle_key(key) {
+                            break;
+                        }
+                    }
+                    Some(Ok(_)) => (),
+                    Some(Err(error)) => return Err(error.into()),
+                    None => break,
+                },
+                _ = ticker.tick() => (),
+                _ = self.intr.notified() => break,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Deci;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    fn render(app: &App, now: Instant) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(100, 32)).unwrap();
+        terminal.draw(|frame| app.draw(frame, now)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(buffer.area.width as usize)
+            .map(|line| line.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn app() {
+        let now = Instant::now();
+        let mut app = App::new(["ups".into(), "solar".into()], Duration::from_secs(2));
+
+        let cell_data = CellData {
+            cell_voltage: [3.301, 3.312, 3.305].map(Milli::from_f64).to_vec(),
+            average_cell_voltage: Milli::from_f64(3.306),
+            delta_cell_voltage: Milli::from_f64(0.011),
+            battery_voltage: Milli::from_f64(9.918),
+            battery_current: Milli::from_f64(-1.5),
+            battery_temperature: [20.3, 55.5].map(Deci::from_f64).to_vec(),
+            mosfet_temperature: Deci::from_f64(21.0),
+            remain_percent: 80,
+            ..Default::default()
+        };
+
+        app.update(0, Ok(cell_data.clone()), Some(-67), now);
+        app.update(1, Err(Error::NotFound), None, now);
+
+        let screen = render(&app, now);
+        assert!(screen.contains("ubmsc monitor"));
+        assert!(screen.contains("9.92 V"));
+        assert!(screen.contains("-67 dBm"));
+        assert!(screen.contains("3.312"));
+        assert!(screen.contains("Δ 11 mV"));
+        assert!(screen.contains("High temperature #1 55.5 ℃"));
+
+        assert_eq!(
+            app.devices[0].alarms(app.stale_after, now + Duration::from_secs(7)),
+            ["No data for 7s", "High temperature #1 55.5 ℃"]
+        );
+        assert!(app.devices[1].alarms(app.stale_after, now)[0].starts_with("Connection: "));
+
+        // paused data is not updated
+        assert!(!app.handle_key(KeyCode::Char(' ').into()));
+        app.update(
+            0,
+            Ok(CellData {
+                battery_voltage: Milli::from_f64(9.5),
+                ..cell_data
+            }),
+            None,
+            now,
+        );
+        assert_eq!(app.devices[0].current.len(), 1);
+        let screen = render(&app, now);
+        assert!(screen.contains("PAUSED"));
+        assert!(screen.contains("9.92 V"));
+
+        // switch devices
+        assert!(!app.handle_key(KeyCode::Tab.into()));
+        assert_eq!(app.selected, 1);
+        assert!(!app.handle_key(KeyCode::Tab.into()));
+        assert_eq!(app.selected, 0);
+        assert!(!app.handle_key(KeyCode::Char('2').into()));
+        assert_eq!(app.selected, 1);
+        assert!(render(&app, now).contains("failed"));
+
+        assert!(app.handle_key(KeyCode::Char('q').into()));
+    }
+}
@@ -0,0 +1,648 @@
+use crate::{
+    log, CellData, DeviceInfo, Error, Exporter, Field, FieldKind, FieldValue, Main, Result,
+};
+use core::time::Duration;
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Outgoing, Packet, QoS};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use tokio::{
+    select, spawn,
+    task::JoinHandle,
+    time::{interval, sleep, timeout},
+};
+
+/// Payload of available state
+const ONLINE: &str = "online";
+
+/// Payload of unavailable state
+const OFFLINE: &str = "offline";
+
+/// Number of requests buffered while broker is unreachable
+const REQUESTS_CAPACITY: usize = 1024;
+
+/// Delay before reconnecting to broker
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Time to deliver pending messages on shutdown
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Kind of Home Assistant sensor
+#[derive(Clone, Copy)]
+struct Kind {
+    device_class: Option<&'static str>,
+    unit: Option<&'static str>,
+    state_class: &'static str,
+}
+
+impl Kind {
+    /// Kind of sensor which reports field
+    fn of(field: &Field) -> Self {
+        Self {
+            device_class: match field.unit_name {
+                "volts" => Some("voltage"),
+                "amperes" => Some("current"),
+                "watts" => Some("power"),
+                "celsius" => Some("temperature"),
+                "percent" => Some("battery"),
+                "seconds" => Some("duration"),
+                _ => None,
+            },
+            unit: match field.unit {
+                "" => None,
+                "℃" => Some("°C"),
+                "A·h" => Some("Ah"),
+                unit => Some(unit),
+            },
+            state_class: if field.kind == FieldKind::Counter {
+                "total_increasing"
+            } else {
+                "measurement"
+            },
+        }
+    }
+}
+
+/// Sensor value of cell data
+struct Sensor {
+    /// Topic relative to device (i.e. `cell_voltage/0`)
+    id: String,
+    /// Human readable name
+    name: String,
+    kind: Kind,
+    value: String,
+}
+
+impl Sensor {
+    fn new(field: &Field, cell: Option<usize>, value: FieldValue) -> Self {
+        let mut name = field.name.replace('_', " ");
+        name[..1].make_ascii_uppercase();
+        let (id, name) = match cell {
+            Some(cell) => (format!("{}/{cell}", field.name), format!("{name} {cell}")),
+            None => (field.name.into(), name),
+        };
+        Self {
+            id,
+            name,
+            kind: Kind::of(field),
+            value: value.to_string(),
+        }
+    }
+}
+
+/// Split cell data to sensors skipping NaN and infinite values
+fn sensors(cell_data: &CellData) -> Vec<Sensor> {
+    let mut sensors = Vec::new();
+
+    for (field, value) in cell_data.values() {
+        if field.kind == FieldKind::Cells {
+            for cell in 0..value.cells() {
+                if let Some(value) = value.cell(cell).filter(FieldValue::is_finite) {
+                    sensors.push(Sensor::new(field, Some(cell), value));
+                }
+            }
+        } else if value.is_finite() {
+            sensors.push(Sensor::new(field, None, value));
+        }
+    }
+
+    sensors
+}
+
+/// Home Assistant discovery payload of sensor
+fn discovery_config(
+    sensor: &Sensor,
+    node_id: &str,
+    state_topic: &str,
+    availability: [&str; 2],
+    device: &Value,
+) -> Value {
+    let object_id = format!("{node_id}_{}", object_id(&sensor.id));
+
+    let mut config = json!({
+        "name": sensor.name,
+        "unique_id": object_id,
+        "object_id": object_id,
+        "state_topic": state_topic,
+        "availability": availability
+            .iter()
+            .map(|topic| json!({ "topic": topic }))
+            .collect::<Vec<_>>(),
+        "availability_mode": "all",
+        "state_class": sensor.kind.state_class,
+        "device": device,
+    });
+
+    if let Some(device_class) = sensor.kind.device_class {
+        config["device_class"] = device_class.into();
+    }
+    if let Some(unit) = sensor.kind.unit {
+        config["unit_of_measurement"] = unit.into();
+    }
+
+    config
+}
+
+/// Home Assistant device block
+fn discovery_device(node_id: &str, name: &str, device_info: &DeviceInfo) -> Value {
+    let mut device = json!({
+        "identifiers": [node_id],
+        "name": name,
+    });
+
+    for (field, value) in [
+        ("model", &device_info.device_model),
+        ("hw_version", &device_info.hardware_version),
+        ("sw_version", &device_info.software_version),
+        ("serial_number", &device_info.serial_number),
+    ] {
+        if !value.is_empty() {
+            device[field] = value.as_str().into();
+        }
+    }
+
+    device
+}
+
+/// Check that topic prefix can be used to publish to
+pub fn is_topic(topic: &str) -> bool {
+    !topic.is_empty()
+        && !topic.starts_with('/')
+        && !topic.ends_with('/')
+        && !topic.contains(['+', '#', '\0'])
+}
+
+/// Make single topic level from name
+fn topic_level(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if matches!(c, '/' | '+' | '#') || c.is_whitespace() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Make Home Assistant object identifier from name
+fn object_id(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// MQTT publisher of cell data
+struct Publisher {
+    client: AsyncClient,
+    /// Task which drives connection
+    connection: JoinHandle<()>,
+    topic: String,
+    discovery: String,
+    /// Topics of published discovery configs
+    announced: BTreeSet<String>,
+}
+
+impl Publisher {
+    /// Start connection to broker with last will marking publisher offline
+    fn connect(mut options: MqttOptions, topic: &str, discovery: &str) -> Self {
+        let status = status_topic(topic);
+        options.set_last_will(LastWill::new(&status, OFFLINE, QoS::AtLeastOnce, true));
+
+        let (client, mut eventloop) = AsyncClient::new(options, REQUESTS_CAPACITY);
+
+        // queued first to be sent once connected
+        if let Err(error) = client.try_publish(&status, QoS::AtLeastOnce, true, ONLINE) {
+            log::error!("Error while publishing status: {error}");
+        }
+
+        let connection = spawn({
+            let client = client.clone();
+            async move {
+                let mut connected = false;
+                loop {
+                    match eventloop.poll().await {
+                        Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                            log::info!("Connected to MQTT broker");
+                            // last will replaced status while connection was lost
+                            if connected {
+                                if let Err(error) =
+                                    client.try_publish(&status, QoS::AtLeastOnce, true, ONLINE)
+                                {
+                                    log::error!("Error while publishing status: {error}");
+                                }
+                            }
+                            connected = true;
+                        }
+                        Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                        Ok(event) => log::trace!("MQTT event: {event:?}"),
+                        Err(error) => {
+                            log::error!("MQTT connection error: {error}");
+                            sleep(RECONNECT_DELAY).await;
+                        }
+                    }
+                }
+            }
+        });
+
+        Self {
+            client,
+            connection,
+            topic: topic.into(),
+            discovery: discovery.into(),
+            announced: BTreeSet::new(),
+        }
+    }
+
+    /// Publish message with at least once delivery
+    ///
+    /// Messages are dropped when too many of them waiting for broker.
+    fn publish(&self, topic: &str, retain: bool, payload: impl Into<Vec<u8>>) {
+        if let Err(error) = self
+            .client
+            .try_publish(topic, QoS::AtLeastOnce, retain, payload)
+        {
+            log::error!("Error while publishing to '{topic}': {error}");
+        }
+    }
+
+    /// Publish data of all devices
+    fn publish_all(&mut self, exporter: &Exporter) {
+        let status = exporter.status();
+
+        for (index, name) in exporter.device_names().enumerate() {
+            let online = status[index]
+                .last_scrape
+                .as_ref()
+                .map(|scrape| scrape.success)
+                .unwrap_or_default();
+            let device_info = exporter.device_info(index).map(|sample| sample.data);
+            let cell_data = exporter.cell_data(index).map(|sample| sample.data);
+
+            self.publish_device(name, device_info.as_ref(), cell_data.as_ref(), online);
+        }
+    }
+
+    /// Publish data of device announcing new sensors
+    fn publish_device(
+        &mut self,
+        name: &str,
+        device_info: Option<&DeviceInfo>,
+        cell_data: Option<&CellData>,
+        online: bool,
+    ) {
+        let device_topic = format!("{}/{}", self.topic, topic_level(name));
+        let availability = format!("{device_topic}/availability");
+
+        if let (Some(device_info), Some(cell_data)) = (device_info, cell_data) {
+            let node_id = object_id(&format!(
+                "ubmsc_{}",
+                if device_info.serial_number.is_empty() {
+                    name
+                } else {
+                    &device_info.serial_number
+                }
+            ));
+            let device = discovery_device(&node_id, name, device_info);
+            let status = status_topic(&self.topic);
+
+            let sensors = sensors(cell_data);
+
+            for sensor in &sensors {
+                let config_topic = format!(
+                    "{}/sensor/{node_id}/{}/config",
+                    self.discovery,
+                    object_id(&sensor.id)
+                );
+                if self.announced.contains(&config_topic) {
+                    continue;
+                }
+
+                let config = discovery_config(
+                    sensor,
+                    &node_id,
+                    &format!("{device_topic}/{}", sensor.id),
+                    [&status, &availability],
+                    &device,
+                );
+                self.publish(&config_topic, true, config.to_string());
+                self.announced.insert(config_topic);
+            }
+
+            if online {
+                for sensor in &sensors {
+                    let topic = format!("{device_topic}/{}", sensor.id);
+                    self.publish(&topic, false, sensor.value.as_str());
+                }
+            }
+        }
+
+        self.publish(&availability, true, if online { ONLINE } else { OFFLINE });
+    }
+
+    /// Mark publisher offline and disconnect
+    async fn close(self) {
+        self.publish(&status_topic(&self.topic), true, OFFLINE);
+
+        if let Err(error) = self.client.disconnect().await {
+            log::error!("Error while disconnecting from MQTT broker: {error}");
+        }
+
+        let mut connection = self.connection;
+        if timeout(DISCONNECT_TIMEOUT, &mut connection).await.is_err() {
+            log::warn!("MQTT broker is unreachable, pending messages are dropped");
+            connection.abort();
+        }
+    }
+}
+
+/// Availability topic of publisher
+fn status_topic(topic: &str) -> String {
+    format!("{topic}/status")
+}
+
+impl Main {
+    /// MQTT client options
+    fn mqtt_options(&self, host: String, port: u16) -> MqttOptions {
+        let mut options = MqttOptions::new(
+            format!("{}-{}", self.mqtt_topic(), std::process::id()),
+            host,
+            port,
+        );
+
+        if let Some((user, password)) = &self.mqtt_auth {
+            options.set_credentials(user, password);
+        }
+
+        options
+    }
+
+    pub async fn run_mqtt_client(&self) -> Result<()> {
+        let url = self.url();
+        if url.scheme_str() != Some("mqtt") {
+            return Err(Error::BadConfig(
+                "MQTT publisher requires mqtt://host:port URL of broker".into(),
+            ));
+        }
+
+        for topic in [self.mqtt_topic(), self.mqtt_discovery()] {
+            if !is_topic(topic) {
+                return Err(Error::BadConfig(format!("Invalid MQTT topic '{topic}'")));
+            }
+        }
+
+        let addr = self.url_addr().await?;
+
+        let exporter = self.exporter()?;
+
+        let mut publisher = Publisher::connect(
+            self.mqtt_options(addr.ip().to_string(), addr.port()),
+            self.mqtt_topic(),
+            self.mqtt_discovery(),
+        );
+
+        if self.exporter {
+            log::info!("Start MQTT publisher for: {addr}");
+
+            let mut poller = interval(self.scrape_interval());
+
+            loop {
+                select! {
+                    _ = poller.tick() => (),
+                    _ = self.intr.notified() => break,
+                }
+
+                if exporter.scrape(&self.clients).await.is_ok() {
+                    publisher.publish_all(&exporter);
+                }
+            }
+
+            log::info!("Stop MQTT publisher for: {addr}");
+        } else if exporter.scrape(&self.clients).await.is_ok() {
+            publisher.publish_all(&exporter);
+        }
+
+        publisher.close().await;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Deci, Milli};
+
+    #[test]
+    fn names() {
+        assert!(is_topic("ubmsc"));
+        assert!(is_topic("home/bms"));
+        assert!(!is_topic(""));
+        assert!(!is_topic("home/"));
+        assert!(!is_topic("home/+"));
+        assert_eq!(topic_level("c8:47:80/1 a"), "c8:47:80_1_a");
+        assert_eq!(object_id("cell_voltage/0"), "cell_voltage_0");
+        assert_eq!(object_id("c8:47:80-1"), "c8_47_80-1");
+    }
+
+    #[test]
+    fn discovery() {
+        let cell_data = CellData {
+            cell_voltage: [3.3, 3.31].map(Milli::from_f64).to_vec(),
+            battery_voltage: Milli::from_f64(6.61),
+            mosfet_temperature: Deci::NAN,
+            remain_percent: 98,
+            ..Default::default()
+        };
+
+        let sensors = sensors(&cell_data);
+        let ids = sensors
+            .iter()
+            .map(|sensor| sensor.id.as_str())
+            .collect::<Vec<_>>();
+        assert!(ids.contains(&"cell_voltage/1"));
+        assert!(ids.contains(&"battery_voltage"));
+        assert!(!ids.contains(&"mosfet_temperature"));
+        assert_eq!(sensors[1].name, "Cell voltage 1");
+
+        let device_info = DeviceInfo {
+            device_model: "BK_BLE_1.0".into(),
+            software_version: "11.XW_S11.26___".into(),
+            serial_number: "4052311075".into(),
+            ..Default::default()
+        };
+        let device = discovery_device("ubmsc_4052311075", "ups", &device_info);
+
+        let sensor = sensors
+            .iter()
+            .find(|sensor| sensor.id == "remain_percent")
+            .unwrap();
+        assert_eq!(sensor.value, "98");
+
+        let config = discovery_config(
+            sensor,
+            "ubmsc_4052311075",
+            "ubmsc/ups/remain_percent",
+            ["ubmsc/status", "ubmsc/ups/availability"],
+            &device,
+        );
+
+        assert_eq!(
+            config,
+            json!({
+                "name": "Remain percent",
+                "unique_id": "ubmsc_4052311075_remain_percent",
+                "object_id": "ubmsc_4052311075_remain_percent",
+                "state_topic": "ubmsc/ups/remain_percent",
+                "availability": [
+                    { "topic": "ubmsc/status" },
+                    { "topic": "ubmsc/ups/availability" },
+                ],
+                "availability_mode": "all",
+                "device_class": "battery",
+                "unit_of_measurement": "%",
+                "state_class": "measurement",
+                "device": {
+                    "identifiers": ["ubmsc_4052311075"],
+                    "name": "ups",
+                    "model": "BK_BLE_1.0",
+                    "sw_version": "11.XW_S11.26___",
+                    "serial_number": "4052311075",
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn publish() {
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+            sync::mpsc,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        // minimal MQTT 3.1.1 broker which records published messages
+        // (client may close connection before acknowledgments)
+        let broker = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut will = false;
+
+            loop {
+                let mut header = [0u8; 1];
+                if stream.read_exact(&mut header).await.is_err() {
+                    break;
+                }
+
+                let mut length = 0;
+                for shift in (0..).step_by(7) {
+                    let byte = stream.read_u8().await.unwrap();
+                    length |= ((byte & 0x7f) as usize) << shift;
+                    if byte < 0x80 {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                stream.read_exact(&mut body).await.unwrap();
+
+                match header[0] >> 4 {
+                    // CONNECT
+                    1 => {
+                        will = body.windows(12).any(|win| win == b"ubmsc/status");
+                        stream.write_all(&[0x20, 2, 0, 0]).await.ok();
+                    }
+                    // PUBLISH
+                    3 => {
+                        let qos = (header[0] >> 1) & 3;
+                        let retain = header[0] & 1 != 0;
+                        let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                        let topic = String::from_utf8(body[2..2 + topic_len].to_vec()).unwrap();
+                        let mut payload = &body[2 + topic_len..];
+                        if qos > 0 {
+                            stream
+                                .write_all(&[0x40, 2, payload[0], payload[1]])
+                                .await
+                                .ok();
+                            payload = &payload[2..];
+                        }
+                        let payload = String::from_utf8(payload.to_vec()).unwrap();
+                        sender.send((topic, payload, retain)).unwrap();
+                    }
+                    // PINGREQ
+                    12 => {
+                        stream.write_all(&[0xd0, 0]).await.ok();
+                    }
+                    // DISCONNECT
+                    14 => break,
+                    _ => (),
+                }
+            }
+
+            will
+        });
+
+        let mut publisher = Publisher::connect(
+            MqttOptions::new("ubmsc-test", "127.0.0.1", addr.port()),
+            "ubmsc",
+            "homeassistant",
+        );
+
+        let device_info = DeviceInfo {
+            serial_number: "4052311075".into(),
+            ..Default::default()
+        };
+        let cell_data = CellData {
+            battery_voltage: Milli::from_f64(6.61),
+            ..Default::default()
+        };
+
+        publisher.publish_device("ups", Some(&device_info), Some(&cell_data), true);
+        // already announced sensors are not announced again
+        publisher.publish_device("ups", Some(&device_info), Some(&cell_data), true);
+        publisher.close().await;
+
+        assert!(broker.await.unwrap(), "last will expected");
+
+        let mut messages = Vec::new();
+        while let Ok(message) = receiver.try_recv() {
+            messages.push(message);
+        }
+
+        let find = |topic: &str| {
+            messages
+                .iter()
+                .filter(|message| message.0 == topic)
+                .collect::<Vec<_>>()
+        };
+
+        let config = find("homeassistant/sensor/ubmsc_4052311075/battery_voltage/config");
+        assert_eq!(config.len(), 1);
+        assert!(config[0].2);
+        let config: Value = serde_json::from_str(&config[0].1).unwrap();
+        assert_eq!(config["state_topic"], "ubmsc/ups/battery_voltage");
+        assert_eq!(config["device_class"], "voltage");
+
+        let state = find("ubmsc/ups/battery_voltage");
+        assert_eq!(state.len(), 2);
+        assert_eq!(state[0].1, "6.61");
+        assert!(!state[0].2);
+
+        let availability = find("ubmsc/ups/availability");
+        assert_eq!(availability[0].1, ONLINE);
+        assert!(availability[0].2);
+
+        let status = find("ubmsc/status");
+        assert_eq!(status.first().unwrap().1, ONLINE);
+        assert_eq!(status.last().unwrap().1, OFFLINE);
+        assert!(status.last().unwrap().2);
+    }
+}
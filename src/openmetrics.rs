@@ -0,0 +1,302 @@
+use crate::{CellData, DeviceInfo};
+use prometheus::{
+    proto::{LabelPair, Metric, MetricFamily, MetricType},
+    Encoder,
+};
+use std::io::Write;
+
+/// The content type of OpenMetrics text format
+pub const OPENMETRICS_FORMAT: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Encoder for OpenMetrics text format
+#[derive(Clone, Copy, Default, Debug)]
+pub struct OpenMetricsEncoder;
+
+impl OpenMetricsEncoder {
+    /// Create new encoder
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Encoder for OpenMetricsEncoder {
+    fn encode<W: Write>(
+        &self,
+        families: &[MetricFamily],
+        writer: &mut W,
+    ) -> prometheus::Result<()> {
+        for family in families {
+            if family.get_metric().is_empty() {
+                continue;
+            }
+
+            let kind = family.get_field_type();
+            let name = family.get_name();
+            // counter family names has no `_total` suffix
+            let name = if kind == MetricType::COUNTER {
+                name.strip_suffix("_total").unwrap_or(name)
+            } else {
+                name
+            };
+
+            writeln!(writer, "# TYPE {name} {}", type_name(kind))?;
+            if let Some(unit) = unit_of(name) {
+                writeln!(writer, "# UNIT {name} {unit}")?;
+            }
+            if !family.get_help().is_empty() {
+                writeln!(writer, "# HELP {name} {}", escape(family.get_help(), false))?;
+            }
+
+            for metric in family.get_metric() {
+                match kind {
+                    MetricType::COUNTER => {
+                        let value = metric.get_counter().get_value();
+                        write_sample(writer, name, "_total", metric, None, value)?;
+                    }
+                    MetricType::GAUGE => {
+                        let value = metric.get_gauge().get_value();
+                        write_sample(writer, name, "", metric, None, value)?;
+                    }
+                    MetricType::UNTYPED => {
+                        let value = metric.get_untyped().get_value();
+                        write_sample(writer, name, "", metric, None, value)?;
+                    }
+                    MetricType::HISTOGRAM => {
+                        let histogram = metric.get_histogram();
+                        let mut inf_seen = false;
+                        for bucket in histogram.get_bucket() {
+                            let bound = bucket.get_upper_bound();
+                            inf_seen |= bound == f64::INFINITY;
+                            let count = bucket.get_cumulative_count() as f64;
+                            write_sample(
+                                writer,
+                                name,
+                                "_bucket",
+                                metric,
+                                Some(("le", &format_value(bound))),
+                                count,
+                            )?;
+                        }
+                        let count = histogram.get_sample_count() as f64;
+                        if !inf_seen {
+                            write_sample(
+                                writer,
+                                name,
+                                "_bucket",
+                                metric,
+                                Some(("le", "+Inf")),
+                                count,
+                            )?;
+                        }
+                        write_sample(writer, name, "_count", metric, None, count)?;
+                        write_sample(
+                            writer,
+                            name,
+                            "_sum",
+                            metric,
+                            None,
+                            histogram.get_sample_sum(),
+                        )?;
+                    }
+                    MetricType::SUMMARY => {
+                        let summary = metric.get_summary();
+                        for quantile in summary.get_quantile() {
+                            write_sample(
+                                writer,
+                                name,
+                                "",
+                                metric,
+                                Some(("quantile", &format_value(quantile.get_quantile()))),
+                                quantile.get_value(),
+                            )?;
+                        }
+                        let count = summary.get_sample_count() as f64;
+                        write_sample(writer, name, "_count", metric, None, count)?;
+                        write_sample(writer, name, "_sum", metric, None, summary.get_sample_sum())?;
+                    }
+                }
+            }
+        }
+
+        writeln!(writer, "# EOF")?;
+
+        Ok(())
+    }
+
+    fn format_type(&self) -> &str {
+        OPENMETRICS_FORMAT
+    }
+}
+
+fn type_name(kind: MetricType) -> &'static str {
+    match kind {
+        MetricType::COUNTER => "counter",
+        MetricType::GAUGE => "gauge",
+        MetricType::HISTOGRAM => "histogram",
+        MetricType::SUMMARY => "summary",
+        MetricType::UNTYPED => "unknown",
+    }
+}
+
+/// Unit of metric which reports data field (metric name ends with field name and unit)
+pub(crate) fn unit_of(name: &str) -> Option<&'static str> {
+    DeviceInfo::FIELDS
+        .iter()
+        .chain(CellData::FIELDS)
+        .find(|field| {
+            if field.unit_name.is_empty() {
+                return false;
+            }
+            let suffix = if field.name.ends_with(field.unit_name) {
+                field.name.to_string()
+            } else {
+                format!("{}_{}", field.name, field.unit_name)
+            };
+            name.strip_suffix(&suffix)
+                .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('_'))
+        })
+        .map(|field| field.unit_name)
+}
+
+fn write_sample(
+    writer: &mut impl Write,
+    name: &str,
+    suffix: &str,
+    metric: &Metric,
+    extra_label: Option<(&str, &str)>,
+    value: f64,
+) -> prometheus::Result<()> {
+    write!(writer, "{name}{suffix}")?;
+    write_labels(writer, metric.get_label(), extra_label)?;
+    write!(writer, " {}", format_value(value))?;
+
+    let timestamp = metric.get_timestamp_ms();
+    if timestamp != 0 {
+        write!(writer, " {}", timestamp as f64 / 1000.0)?;
+    }
+
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+fn write_labels(
+    writer: &mut impl Write,
+    labels: &[LabelPair],
+    extra_label: Option<(&str, &str)>,
+) -> prometheus::Result<()> {
+    if labels.is_empty() && extra_label.is_none() {
+        return Ok(());
+    }
+
+    let mut separator = '{';
+    for (name, value) in labels
+        .iter()
+        .map(|label| (label.get_name(), label.get_value()))
+        .chain(extra_label)
+    {
+        write!(writer, "{separator}{name}=\"{}\"", escape(value, true))?;
+        separator = ',';
+    }
+    write!(writer, "}}")?;
+
+    Ok(())
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".into()
+    } else if value == f64::INFINITY {
+        "+Inf".into()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".into()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape(value: &str, quote: bool) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '"' if quote => escaped.push_str("\\\""),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prometheus::{Counter, Gauge, GaugeVec, Opts, Registry};
+
+    #[test]
+    fn units() {
+        assert_eq!(unit_of("ubmsc_cell_voltage_volts"), Some("volts"));
+        assert_eq!(
+            unit_of("bms_cycle_capacity_ampere_hours"),
+            Some("ampere_hours")
+        );
+        assert_eq!(unit_of("remain_percent"), Some("percent"));
+        assert_eq!(unit_of("ubmsc_up_time_seconds"), Some("seconds"));
+        // legacy names have no unit suffix
+        assert_eq!(unit_of("bms_cell_voltage"), None);
+        assert_eq!(unit_of("ubmsc_cycle_count"), None);
+        assert_eq!(unit_of("ubmsc_scrape_duration_seconds"), None);
+    }
+
+    #[test]
+    fn encode() {
+        let registry = Registry::new();
+
+        let voltage = Gauge::with_opts(
+            Opts::new("battery_voltage_volts", "Voltage of battery, V")
+                .const_label("device", "a\"b"),
+        )
+        .unwrap();
+        voltage.set(14.25);
+        registry.register(Box::new(voltage)).unwrap();
+
+        let capacity = Counter::with_opts(Opts::new(
+            "cycle_capacity_ampere_hours_total",
+            "Cycle capacity, A·h",
+        ))
+        .unwrap();
+        capacity.inc_by(2.5);
+        registry.register(Box::new(capacity)).unwrap();
+
+        let temperature = GaugeVec::new(
+            Opts::new("battery_temperature_celsius", "Temperatures of battery, ℃"),
+            &["cell"],
+        )
+        .unwrap();
+        temperature.with_label_values(&["0"]).set(f64::NAN);
+        registry.register(Box::new(temperature)).unwrap();
+
+        let mut buffer = Vec::new();
+        OpenMetricsEncoder::new()
+            .encode(&registry.gather(), &mut buffer)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            r#"# TYPE battery_temperature_celsius gauge
+# UNIT battery_temperature_celsius celsius
+# HELP battery_temperature_celsius Temperatures of battery, ℃
+battery_temperature_celsius{cell="0"} NaN
+# TYPE battery_voltage_volts gauge
+# UNIT battery_voltage_volts volts
+# HELP battery_voltage_volts Voltage of battery, V
+battery_voltage_volts{device="a\"b"} 14.25
+# TYPE cycle_capacity_ampere_hours counter
+# UNIT cycle_capacity_ampere_hours ampere_hours
+# HELP cycle_capacity_ampere_hours Cycle capacity, A·h
+cycle_capacity_ampere_hours_total 2.5
+# EOF
+"#
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{utils::*, CellData, DeviceInfo, Error, Result};
+use crate::{utils::*, CellData, DeviceInfo, Error, Result, Settings};
 use core::mem::size_of;
 
 pub const HEARTBEAT: [u8; 4] = *b"AT\r\n";
@@ -220,28 +220,25 @@ impl TryFrom<&'_ RawCellData> for CellData {
             return Err(Error::BadRecordType);
         }
         Ok(Self {
-            cell_voltage: i16les_to_values(&raw.cell_voltage, 1e-3),
-            average_cell_voltage: i16le_to_value(&raw.average_cell_voltage, 1e-3),
-            delta_cell_voltage: i16le_to_value(&raw.delta_cell_voltage, 1e-3),
-            balance_current: i16le_to_value(&raw.balance_current, 1e-3),
-            cell_resistance: i16les_to_values(&raw.cell_resistance, 1e-3),
-            battery_voltage: i32le_to_value(&raw.battery_voltage, 1e-3),
-            battery_power: i32le_to_value(&raw.battery_power, 1e-3),
-            battery_current: i32le_to_value(&raw.battery_current, 1e-3),
-            battery_temperature: i16les_to_values(&raw.battery_temperature, 1e-1),
-            mosfet_temperature: i16le_to_value(
-                if raw.mosfet_temperature != [0u8; 2] {
-                    &raw.mosfet_temperature
-                } else {
-                    &raw.mosfet_temperature2
-                },
-                1e-1,
-            ),
+            cell_voltage: i16les_to_values(&raw.cell_voltage),
+            average_cell_voltage: i16le_to_value(&raw.average_cell_voltage),
+            delta_cell_voltage: i16le_to_value(&raw.delta_cell_voltage),
+            balance_current: i16le_to_value(&raw.balance_current),
+            cell_resistance: i16les_to_values(&raw.cell_resistance),
+            battery_voltage: i32le_to_value(&raw.battery_voltage),
+            battery_power: i32le_to_value(&raw.battery_power),
+            battery_current: i32le_to_value(&raw.battery_current),
+            battery_temperature: i16les_to_values(&raw.battery_temperature),
+            mosfet_temperature: i16le_to_value(if raw.mosfet_temperature != [0u8; 2] {
+                &raw.mosfet_temperature
+            } else {
+                &raw.mosfet_temperature2
+            }),
             remain_percent: raw.remain_percent[0],
-            remain_capacity: u32le_to_value(&raw.remain_capacity, 1e-3),
-            nominal_capacity: u32le_to_value(&raw.nominal_capacity, 1e-3),
+            remain_capacity: u32le_to_value(&raw.remain_capacity),
+            nominal_capacity: u32le_to_value(&raw.nominal_capacity),
             cycle_count: u32le_to_count(&raw.cycle_count),
-            cycle_capacity: u32le_to_value(&raw.cycle_capacity, 1e-3),
+            cycle_capacity: u32le_to_value(&raw.cycle_capacity),
             up_time: u32le_to_count(&raw.up_time),
         })
     }
@@ -302,6 +299,116 @@ impl TryFrom<&'_ [u8]> for &'_ RawCellData {
     }
 }
 
+impl TryFrom<&'_ RawSettings> for Settings {
+    type Error = Error;
+
+    fn try_from(raw: &'_ RawSettings) -> Result<Self> {
+        if raw.record.record_type != 0x01 {
+            return Err(Error::BadRecordType);
+        }
+        Ok(Self {
+            smart_sleep_voltage: u32le_to_value(&raw.smart_sleep_voltage),
+            cell_uvp: u32le_to_value(&raw.cell_uvp),
+            cell_uvpr: u32le_to_value(&raw.cell_uvpr),
+            cell_ovp: u32le_to_value(&raw.cell_ovp),
+            cell_ovpr: u32le_to_value(&raw.cell_ovpr),
+            balance_trigger_voltage: u32le_to_value(&raw.balance_trigger_voltage),
+            soc100_voltage: u32le_to_value(&raw.soc100_voltage),
+            soc0_voltage: u32le_to_value(&raw.soc0_voltage),
+            request_charge_voltage: u32le_to_value(&raw.request_charge_voltage),
+            request_float_voltage: u32le_to_value(&raw.request_float_voltage),
+            power_off_voltage: u32le_to_value(&raw.power_off_voltage),
+            max_charge_current: u32le_to_value(&raw.max_charge_current),
+            charge_ocp_delay: u32le_to_count(&raw.charge_ocp_delay),
+            charge_ocp_recovery: u32le_to_count(&raw.charge_ocp_recovery),
+            max_discharge_current: u32le_to_value(&raw.max_discharge_current),
+            discharge_ocp_delay: u32le_to_count(&raw.discharge_ocp_delay),
+            discharge_ocp_recovery: u32le_to_count(&raw.discharge_ocp_recovery),
+            scp_recovery: u32le_to_count(&raw.scp_recovery),
+            max_balance_current: u32le_to_value(&raw.max_balance_current),
+            charge_otp: i32le_to_value(&raw.charge_otp),
+            charge_otp_recovery: i32le_to_value(&raw.charge_otp_recovery),
+            discharge_otp: i32le_to_value(&raw.discharge_otp),
+            discharge_otp_recovery: i32le_to_value(&raw.discharge_otp_recovery),
+            charge_utp: i32le_to_value(&raw.charge_utp),
+            charge_utp_recovery: i32le_to_value(&raw.charge_utp_recovery),
+            mosfet_otp: i32le_to_value(&raw.mosfet_otp),
+            mosfet_otp_recovery: i32le_to_value(&raw.mosfet_otp_recovery),
+            cell_count: u32le_to_count(&raw.cell_count),
+            charge: raw.charge != [0u8; 4],
+            discharge: raw.discharge != [0u8; 4],
+            balance: raw.balance != [0u8; 4],
+            nominal_capacity: u32le_to_value(&raw.nominal_capacity),
+            scp_delay: u32le_to_count(&raw.scp_delay),
+            start_balance_voltage: u32le_to_value(&raw.start_balance_voltage),
+        })
+    }
+}
+
+impl TryFrom<&'_ [u8]> for Settings {
+    type Error = Error;
+
+    fn try_from(raw: &'_ [u8]) -> Result<Self> {
+        let res: &RawSettings = raw.try_into()?;
+        res.try_into()
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+#[repr(C, packed)]
+struct RawSettings {
+    record: RawRecord,
+    smart_sleep_voltage: [u8; 4],
+    cell_uvp: [u8; 4],
+    cell_uvpr: [u8; 4],
+    cell_ovp: [u8; 4],
+    cell_ovpr: [u8; 4],
+    balance_trigger_voltage: [u8; 4],
+    soc100_voltage: [u8; 4],
+    soc0_voltage: [u8; 4],
+    request_charge_voltage: [u8; 4],
+    request_float_voltage: [u8; 4],
+    power_off_voltage: [u8; 4],
+    max_charge_current: [u8; 4],
+    charge_ocp_delay: [u8; 4],
+    charge_ocp_recovery: [u8; 4],
+    max_discharge_current: [u8; 4],
+    discharge_ocp_delay: [u8; 4],
+    discharge_ocp_recovery: [u8; 4],
+    scp_recovery: [u8; 4],
+    max_balance_current: [u8; 4],
+    charge_otp: [u8; 4],
+    charge_otp_recovery: [u8; 4],
+    discharge_otp: [u8; 4],
+    discharge_otp_recovery: [u8; 4],
+    charge_utp: [u8; 4],
+    charge_utp_recovery: [u8; 4],
+    mosfet_otp: [u8; 4],
+    mosfet_otp_recovery: [u8; 4],
+    cell_count: [u8; 4],
+    charge: [u8; 4],
+    discharge: [u8; 4],
+    balance: [u8; 4],
+    nominal_capacity: [u8; 4],
+    scp_delay: [u8; 4],
+    start_balance_voltage: [u8; 4],
+}
+
+impl From<&'_ [u8; size_of::<RawSettings>()]> for &'_ RawSettings {
+    fn from(raw: &[u8; size_of::<RawSettings>()]) -> Self {
+        unsafe { &*(raw as *const _ as *const _) }
+    }
+}
+
+impl TryFrom<&'_ [u8]> for &'_ RawSettings {
+    type Error = Error;
+
+    fn try_from(raw: &[u8]) -> Result<Self> {
+        let (raw, _) = raw.split_first_chunk().ok_or(Error::NotEnoughData)?;
+        Ok(raw.into())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -384,6 +491,7 @@ mod tests {
 
     mod response_parse {
         use super::*;
+        use crate::Milli;
 
         #[test]
         fn device_info() {
@@ -501,28 +609,69 @@ mod tests {
             println!("{info:02x?}");
 
             assert_eq!(
-                info.cell_voltage,
-                [2.384, 2.384, 2.384, 2.384, 2.384, 2.3830001]
+                format!("{:?}", info.cell_voltage),
+                "[2.384, 2.384, 2.384, 2.384, 2.384, 2.383]"
             );
-            assert_eq!(info.average_cell_voltage, 2.384);
-            assert_eq!(info.delta_cell_voltage, 0.0);
-            assert_eq!(info.balance_current, 0.0);
+            assert_eq!(info.average_cell_voltage.to_string(), "2.384");
+            assert_eq!(info.delta_cell_voltage, Milli::ZERO);
+            assert_eq!(info.balance_current, Milli::ZERO);
             assert_eq!(
-                info.cell_resistance,
-                [0.13800001, 0.13700001, 0.14, 0.13800001, 0.13900001, 0.13900001]
+                format!("{:?}", info.cell_resistance),
+                "[0.138, 0.137, 0.14, 0.138, 0.139, 0.139]"
             );
-            assert_eq!(info.battery_voltage, 14.306001);
-            assert_eq!(info.battery_power, 2.2310002);
-            assert_eq!(info.battery_current, 0.156);
-            assert_eq!(info.battery_temperature, [23.800001, 24.300001]);
-            assert_eq!(info.mosfet_temperature, 25.9);
+            assert_eq!(info.battery_voltage.to_string(), "14.306");
+            assert_eq!(info.battery_power.to_string(), "2.231");
+            assert_eq!(info.battery_current.to_string(), "0.156");
+            assert_eq!(format!("{:?}", info.battery_temperature), "[23.8, 24.3]");
+            assert_eq!(info.mosfet_temperature.to_string(), "25.9");
             assert_eq!(info.remain_percent, 100);
-            assert_eq!(info.remain_capacity, 12.000001);
-            assert_eq!(info.nominal_capacity, 12.000001);
+            assert_eq!(info.remain_capacity.to_string(), "12");
+            assert_eq!(info.nominal_capacity.to_string(), "12");
             assert_eq!(info.cycle_count, 1);
-            assert_eq!(info.cycle_capacity, 16.646);
+            assert_eq!(info.cycle_capacity.to_string(), "16.646");
             assert_eq!(info.up_time, 1539196);
             //assert!(false);
         }
+
+        #[test]
+        fn settings() {
+            let mut raw = [0u8; 300];
+            raw[..6].copy_from_slice(&[0x55, 0xaa, 0xeb, 0x90, 0x01, 0x4f]);
+            let mut put = |offset: usize, value: i32| {
+                raw[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            };
+            put(10, 2000); // cell_uvp
+            put(18, 2550); // cell_ovp
+            put(50, 30000); // max_charge_current
+            put(54, 30); // charge_ocp_delay
+            put(82, 700); // charge_otp
+            put(98, -50); // charge_utp
+            put(114, 6); // cell_count
+            put(118, 1); // charge
+            put(130, 12000); // nominal_capacity
+            put(138, 2450); // start_balance_voltage
+
+            let settings = Settings::try_from(raw.as_slice()).unwrap();
+
+            println!("{settings:?}");
+
+            assert_eq!(settings.cell_uvp.to_string(), "2");
+            assert_eq!(settings.cell_ovp.to_string(), "2.55");
+            assert_eq!(settings.max_charge_current.to_string(), "30");
+            assert_eq!(settings.charge_ocp_delay, 30);
+            assert_eq!(settings.charge_otp.to_string(), "70");
+            assert_eq!(settings.charge_utp.to_string(), "-5");
+            assert_eq!(settings.cell_count, 6);
+            assert!(settings.charge);
+            assert!(!settings.discharge);
+            assert_eq!(settings.nominal_capacity.to_string(), "12");
+            assert_eq!(settings.start_balance_voltage.to_string(), "2.45");
+
+            raw[4] = 0x02;
+            assert!(matches!(
+                Settings::try_from(raw.as_slice()),
+                Err(Error::BadRecordType)
+            ));
+        }
     }
 }
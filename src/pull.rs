@@ -1,44 +1,187 @@
-use crate::{log, Encoding, Exporter, Main, Result};
+use crate::{log, Client, Encoding, Error, Event, Exporter, Format, Main, Result};
+use core::fmt::Debug;
+use serde::Serialize;
 use std::sync::Arc;
 
-use core::time::Duration;
-use http_body_util::Full;
+use core::{convert::Infallible, time::Duration};
+use futures::stream;
+use http::uri::Scheme;
+use http_body_util::{combinators::UnsyncBoxBody, BodyExt, Full, StreamBody};
 use hyper::{
-    body::{Bytes, Incoming},
-    header::{ACCEPT, CONTENT_TYPE},
+    body::{Bytes, Frame, Incoming},
+    header::{ACCEPT, CACHE_CONTROL, CONTENT_TYPE},
     server::conn::http1,
     service::service_fn,
-    Request, Response,
+    Request, Response, Uri,
 };
+
 use hyper_util::rt::TokioIo;
 use tokio::{
+    io::{AsyncRead, AsyncWrite},
     net::TcpListener,
     select,
+    sync::broadcast::{error::RecvError, Receiver},
     task::JoinSet,
     time::{interval, timeout},
 };
 
-impl Exporter {
-    async fn serve_request(
-        &self,
-        request: Request<Incoming>,
-    ) -> hyper::Result<Response<Full<Bytes>>> {
+#[cfg(feature = "auth")]
+use crate::auth::Auth;
+#[cfg(feature = "auth")]
+use hyper::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+#[cfg(feature = "auth")]
+use tokio::task::spawn_blocking;
+
+#[cfg(feature = "tls")]
+use crate::tls;
+#[cfg(feature = "tls")]
+use tokio_rustls::TlsAcceptor;
+
+const INDEX_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><title>BMS exporter</title></head>
+<body>
+<h1>BMS exporter</h1>
+<ul>
+<li><a href="{metrics}">Metrics</a></li>
+<li><a href="/devices">Devices</a></li>
+<li><a href="/api/v1/stream">Event stream</a></li>
+<li><a href="/-/healthy">Health</a></li>
+<li><a href="/-/ready">Readiness</a></li>
+</ul>
+</body>
+</html>
+"#;
+
+const API_DEVICES_PATH: &str = "/api/v1/devices/";
+
+/// Interval of keep-alive comments in event stream
+const STREAM_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+type Body = UnsyncBoxBody<Bytes, Infallible>;
+
+/// HTTP requests router
+struct Router {
+    exporter: Arc<Exporter>,
+    clients: Vec<Arc<Client>>,
+    metrics_path: String,
+    #[cfg(feature = "auth")]
+    auth: Arc<Auth>,
+}
+
+impl Router {
+    async fn serve_request(&self, request: Request<Incoming>) -> hyper::Result<Response<Body>> {
         if request.method() != "GET" {
-            return Ok(Response::builder()
-                .status(405)
-                .header(CONTENT_TYPE, "text/plain")
-                .body(Full::new(Bytes::from("Method not allowed")))
-                .unwrap());
+            return Ok(response(405, "text/plain", "Method not allowed"));
         }
 
-        if request.uri() != "/metrics" {
+        let path = request.uri().path();
+
+        #[cfg(feature = "auth")]
+        if !matches!(path, "/-/healthy" | "/-/ready") && !self.is_authorized(&request).await {
             return Ok(Response::builder()
-                .status(404)
+                .status(401)
                 .header(CONTENT_TYPE, "text/plain")
-                .body(Full::new(Bytes::from("Not found")))
+                .header(WWW_AUTHENTICATE, r#"Basic realm="ubmsc""#)
+                .body(Full::new(Bytes::from("Unauthorized")).boxed_unsync())
                 .unwrap());
         }
 
+        Ok(if path == self.metrics_path {
+            self.serve_metrics(&request)
+        } else if let Some(path) = path.strip_prefix(API_DEVICES_PATH) {
+            self.serve_device_data(&request, path).await
+        } else {
+            match path {
+                "/" => response(
+                    200,
+                    "text/html; charset=utf-8",
+                    INDEX_PAGE.replace("{metrics}", &self.metrics_path),
+                ),
+                "/-/healthy" => response(200, "text/plain", "OK"),
+                "/api/v1/stream" => event_stream(self.exporter.subscribe()),
+                "/-/ready" => {
+                    if self.exporter.is_ready() {
+                        response(200, "text/plain", "Ready")
+                    } else {
+                        response(503, "text/plain", "Not ready")
+                    }
+                }
+                "/devices" => data_response(data_format(&request), Ok(self.exporter.status())),
+                _ => response(404, "text/plain", "Not found"),
+            }
+        })
+    }
+
+    #[cfg(feature = "auth")]
+    async fn is_authorized(&self, request: &Request<Incoming>) -> bool {
+        if !self.auth.is_enabled() {
+            return true;
+        }
+
+        let auth = self.auth.clone();
+        let authorization = request
+            .headers()
+            .get(AUTHORIZATION)
+            .map(|value| value.as_bytes().to_vec());
+
+        // bcrypt is too slow to run in async context
+        spawn_blocking(move || auth.check(authorization.as_deref()))
+            .await
+            .unwrap_or(false)
+    }
+
+    async fn serve_device_data(&self, request: &Request<Incoming>, path: &str) -> Response<Body> {
+        let Some((name, kind)) = path.split_once('/') else {
+            return response(404, "text/plain", "Not found");
+        };
+
+        let Some(name) = percent_decode(name) else {
+            return response(400, "text/plain", "Bad device name");
+        };
+
+        let Some(index) = self.exporter.find_device(&name) else {
+            return response(404, "text/plain", "Device not found");
+        };
+
+        let fresh = request
+            .uri()
+            .query()
+            .map(|query| {
+                query
+                    .split('&')
+                    .any(|param| matches!(param, "fresh" | "fresh=1" | "fresh=true"))
+            })
+            .unwrap_or(false);
+
+        let client = || self.clients[index].as_ref();
+
+        let format = data_format(request);
+
+        match kind {
+            "device_info" => {
+                if fresh {
+                    data_response(
+                        format,
+                        self.exporter.read_device_info(index, client()).await,
+                    )
+                } else {
+                    cached_response(format, self.exporter.device_info(index))
+                }
+            }
+            "cell_data" => {
+                if fresh {
+                    data_response(format, self.exporter.read_cell_data(index, client()).await)
+                } else {
+                    cached_response(format, self.exporter.cell_data(index))
+                }
+            }
+            "settings" => data_response(format, self.exporter.read_settings(index, client()).await),
+            _ => response(404, "text/plain", "Not found"),
+        }
+    }
+
+    fn serve_metrics(&self, request: &Request<Incoming>) -> Response<Body> {
         let mut buffer = Vec::with_capacity(4096);
 
         let encoding = request
@@ -46,32 +189,178 @@ impl Exporter {
             .get(ACCEPT)
             .and_then(Encoding::from_accept);
 
-        let content_type = match self.encode(encoding, &mut buffer) {
-            Ok(content_type) => content_type,
+        match self.exporter.encode(encoding, &mut buffer) {
+            Ok(content_type) => response(200, content_type, buffer),
             Err(error) => {
                 log::error!("Error while encoding metrics: {error}");
+                response(500, "text/plain", "Internal error")
+            }
+        }
+    }
+}
+
+async fn serve_connection(
+    stream: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    router: Arc<Router>,
+) {
+    if let Err(err) = http1::Builder::new()
+        .serve_connection(
+            TokioIo::new(stream),
+            service_fn(|request| async {
+                log::debug!("Process request: {request:?}");
+
+                router.serve_request(request).await.map(|response| {
+                    log::debug!("Send response: {response:?}");
+                    response
+                })
+            }),
+        )
+        .await
+    {
+        log::error!("Error serving connection: {:?}", err);
+    }
+}
+
+fn is_https(url: &Uri) -> bool {
+    url.scheme()
+        .map(|scheme| scheme == &Scheme::HTTPS)
+        .unwrap_or_default()
+}
 
-                return Ok(Response::builder()
-                    .status(200)
-                    .header(CONTENT_TYPE, "text/plain")
-                    .body(Full::new(Bytes::from("Internal error")))
-                    .unwrap());
+/// Server-sent events stream of device events
+fn event_stream(events: Receiver<Event>) -> Response<Body> {
+    let stream = stream::unfold(events, |mut events| async move {
+        let chunk = match timeout(STREAM_KEEP_ALIVE, events.recv()).await {
+            Ok(Ok(event)) => match serde_json::to_string(&event) {
+                Ok(data) => format!("event: {}\ndata: {data}\n\n", event.name()),
+                Err(error) => {
+                    log::error!("Error while encoding event: {error}");
+                    return None;
+                }
+            },
+            Ok(Err(RecvError::Lagged(count))) => {
+                log::warn!("Event stream lagged by {count} events");
+                format!(": lagged {count}\n\n")
             }
+            Ok(Err(RecvError::Closed)) => return None,
+            Err(_) => ": keep-alive\n\n".into(),
         };
+        Some((Ok(Frame::data(Bytes::from(chunk))), events))
+    });
 
-        let response = Response::builder()
-            .status(200)
-            .header(CONTENT_TYPE, content_type)
-            .body(Full::new(Bytes::from(buffer)))
-            .unwrap();
+    Response::builder()
+        .status(200)
+        .header(CONTENT_TYPE, "text/event-stream")
+        .header(CACHE_CONTROL, "no-cache")
+        .body(StreamBody::new(stream).boxed_unsync())
+        .unwrap()
+}
+
+/// Decode percent-encoded path segment (i.e. `my%20ups`)
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
 
-        Ok(response)
+    while let Some(byte) = iter.next() {
+        bytes.push(if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            u8::from_str_radix(core::str::from_utf8(&hex).ok()?, 16).ok()?
+        } else {
+            byte
+        });
     }
+
+    String::from_utf8(bytes).ok()
+}
+
+/// Data format requested by client (JSON by default)
+fn data_format(request: &Request<Incoming>) -> Format {
+    request
+        .headers()
+        .get(ACCEPT)
+        .and_then(Format::from_accept)
+        .unwrap_or(Format::Json)
+}
+
+fn data_response(format: Format, result: Result<impl Debug + Serialize>) -> Response<Body> {
+    let mut data = Vec::new();
+    match result.and_then(|value| format.format_value(&value, &mut data)) {
+        Ok(()) => response(200, format.media_type(), data),
+        Err(error) => error_response(&error),
+    }
+}
+
+fn cached_response(format: Format, sample: Option<impl Debug + Serialize>) -> Response<Body> {
+    if let Some(sample) = sample {
+        data_response(format, Ok(sample))
+    } else {
+        response(503, "text/plain", "No data yet")
+    }
+}
+
+fn error_response(error: &Error) -> Response<Body> {
+    let status = match error {
+        Error::NotSupported => 501,
+        Error::Timeout => 504,
+        Error::JsonEnc(_) => 500,
+        #[cfg(feature = "cbor")]
+        Error::CborEnc(_) => 500,
+        #[cfg(feature = "msgpack")]
+        Error::MsgPackEnc(_) => 500,
+        _ => 502,
+    };
+    response(status, "text/plain", error.to_string())
+}
+
+fn response(status: u16, content_type: &str, body: impl Into<Bytes>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, content_type)
+        .body(Full::new(body.into()).boxed_unsync())
+        .unwrap()
 }
 
 impl Main {
+    /// Create TLS acceptor when HTTPS server requested
+    #[cfg(feature = "tls")]
+    fn tls_acceptor(&self) -> Result<Option<TlsAcceptor>> {
+        if !is_https(&self.url()) {
+            return Ok(None);
+        }
+
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => tls::acceptor(cert, key).map(Some),
+            _ => {
+                log::error!("HTTPS server requires TLS certificate and key");
+                Err(Error::BadConfig(
+                    "Missing TLS certificate or key for HTTPS".into(),
+                ))
+            }
+        }
+    }
+
     pub async fn run_exporter_server(&self) -> Result<()> {
-        let exporter = Arc::new(Exporter::new(self.default_encoding(), &self.clients)?);
+        let exporter = Arc::new(self.exporter()?);
+
+        let router = Arc::new(Router {
+            exporter: exporter.clone(),
+            clients: self.clients.clone(),
+            metrics_path: self.url().path().into(),
+            #[cfg(feature = "auth")]
+            auth: Arc::new(self.auth.clone()),
+        });
+
+        #[cfg(feature = "tls")]
+        let tls = self.tls_acceptor()?;
+
+        #[cfg(not(feature = "tls"))]
+        if is_https(&self.url()) {
+            log::error!("HTTPS server is not supported without tls feature");
+            return Err(Error::NotSupported);
+        }
 
         let addr = self.url_addr().await?;
 
@@ -80,7 +369,6 @@ impl Main {
         let listener = TcpListener::bind(addr).await?;
 
         let server = tokio::task::spawn({
-            let exporter = exporter.clone();
             let intr = self.intr.clone();
             async move {
                 let mut joins = JoinSet::new();
@@ -96,27 +384,22 @@ impl Main {
                         }
                     };
 
-                    let io = TokioIo::new(stream);
+                    let router = router.clone();
 
-                    let exporter = exporter.clone();
+                    #[cfg(feature = "tls")]
+                    let tls = tls.clone();
 
                     joins.spawn(async move {
-                        if let Err(err) = http1::Builder::new()
-                            .serve_connection(
-                                io,
-                                service_fn(|request| async {
-                                    log::debug!("Process request: {request:?}");
-
-                                    exporter.serve_request(request).await.map(|response| {
-                                        log::debug!("Send response: {response:?}");
-                                        response
-                                    })
-                                }),
-                            )
-                            .await
-                        {
-                            log::error!("Error serving connection: {:?}", err);
+                        #[cfg(feature = "tls")]
+                        if let Some(tls) = tls {
+                            match tls.accept(stream).await {
+                                Ok(stream) => serve_connection(stream, router).await,
+                                Err(error) => log::error!("Error while TLS handshake: {error}"),
+                            }
+                            return;
                         }
+
+                        serve_connection(stream, router).await;
                     });
                 }
 
@@ -127,7 +410,7 @@ impl Main {
             }
         });
 
-        let mut poller = interval(self.args.scrape_interval);
+        let mut poller = interval(self.scrape_interval());
 
         log::info!("Start scraper");
 
@@ -150,3 +433,154 @@ impl Main {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::CellData;
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    fn router(metrics_path: &str) -> Arc<Router> {
+        Arc::new(Router {
+            exporter: Arc::new(
+                Exporter::new(Default::default(), [("my ups".into(), Default::default())]).unwrap(),
+            ),
+            clients: Vec::new(),
+            metrics_path: metrics_path.into(),
+            #[cfg(feature = "auth")]
+            auth: Default::default(),
+        })
+    }
+
+    /// Send raw HTTP request and get status with body of response
+    async fn request(router: &Arc<Router>, method: &str, path: &str) -> (u16, String) {
+        let (mut client, server) = duplex(65536);
+        let server = tokio::spawn(serve_connection(server, router.clone()));
+
+        client
+            .write_all(
+                format!("{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+                    .as_bytes(),
+            )
+            .await
+            .unwrap();
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        server.await.unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head[9..12].parse().unwrap(), body.into())
+    }
+
+    #[tokio::test]
+    async fn metrics_path() {
+        let router = router("/probe");
+
+        let (status, body) = request(&router, "GET", "/probe").await;
+        assert_eq!(status, 200);
+        assert!(body.contains(r#"ubmsc_scrape_success{device="my ups"} 0"#));
+
+        assert_eq!(request(&router, "GET", "/metrics").await.0, 404);
+
+        let (status, body) = request(&router, "GET", "/").await;
+        assert_eq!(status, 200);
+        assert!(body.contains(r#"<a href="/probe">"#));
+    }
+
+    #[tokio::test]
+    async fn ready() {
+        let router = router("/metrics");
+
+        assert_eq!(request(&router, "GET", "/-/healthy").await.0, 200);
+        assert_eq!(
+            request(&router, "GET", "/-/ready").await,
+            (503, "Not ready".into())
+        );
+
+        router.exporter.store(0, None, Some(CellData::default()));
+
+        assert_eq!(
+            request(&router, "GET", "/-/ready").await,
+            (200, "Ready".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn not_allowed() {
+        let router = router("/metrics");
+
+        assert_eq!(request(&router, "POST", "/metrics").await.0, 405);
+        assert_eq!(request(&router, "PUT", "/-/ready").await.0, 405);
+    }
+
+    #[tokio::test]
+    async fn not_found() {
+        let router = router("/metrics");
+
+        for path in [
+            "/unknown",
+            "/metrics/extra",
+            "/api/v1/devices/solar",
+            "/api/v1/devices/solar/cell_data",
+            "/api/v1/devices/my%20ups/unknown",
+        ] {
+            assert_eq!(request(&router, "GET", path).await.0, 404, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn device_name() {
+        let router = router("/metrics");
+
+        assert_eq!(
+            request(&router, "GET", "/api/v1/devices/my%20ups/cell_data").await,
+            (503, "No data yet".into())
+        );
+
+        router.exporter.store(
+            0,
+            None,
+            Some(CellData {
+                remain_percent: 42,
+                ..Default::default()
+            }),
+        );
+
+        let (status, body) = request(&router, "GET", "/api/v1/devices/my%20ups/cell_data").await;
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""remain_percent":42"#));
+
+        assert_eq!(
+            request(&router, "GET", "/api/v1/devices/my%2/cell_data")
+                .await
+                .0,
+            400
+        );
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(percent_decode("my%20ups").as_deref(), Some("my ups"));
+        assert_eq!(percent_decode("%D0%B1%D0%BC%D1%81").as_deref(), Some("бмс"));
+        assert_eq!(percent_decode("ups").as_deref(), Some("ups"));
+        assert_eq!(percent_decode("%2"), None);
+        assert_eq!(percent_decode("%+1"), None);
+        assert_eq!(percent_decode("%FF"), None);
+    }
+
+    #[test]
+    fn error_status() {
+        let json_error = serde_json::from_str::<u8>("").unwrap_err();
+
+        for (error, status) in [
+            (Error::JsonEnc(json_error), 500),
+            (Error::NotSupported, 501),
+            (Error::NotEnoughData, 502),
+            (Error::LostConnection, 502),
+            (Error::Timeout, 504),
+        ] {
+            assert_eq!(error_response(&error).status(), status, "{error}");
+        }
+    }
+}
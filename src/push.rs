@@ -1,15 +1,44 @@
-use crate::{log, Error, Exporter, Main, Result};
+use crate::{log, queue::Queue, Error, Exporter, Main, QueueMetrics, Result};
 use std::net::SocketAddr;
 
+use base64::{
+    engine::general_purpose::{STANDARD as BASE64, URL_SAFE as BASE64_URL},
+    Engine as _,
+};
 use http_body_util::Full;
 use hyper::{
     body::Bytes,
     client::conn::http1::handshake,
-    header::{CONTENT_TYPE, HOST},
-    Request, Uri,
+    header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, HOST},
+    Method, Request, Uri,
 };
 use hyper_util::rt::TokioIo;
-use tokio::{net::TcpStream, select, time::interval};
+use prometheus::TEXT_FORMAT;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+    select,
+    time::{interval, sleep},
+};
+
+#[cfg(feature = "remote-write")]
+use crate::{RemoteWriteEncoder, REMOTE_WRITE_FORMAT, REMOTE_WRITE_VERSION};
+#[cfg(feature = "remote-write")]
+use hyper::header::CONTENT_ENCODING;
+#[cfg(feature = "remote-write")]
+use prometheus::Encoder as _;
+
+#[cfg(feature = "influx")]
+use crate::INFLUX_FORMAT;
+
+#[cfg(feature = "tls")]
+use crate::tls;
+#[cfg(feature = "tls")]
+use tokio_rustls::{rustls::pki_types::ServerName, TlsConnector};
+
+/// Header with version of remote write protocol
+#[cfg(feature = "remote-write")]
+const REMOTE_WRITE_VERSION_HEADER: &str = "x-prometheus-remote-write-version";
 
 #[derive(thiserror::Error, Debug)]
 enum PushError {
@@ -21,13 +50,24 @@ enum PushError {
     #[error("HTTP error: {0}")]
     Http(#[from] http::Error),
 
-    /// HyperError
+    /// Hyper error
     #[error("Hyper error: {0}")]
     Hyper(#[from] hyper::Error),
 
     /// Invalid status
     #[error("Invalid response status: {0}")]
     BadStatus(u16),
+
+    /// Interrupted by signal
+    #[error("Interrupted")]
+    Interrupted,
+}
+
+impl PushError {
+    /// Target rejected data so retrying makes no sense
+    fn is_permanent(&self) -> bool {
+        matches!(self, Self::BadStatus(status) if (400..500).contains(status) && *status != 408 && *status != 429)
+    }
 }
 
 impl From<std::io::Error> for PushError {
@@ -36,43 +76,275 @@ impl From<std::io::Error> for PushError {
     }
 }
 
-impl Exporter {
-    async fn do_request(
+/// HTTP method to push metrics
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum PushMethod {
+    /// Replace all metrics of group
+    #[default]
+    Put,
+    /// Replace only metrics with same names
+    Post,
+}
+
+impl core::str::FromStr for PushMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "put" | "PUT" => Self::Put,
+            "post" | "POST" => Self::Post,
+            _ => return Err(format!("Unknown push method: {s}")),
+        })
+    }
+}
+
+impl From<PushMethod> for Method {
+    fn from(method: PushMethod) -> Self {
+        match method {
+            PushMethod::Put => Method::PUT,
+            PushMethod::Post => Method::POST,
+        }
+    }
+}
+
+/// Protocol to push metrics
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum PushProtocol {
+    /// Exposition format (Pushgateway, VictoriaMetrics import)
+    #[default]
+    Exposition,
+    /// Prometheus remote write
+    #[cfg(feature = "remote-write")]
+    RemoteWrite,
+    /// InfluxDB line protocol
+    #[cfg(feature = "influx")]
+    Influx,
+}
+
+impl core::str::FromStr for PushProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "exposition" => Self::Exposition,
+            #[cfg(feature = "remote-write")]
+            "remote-write" => Self::RemoteWrite,
+            #[cfg(feature = "influx")]
+            "influx" => Self::Influx,
+            _ => return Err(format!("Unknown push protocol: {s}")),
+        })
+    }
+}
+
+/// Group of metrics to push
+struct Group {
+    /// Request path (with Pushgateway grouping key)
+    path: String,
+    /// Index of device to push metrics of (all devices when not set)
+    device: Option<usize>,
+}
+
+/// HTTP(s) client of push target
+pub struct Pusher {
+    url: Uri,
+    addr: SocketAddr,
+    protocol: PushProtocol,
+    method: PushMethod,
+    groups: Vec<Group>,
+    /// Extra request headers (including authorization)
+    headers: HeaderMap,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConnector>,
+}
+
+impl Pusher {
+    /// Delete pushed groups
+    async fn delete(&self) -> core::result::Result<(), PushError> {
+        for group in &self.groups {
+            log::info!("Delete group: {}", group.path);
+            self.request(Method::DELETE, &group.path, None, Vec::new())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Deliver queued samples in order
+    async fn flush(&self, queue: &mut Queue) -> core::result::Result<(), PushError> {
+        while let Some(data) = queue.front()? {
+            match self.send_sample(data).await {
+                Ok(()) => queue.pop()?,
+                Err(error) if error.is_permanent() => {
+                    log::error!("Drop queued sample rejected by target: {error}");
+                    queue.discard()?;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send timestamped sample
+    async fn send_sample(&self, data: Vec<u8>) -> core::result::Result<(), PushError> {
+        let (method, content_type) = match self.protocol {
+            PushProtocol::Exposition => (self.method.into(), TEXT_FORMAT),
+            #[cfg(feature = "remote-write")]
+            PushProtocol::RemoteWrite => (Method::POST, REMOTE_WRITE_FORMAT),
+            #[cfg(feature = "influx")]
+            PushProtocol::Influx => (Method::POST, INFLUX_FORMAT),
+        };
+
+        self.request(method, &self.groups[0].path, Some(content_type), data)
+            .await
+    }
+
+    /// Send request to target
+    async fn request(
         &self,
-        addr: &SocketAddr,
-        url: &Uri,
+        method: Method,
+        path: &str,
+        content_type: Option<&str>,
+        body: Vec<u8>,
     ) -> core::result::Result<(), PushError> {
-        log::info!("Connect to {addr}");
+        let host = self.url.host().unwrap_or("127.0.0.1");
 
-        let stream = TcpStream::connect(addr).await?;
+        let mut request = Request::builder().method(method).uri(path).header(
+            HOST,
+            if let Some(port) = self.url.port() {
+                format!("{host}:{port}")
+            } else {
+                host.into()
+            },
+        );
 
-        let io = TokioIo::new(stream);
+        if let Some(content_type) = content_type {
+            request = request.header(CONTENT_TYPE, content_type);
+        }
 
-        let (mut sender, conn) = handshake(io).await?;
+        if let Some(headers) = request.headers_mut() {
+            headers.extend(self.headers.clone());
+        }
 
-        tokio::task::spawn(async move {
-            if let Err(error) = conn.await {
-                log::error!("Connection failed: {error:?}");
-            }
-        });
+        let request = request.body(Full::<Bytes>::from(body))?;
+
+        log::info!("Connect to {}", self.addr);
+
+        let stream = TcpStream::connect(self.addr).await?;
 
-        let mut data = Vec::with_capacity(4096);
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            let name = ServerName::try_from(host.trim_matches(['[', ']']).to_string())
+                .map_err(|error| Error::BadConfig(format!("Invalid server name: {error}")))?;
+            let stream = tls.connect(name, stream).await?;
+            return send_request(stream, request).await;
+        }
+
+        send_request(stream, request).await
+    }
+}
+
+async fn send_request(
+    stream: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    request: Request<Full<Bytes>>,
+) -> core::result::Result<(), PushError> {
+    let (mut sender, conn) = handshake(TokioIo::new(stream)).await?;
+
+    tokio::task::spawn(async move {
+        if let Err(error) = conn.await {
+            log::error!("Connection failed: {error:?}");
+        }
+    });
 
-        let content_type = self.encode(None, &mut data)?;
+    log::debug!("Request: {request:?}");
+
+    let response = sender.send_request(request).await?;
+
+    log::debug!("Response: {response:?}");
+
+    if !response.status().is_success() {
+        return Err(PushError::BadStatus(response.status().as_u16()));
+    }
+
+    Ok(())
+}
 
-        let request = Request::put(url)
-            .header(HOST, url.host().unwrap())
-            .header(CONTENT_TYPE, content_type)
-            .body(Full::<Bytes>::from(data))?;
+/// Make part of Pushgateway grouping key
+///
+/// Values which cannot be used in path as is are encoded using base64.
+fn grouping_label(name: &str, value: &str) -> String {
+    if value.is_empty() {
+        format!("/{name}@base64/=")
+    } else if value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c))
+    {
+        format!("/{name}/{value}")
+    } else {
+        format!("/{name}@base64/{}", BASE64_URL.encode(value))
+    }
+}
 
-        log::debug!("Request: {request:?}");
+/// Make Pushgateway grouping key path
+///
+/// Default `/metrics` prefix is used when URL has no path.
+fn grouping_path(url: &Uri, job: &str, instance: &str) -> String {
+    let path = url.path().trim_end_matches('/');
+    format!(
+        "{}{}{}",
+        if path.is_empty() { "/metrics" } else { path },
+        grouping_label("job", job),
+        grouping_label("instance", instance),
+    )
+}
 
-        let response = sender.send_request(request).await?;
+fn is_https(url: &Uri) -> bool {
+    url.scheme()
+        .map(|scheme| scheme == &http::uri::Scheme::HTTPS)
+        .unwrap_or_default()
+}
 
-        log::debug!("Response: {response:?}");
+impl Exporter {
+    /// Encode metrics timestamped by current time
+    fn encode_sample(&self, protocol: PushProtocol, output: &mut Vec<u8>) -> Result<()> {
+        match protocol {
+            PushProtocol::Exposition => {
+                self.encode_timestamped(output)?;
+            }
+            #[cfg(feature = "remote-write")]
+            PushProtocol::RemoteWrite => {
+                RemoteWriteEncoder::new().encode(&self.gather(), output)?;
+            }
+            #[cfg(feature = "influx")]
+            PushProtocol::Influx => {
+                self.encode_influx(output)?;
+            }
+        }
+        Ok(())
+    }
 
-        if !response.status().is_success() {
-            return Err(PushError::BadStatus(response.status().as_u16()));
+    async fn push(&self, pusher: &Pusher) -> core::result::Result<(), PushError> {
+        if pusher.protocol != PushProtocol::Exposition {
+            let mut data = Vec::with_capacity(4096);
+            self.encode_sample(pusher.protocol, &mut data)?;
+            if data.is_empty() {
+                log::debug!("Nothing to push yet");
+                return Ok(());
+            }
+            return pusher.send_sample(data).await;
+        }
+
+        for group in &pusher.groups {
+            let mut data = Vec::with_capacity(4096);
+
+            let content_type = if let Some(index) = group.device {
+                self.encode_device(index, None, &mut data)?
+            } else {
+                self.encode(None, &mut data)?
+            };
+
+            pusher
+                .request(pusher.method.into(), &group.path, Some(content_type), data)
+                .await?;
         }
 
         Ok(())
@@ -80,12 +352,214 @@ impl Exporter {
 }
 
 impl Main {
-    pub async fn run_exporter_client(&self) -> Result<()> {
+    /// Push metrics retrying with exponential backoff
+    async fn push_retrying(
+        &self,
+        exporter: &Exporter,
+        pusher: &Pusher,
+    ) -> core::result::Result<(), PushError> {
+        let mut delay = self.push_backoff();
+        let mut attempt = 0;
+
+        loop {
+            match exporter.push(pusher).await {
+                Err(error) if attempt < self.push_retries() && !error.is_permanent() => {
+                    attempt += 1;
+                    log::warn!(
+                        "Error while pushing metrics: {error} (retry #{attempt} in {delay:?})"
+                    );
+                    select! {
+                        _ = sleep(delay) => (),
+                        _ = self.intr.notified() => return Err(PushError::Interrupted),
+                    }
+                    delay = (delay * 2).min(self.scrape_interval());
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Push metrics delivering queued samples first
+    ///
+    /// Metrics which failed to push are queued with timestamps.
+    async fn push_metrics(
+        &self,
+        exporter: &Exporter,
+        pusher: &Pusher,
+        queue: Option<&mut Queue>,
+    ) -> core::result::Result<(), PushError> {
+        let Some(queue) = queue else {
+            return self.push_retrying(exporter, pusher).await;
+        };
+
+        let result = match pusher.flush(queue).await {
+            Ok(()) => self.push_retrying(exporter, pusher).await,
+            Err(error) => Err(error),
+        };
+
+        if let Err(error) = &result {
+            if !error.is_permanent() {
+                let mut data = Vec::with_capacity(4096);
+                exporter.encode_sample(pusher.protocol, &mut data)?;
+                if !data.is_empty() {
+                    queue.push(&data)?;
+                    log::info!("Metrics queued ({} samples in queue)", queue.len());
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Open on-disk queue when requested
+    fn push_queue(&self, exporter: &Exporter) -> Result<Option<Queue>> {
+        let Some(dir) = &self.push_queue else {
+            return Ok(None);
+        };
+
+        if self.push_job.is_some() {
+            return Err(Error::BadConfig(
+                "Push queue requires target which accepts timestamps (not Pushgateway)".into(),
+            ));
+        }
+
+        let capacity = self.push_queue_size();
+        if capacity == 0 {
+            return Err(Error::BadConfig("Zero push queue size".into()));
+        }
+
+        let metrics = QueueMetrics::new(&self.metrics_options())?;
+        metrics.register(Some(exporter.service_registry()))?;
+
+        Queue::open(dir, capacity, metrics).map(Some)
+    }
+
+    /// Create client of push target
+    async fn pusher(&self) -> Result<Pusher> {
+        let url = self.url();
         let addr = self.url_addr().await?;
 
-        let exporter = Exporter::new(self.default_encoding(), &self.clients)?;
+        let protocol = self.push_protocol.unwrap_or_default();
+
+        let groups = if let Some(job) = &self.push_job {
+            if protocol != PushProtocol::Exposition {
+                return Err(Error::BadConfig(
+                    "Pushgateway job can be used with exposition protocol only".into(),
+                ));
+            }
+
+            if job.is_empty() {
+                return Err(Error::BadConfig("Empty Pushgateway job".into()));
+            }
+
+            let group = grouping_path(&url, job, &self.push_instance());
+
+            if self.push_per_device {
+                self.devices
+                    .iter()
+                    .enumerate()
+                    .map(|(index, device)| Group {
+                        path: format!("{group}{}", grouping_label("ubmsc_device", &device.name())),
+                        device: Some(index),
+                    })
+                    .collect()
+            } else {
+                vec![Group {
+                    path: group,
+                    device: None,
+                }]
+            }
+        } else {
+            if self.push_per_device {
+                return Err(Error::BadConfig(
+                    "Per-device groups require Pushgateway job".into(),
+                ));
+            }
+
+            vec![Group {
+                path: url
+                    .path_and_query()
+                    .map(|path| path.as_str())
+                    .unwrap_or("/")
+                    .into(),
+                device: None,
+            }]
+        };
 
-        let mut poller = interval(self.args.scrape_interval);
+        let mut headers = HeaderMap::new();
+
+        let authorization = match (&self.basic_auth, &self.bearer_token) {
+            (Some(_), Some(_)) => {
+                return Err(Error::BadConfig(
+                    "Basic auth and bearer token cannot be used together".into(),
+                ))
+            }
+            (Some((user, password)), None) => Some(format!(
+                "Basic {}",
+                BASE64.encode(format!("{user}:{password}"))
+            )),
+            #[cfg(feature = "influx")]
+            (None, Some(token)) if protocol == PushProtocol::Influx => {
+                Some(format!("Token {token}"))
+            }
+            (None, Some(token)) => Some(format!("Bearer {token}")),
+            (None, None) => None,
+        };
+
+        if let Some(authorization) = authorization {
+            let mut value = HeaderValue::try_from(authorization)
+                .map_err(|_| Error::BadConfig("Invalid credentials".into()))?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+
+        #[cfg(feature = "remote-write")]
+        if protocol == PushProtocol::RemoteWrite {
+            headers.insert(CONTENT_ENCODING, HeaderValue::from_static("snappy"));
+            headers.insert(
+                REMOTE_WRITE_VERSION_HEADER,
+                HeaderValue::from_static(REMOTE_WRITE_VERSION),
+            );
+        }
+
+        for (name, value) in &self.header {
+            headers.append(name.clone(), value.clone());
+        }
+
+        #[cfg(feature = "tls")]
+        let tls = if is_https(&url) {
+            Some(tls::connector(self.ca_cert.as_deref())?)
+        } else {
+            None
+        };
+
+        #[cfg(not(feature = "tls"))]
+        if is_https(&url) {
+            log::error!("HTTPS push is not supported without tls feature");
+            return Err(Error::NotSupported);
+        }
+
+        Ok(Pusher {
+            url,
+            addr,
+            protocol,
+            method: self.push_method.unwrap_or_default(),
+            groups,
+            headers,
+            #[cfg(feature = "tls")]
+            tls,
+        })
+    }
+
+    pub async fn run_exporter_client(&self) -> Result<()> {
+        let pusher = self.pusher().await?;
+        let addr = pusher.addr;
+
+        let exporter = self.exporter()?;
+
+        let mut queue = self.push_queue(&exporter)?;
+
+        let mut poller = interval(self.scrape_interval());
 
         if self.exporter {
             log::info!("Start pusher for: {addr}");
@@ -97,15 +571,23 @@ impl Main {
                 }
 
                 if exporter.scrape(&self.clients).await.is_ok() {
-                    if let Err(error) = exporter.do_request(&addr, &self.url).await {
-                        log::error!("Error while pushing metrics: {error}");
+                    match self.push_metrics(&exporter, &pusher, queue.as_mut()).await {
+                        Ok(()) => (),
+                        Err(PushError::Interrupted) => break,
+                        Err(error) => log::error!("Error while pushing metrics: {error}"),
                     }
                 }
             }
 
+            if self.push_job.is_some() {
+                if let Err(error) = pusher.delete().await {
+                    log::error!("Error while deleting pushed metrics: {error}");
+                }
+            }
+
             log::info!("Stop pusher for: {addr}");
         } else if exporter.scrape(&self.clients).await.is_ok() {
-            if let Err(error) = exporter.do_request(&addr, &self.url).await {
+            if let Err(error) = self.push_metrics(&exporter, &pusher, queue.as_mut()).await {
                 log::error!("Error while pushing metrics: {error}");
             }
         }
@@ -113,3 +595,337 @@ impl Main {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn grouping() {
+        assert_eq!(grouping_label("job", "ubmsc"), "/job/ubmsc");
+        assert_eq!(grouping_label("instance", ""), "/instance@base64/=");
+        assert_eq!(
+            grouping_label("ubmsc_device", "c8:47:80/1"),
+            "/ubmsc_device@base64/Yzg6NDc6ODAvMQ=="
+        );
+
+        let path = |url: &str| grouping_path(&url.parse().unwrap(), "ubmsc", "host");
+        assert_eq!(
+            path("http://127.0.0.1:9091"),
+            "/metrics/job/ubmsc/instance/host"
+        );
+        assert_eq!(
+            path("http://127.0.0.1:9091/"),
+            "/metrics/job/ubmsc/instance/host"
+        );
+        assert_eq!(
+            path("http://127.0.0.1:9091/gateway/metrics/"),
+            "/gateway/metrics/job/ubmsc/instance/host"
+        );
+    }
+
+    /// Request received by stub server
+    #[cfg(feature = "pull")]
+    type StubRequest = (http::request::Parts, Bytes);
+
+    /// Serve single connection of stub server responding with given status
+    #[cfg(feature = "pull")]
+    async fn serve_stub(
+        stream: impl tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+        status: u16,
+        sender: tokio::sync::mpsc::UnboundedSender<StubRequest>,
+    ) {
+        use http_body_util::BodyExt;
+        use hyper::{body::Incoming, server::conn::http1, service::service_fn, Response};
+
+        http1::Builder::new()
+            .serve_connection(
+                TokioIo::new(stream),
+                service_fn(move |request: Request<Incoming>| {
+                    let sender = sender.clone();
+                    async move {
+                        let (parts, body) = request.into_parts();
+                        let body = body.collect().await?.to_bytes();
+                        sender.send((parts, body)).unwrap();
+                        Ok::<_, hyper::Error>(
+                            Response::builder()
+                                .status(status)
+                                .body(Full::<Bytes>::default())
+                                .unwrap(),
+                        )
+                    }
+                }),
+            )
+            .await
+            .unwrap();
+    }
+
+    /// Start stub push target
+    ///
+    /// Connections are accepted one by one, each responds with the next
+    /// status. Received requests are sent to returned channel.
+    #[cfg(feature = "pull")]
+    async fn stub_server(
+        statuses: &[u16],
+    ) -> (
+        SocketAddr,
+        tokio::sync::mpsc::UnboundedReceiver<StubRequest>,
+        tokio::task::JoinHandle<()>,
+    ) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+
+        let statuses = statuses.to_vec();
+        let server = tokio::spawn(async move {
+            for status in statuses {
+                let (stream, _) = listener.accept().await.unwrap();
+                serve_stub(stream, status, sender.clone()).await;
+            }
+        });
+
+        (addr, receiver, server)
+    }
+
+    #[cfg(all(feature = "tls", feature = "pull"))]
+    #[tokio::test]
+    async fn https_auth() {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("ubmsc-push-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+        std::fs::write(&cert_path, certified.cert.pem()).unwrap();
+        std::fs::write(&key_path, certified.signing_key.serialize_pem()).unwrap();
+
+        let acceptor = tls::acceptor(&cert_path, &key_path).unwrap();
+        let connector = tls::connector(Some(&cert_path)).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let stream = acceptor.accept(stream).await.unwrap();
+            serve_stub(stream, 200, sender).await;
+        });
+
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer t0ken"));
+        headers.insert("x-scope-orgid", HeaderValue::from_static("home"));
+
+        let pusher = Pusher {
+            url: format!("https://localhost:{}/metrics", addr.port())
+                .parse()
+                .unwrap(),
+            addr,
+            protocol: PushProtocol::Exposition,
+            method: PushMethod::Put,
+            groups: Vec::new(),
+            headers,
+            tls: Some(connector),
+        };
+
+        pusher
+            .request(
+                Method::PUT,
+                "/metrics/job/ubmsc",
+                Some("text/plain"),
+                b"ubmsc_battery_voltage_volts 13.3\n".to_vec(),
+            )
+            .await
+            .unwrap();
+
+        let (parts, body) = receiver.recv().await.unwrap();
+
+        assert_eq!(parts.method, Method::PUT);
+        assert_eq!(parts.uri.path(), "/metrics/job/ubmsc");
+        assert_eq!(
+            parts.headers[HOST],
+            format!("localhost:{}", addr.port()).as_str()
+        );
+        assert_eq!(parts.headers[AUTHORIZATION], "Bearer t0ken");
+        assert_eq!(parts.headers["x-scope-orgid"], "home");
+        assert_eq!(parts.headers[CONTENT_TYPE], "text/plain");
+        assert_eq!(&body[..], b"ubmsc_battery_voltage_volts 13.3\n");
+
+        server.abort();
+    }
+
+    #[cfg(feature = "pull")]
+    #[tokio::test]
+    async fn flush_queue() {
+        // target is down, then accepts, rejects and accepts again
+        let (addr, mut receiver, server) = stub_server(&[503, 200, 400, 200]).await;
+
+        let pusher = Pusher {
+            url: format!("http://127.0.0.1:{}/api/v1/import/prometheus", addr.port())
+                .parse()
+                .unwrap(),
+            addr,
+            protocol: PushProtocol::Exposition,
+            method: PushMethod::Post,
+            groups: vec![Group {
+                path: "/api/v1/import/prometheus".into(),
+                device: None,
+            }],
+            headers: HeaderMap::new(),
+            #[cfg(feature = "tls")]
+            tls: None,
+        };
+
+        let dir = std::env::temp_dir().join(format!("ubmsc-push-queue-{}", std::process::id()));
+        let mut queue =
+            Queue::open(&dir, 10, QueueMetrics::new(&Default::default()).unwrap()).unwrap();
+        for sample in ["a 1 1000\n", "b 2 2000\n", "c 3 3000\n"] {
+            queue.push(sample.as_bytes()).unwrap();
+        }
+
+        assert!(matches!(
+            pusher.flush(&mut queue).await,
+            Err(PushError::BadStatus(503))
+        ));
+        assert_eq!(queue.len(), 3);
+
+        pusher.flush(&mut queue).await.unwrap();
+        assert!(queue.is_empty());
+
+        for sample in ["a 1 1000\n", "a 1 1000\n", "b 2 2000\n", "c 3 3000\n"] {
+            assert_eq!(&receiver.recv().await.unwrap().1[..], sample.as_bytes());
+        }
+
+        server.await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(all(feature = "remote-write", feature = "pull"))]
+    #[tokio::test]
+    async fn remote_write() {
+        let (addr, mut receiver, server) = stub_server(&[204]).await;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("snappy"));
+        headers.insert(
+            REMOTE_WRITE_VERSION_HEADER,
+            HeaderValue::from_static(REMOTE_WRITE_VERSION),
+        );
+
+        let pusher = Pusher {
+            url: format!("http://127.0.0.1:{}/api/v1/write", addr.port())
+                .parse()
+                .unwrap(),
+            addr,
+            protocol: PushProtocol::RemoteWrite,
+            method: PushMethod::Put,
+            groups: vec![Group {
+                path: "/api/v1/write".into(),
+                device: None,
+            }],
+            headers,
+            #[cfg(feature = "tls")]
+            tls: None,
+        };
+
+        let exporter =
+            Exporter::new(Default::default(), [("ups".into(), Default::default())]).unwrap();
+        exporter.push(&pusher).await.unwrap();
+
+        let (parts, body) = receiver.recv().await.unwrap();
+
+        assert_eq!(parts.method, Method::POST);
+        assert_eq!(parts.uri.path(), "/api/v1/write");
+        assert_eq!(parts.headers[CONTENT_TYPE], REMOTE_WRITE_FORMAT);
+        assert_eq!(parts.headers[CONTENT_ENCODING], "snappy");
+        assert_eq!(parts.headers[REMOTE_WRITE_VERSION_HEADER], "0.1.0");
+
+        let data = snap::raw::Decoder::new().decompress_vec(&body).unwrap();
+        // the first field is time series
+        assert_eq!(data[0], 0x0a);
+        let contains = |sub: &[u8]| data.windows(sub.len()).any(|win| win == sub);
+        assert!(contains(b"__name__"));
+        assert!(contains(b"ubmsc_battery_voltage_volts"));
+        assert!(contains(b"ubmsc_scrape_success"));
+        assert!(contains(b"ups"));
+
+        server.await.unwrap();
+    }
+
+    #[cfg(all(feature = "influx", feature = "pull"))]
+    #[tokio::test]
+    async fn influx() {
+        use crate::{CellData, DeviceInfo, Milli};
+
+        let (addr, mut receiver, server) = stub_server(&[204]).await;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Token t0ken"));
+
+        let path = "/api/v2/write?org=home&bucket=bms&precision=ns";
+
+        let pusher = Pusher {
+            url: format!("http://127.0.0.1:{}{path}", addr.port())
+                .parse()
+                .unwrap(),
+            addr,
+            protocol: PushProtocol::Influx,
+            method: PushMethod::Put,
+            groups: vec![Group {
+                path: path.into(),
+                device: None,
+            }],
+            headers,
+            #[cfg(feature = "tls")]
+            tls: None,
+        };
+
+        let exporter =
+            Exporter::new(Default::default(), [("ups".into(), Default::default())]).unwrap();
+
+        // nothing scraped yet so nothing sent
+        exporter.push(&pusher).await.unwrap();
+        assert!(receiver.try_recv().is_err());
+
+        exporter.store(
+            0,
+            Some(DeviceInfo {
+                serial_number: "40531310629".into(),
+                poweron_times: 3,
+                ..Default::default()
+            }),
+            Some(CellData {
+                battery_voltage: Milli::from_f64(14.25),
+                cell_voltage: vec![Milli::from_f64(3.562)],
+                ..Default::default()
+            }),
+        );
+        exporter.push(&pusher).await.unwrap();
+
+        let (parts, body) = receiver.recv().await.unwrap();
+
+        assert_eq!(parts.method, Method::POST);
+        assert_eq!(parts.uri, path);
+        assert_eq!(parts.headers[CONTENT_TYPE], INFLUX_FORMAT);
+        assert_eq!(parts.headers[AUTHORIZATION], "Token t0ken");
+
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let lines = body.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3, "{body}");
+        assert!(lines[0].starts_with(r#"bms,device=ups,serial=40531310629 device_model="","#));
+        assert!(lines[0].contains(",poweron_times=3i "));
+        assert!(lines[1].starts_with(
+            "bms,device=ups,serial=40531310629 average_cell_voltage=0,delta_cell_voltage=0,"
+        ));
+        assert!(lines[1].contains(",battery_voltage=14.25,"));
+        assert!(
+            lines[2].starts_with("bms,device=ups,serial=40531310629,cell=0 cell_voltage=3.562 ")
+        );
+
+        server.await.unwrap();
+    }
+}
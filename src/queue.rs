@@ -0,0 +1,160 @@
+use crate::{log, QueueMetrics, Result};
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Extension of queued sample files
+const EXTENSION: &str = "prom";
+
+/// Bounded on-disk queue of samples
+///
+/// Each sample is stored in a separate file named by sequence number
+/// so samples survive restarts and are delivered in order.
+pub struct Queue {
+    dir: PathBuf,
+    capacity: usize,
+    /// Sequence numbers of queued samples
+    entries: VecDeque<u64>,
+    metrics: QueueMetrics,
+}
+
+impl Queue {
+    /// Open queue in directory picking up samples queued before
+    pub fn open(dir: &Path, capacity: usize, metrics: QueueMetrics) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            if let Some(seq) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok())
+            {
+                entries.push(seq);
+            }
+        }
+        entries.sort_unstable();
+
+        let mut queue = Self {
+            dir: dir.into(),
+            capacity,
+            entries: entries.into(),
+            metrics,
+        };
+
+        if !queue.is_empty() {
+            log::info!("Found {} queued samples", queue.len());
+        }
+
+        queue.trim()?;
+
+        Ok(queue)
+    }
+
+    /// Number of queued samples
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Queue has no samples
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Append sample dropping the oldest ones when capacity exceeded
+    pub fn push(&mut self, data: &[u8]) -> Result<()> {
+        let seq = self.entries.back().map(|seq| seq + 1).unwrap_or_default();
+
+        // write to temporary file first to never leave partial samples
+        let temp = self.path(seq).with_extension("tmp");
+        fs::write(&temp, data)?;
+        fs::rename(&temp, self.path(seq))?;
+
+        self.entries.push_back(seq);
+
+        self.trim()
+    }
+
+    /// Read the oldest sample
+    pub fn front(&self) -> Result<Option<Vec<u8>>> {
+        self.entries
+            .front()
+            .map(|seq| Ok(fs::read(self.path(*seq))?))
+            .transpose()
+    }
+
+    /// Remove the oldest sample after delivery
+    pub fn pop(&mut self) -> Result<()> {
+        if let Some(seq) = self.entries.pop_front() {
+            fs::remove_file(self.path(seq))?;
+        }
+        self.metrics.depth(self.len());
+        Ok(())
+    }
+
+    /// Remove the oldest sample which cannot be delivered
+    pub fn discard(&mut self) -> Result<()> {
+        self.pop()?;
+        self.metrics.dropped(1);
+        Ok(())
+    }
+
+    fn trim(&mut self) -> Result<()> {
+        let excess = self.len().saturating_sub(self.capacity);
+        if excess > 0 {
+            log::warn!("Push queue is full, drop {excess} oldest samples");
+            for _ in 0..excess {
+                self.discard()?;
+            }
+        }
+        self.metrics.depth(self.len());
+        Ok(())
+    }
+
+    fn path(&self, seq: u64) -> PathBuf {
+        self.dir.join(format!("{seq:020}.{EXTENSION}"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn order_and_capacity() {
+        let dir = std::env::temp_dir().join(format!("ubmsc-queue-{}", std::process::id()));
+        let metrics = || QueueMetrics::new(&Default::default()).unwrap();
+
+        let mut queue = Queue::open(&dir, 3, metrics()).unwrap();
+        assert!(queue.is_empty());
+        assert!(queue.front().unwrap().is_none());
+
+        for sample in ["a", "b", "c", "d"] {
+            queue.push(sample.as_bytes()).unwrap();
+        }
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.front().unwrap().unwrap(), b"b");
+
+        queue.pop().unwrap();
+        drop(queue);
+
+        // samples survive reopening
+        let mut queue = Queue::open(&dir, 1, metrics()).unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.front().unwrap().unwrap(), b"d");
+
+        queue.push(b"e").unwrap();
+        assert_eq!(queue.front().unwrap().unwrap(), b"e");
+
+        queue.pop().unwrap();
+        assert!(queue.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,393 @@
+use crate::openmetrics::unit_of;
+use prometheus::{
+    proto::{LabelPair, Metric, MetricFamily, MetricType},
+    Encoder,
+};
+use std::{
+    io::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The content type of remote write requests
+pub const REMOTE_WRITE_FORMAT: &str = "application/x-protobuf";
+
+/// The version of remote write protocol
+pub const REMOTE_WRITE_VERSION: &str = "0.1.0";
+
+/// Encoder for Prometheus remote write protocol
+///
+/// Produces snappy-compressed protobuf `WriteRequest`.
+/// Samples without timestamps are stamped by the time of encoding.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct RemoteWriteEncoder;
+
+impl RemoteWriteEncoder {
+    /// Create new encoder
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Encoder for RemoteWriteEncoder {
+    fn encode<W: Write>(
+        &self,
+        families: &[MetricFamily],
+        writer: &mut W,
+    ) -> prometheus::Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+
+        let mut request = Vec::with_capacity(4096);
+
+        for family in families {
+            let name = family.get_name();
+
+            for metric in family.get_metric() {
+                let timestamp = match metric.get_timestamp_ms() {
+                    0 => now,
+                    timestamp => timestamp,
+                };
+
+                for (suffix, extra_label, value) in samples(family.get_field_type(), metric) {
+                    let series = time_series(
+                        &format!("{name}{suffix}"),
+                        metric.get_label(),
+                        extra_label
+                            .as_ref()
+                            .map(|(name, value)| (*name, value.as_str())),
+                        value,
+                        timestamp,
+                    );
+                    proto::message(&mut request, 1, &series);
+                }
+            }
+
+            proto::message(&mut request, 3, &metadata(family));
+        }
+
+        let data = snap::raw::Encoder::new()
+            .compress_vec(&request)
+            .map_err(|error| prometheus::Error::Msg(error.to_string()))?;
+
+        writer.write_all(&data)?;
+
+        Ok(())
+    }
+
+    fn format_type(&self) -> &str {
+        REMOTE_WRITE_FORMAT
+    }
+}
+
+type Sample = (&'static str, Option<(&'static str, String)>, f64);
+
+/// Split metric to samples
+fn samples(kind: MetricType, metric: &Metric) -> Vec<Sample> {
+    match kind {
+        MetricType::COUNTER => vec![("", None, metric.get_counter().get_value())],
+        MetricType::GAUGE => vec![("", None, metric.get_gauge().get_value())],
+        MetricType::UNTYPED => vec![("", None, metric.get_untyped().get_value())],
+        MetricType::HISTOGRAM => {
+            let histogram = metric.get_histogram();
+            let count = histogram.get_sample_count() as f64;
+            let mut samples = histogram
+                .get_bucket()
+                .iter()
+                .map(|bucket| {
+                    (
+                        "_bucket",
+                        Some(("le", format_bound(bucket.get_upper_bound()))),
+                        bucket.get_cumulative_count() as f64,
+                    )
+                })
+                .collect::<Vec<_>>();
+            if !histogram
+                .get_bucket()
+                .iter()
+                .any(|bucket| bucket.get_upper_bound() == f64::INFINITY)
+            {
+                samples.push(("_bucket", Some(("le", "+Inf".into())), count));
+            }
+            samples.push(("_count", None, count));
+            samples.push(("_sum", None, histogram.get_sample_sum()));
+            samples
+        }
+        MetricType::SUMMARY => {
+            let summary = metric.get_summary();
+            let mut samples = summary
+                .get_quantile()
+                .iter()
+                .map(|quantile| {
+                    (
+                        "",
+                        Some(("quantile", quantile.get_quantile().to_string())),
+                        quantile.get_value(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            samples.push(("_count", None, summary.get_sample_count() as f64));
+            samples.push(("_sum", None, summary.get_sample_sum()));
+            samples
+        }
+    }
+}
+
+fn format_bound(bound: f64) -> String {
+    if bound == f64::INFINITY {
+        "+Inf".into()
+    } else {
+        bound.to_string()
+    }
+}
+
+/// Encode `TimeSeries` message with single sample
+fn time_series(
+    name: &str,
+    labels: &[LabelPair],
+    extra_label: Option<(&str, &str)>,
+    value: f64,
+    timestamp: i64,
+) -> Vec<u8> {
+    // labels should be sorted by name
+    let mut labels = labels
+        .iter()
+        .map(|label| (label.get_name(), label.get_value()))
+        .chain(extra_label)
+        .chain([("__name__", name)])
+        .collect::<Vec<_>>();
+    labels.sort_unstable_by_key(|(name, _)| *name);
+
+    let mut series = Vec::new();
+
+    for (name, value) in labels {
+        let mut label = Vec::new();
+        proto::string(&mut label, 1, name);
+        proto::string(&mut label, 2, value);
+        proto::message(&mut series, 1, &label);
+    }
+
+    let mut sample = Vec::new();
+    proto::double(&mut sample, 1, value);
+    proto::int64(&mut sample, 2, timestamp);
+    proto::message(&mut series, 2, &sample);
+
+    series
+}
+
+/// Encode `MetricMetadata` message
+fn metadata(family: &MetricFamily) -> Vec<u8> {
+    let kind = match family.get_field_type() {
+        MetricType::COUNTER => 1,
+        MetricType::GAUGE => 2,
+        MetricType::HISTOGRAM => 3,
+        MetricType::SUMMARY => 5,
+        MetricType::UNTYPED => 0,
+    };
+
+    let mut metadata = Vec::new();
+    proto::int64(&mut metadata, 1, kind);
+    proto::string(&mut metadata, 2, family.get_name());
+    proto::string(&mut metadata, 4, family.get_help());
+    if let Some(unit) = unit_of(family.get_name().trim_end_matches("_total")) {
+        proto::string(&mut metadata, 5, unit);
+    }
+    metadata
+}
+
+/// Minimal protobuf encoding
+mod proto {
+    const VARINT: u64 = 0;
+    const FIXED64: u64 = 1;
+    const LEN: u64 = 2;
+
+    fn varint(output: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            output.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        output.push(value as u8);
+    }
+
+    fn tag(output: &mut Vec<u8>, field: u64, wire_type: u64) {
+        varint(output, (field << 3) | wire_type);
+    }
+
+    pub fn int64(output: &mut Vec<u8>, field: u64, value: i64) {
+        tag(output, field, VARINT);
+        varint(output, value as u64);
+    }
+
+    pub fn double(output: &mut Vec<u8>, field: u64, value: f64) {
+        tag(output, field, FIXED64);
+        output.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn message(output: &mut Vec<u8>, field: u64, data: &[u8]) {
+        tag(output, field, LEN);
+        varint(output, data.len() as u64);
+        output.extend_from_slice(data);
+    }
+
+    pub fn string(output: &mut Vec<u8>, field: u64, value: &str) {
+        message(output, field, value.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prometheus::{Counter, GaugeVec, Opts, Registry};
+
+    /// Field of decoded protobuf message
+    #[derive(Debug)]
+    enum Field<'a> {
+        Varint(u64),
+        Fixed64([u8; 8]),
+        Len(&'a [u8]),
+    }
+
+    fn decode_varint(data: &mut &[u8]) -> u64 {
+        let mut value = 0;
+        for shift in (0..).step_by(7) {
+            let byte = data[0];
+            *data = &data[1..];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                break;
+            }
+        }
+        value
+    }
+
+    fn decode(mut data: &[u8]) -> Vec<(u64, Field<'_>)> {
+        let mut fields = Vec::new();
+        while !data.is_empty() {
+            let tag = decode_varint(&mut data);
+            let field = match tag & 7 {
+                0 => Field::Varint(decode_varint(&mut data)),
+                1 => {
+                    let (value, rest) = data.split_at(8);
+                    data = rest;
+                    Field::Fixed64(value.try_into().unwrap())
+                }
+                2 => {
+                    let len = decode_varint(&mut data) as usize;
+                    let (value, rest) = data.split_at(len);
+                    data = rest;
+                    Field::Len(value)
+                }
+                wire_type => panic!("Unexpected wire type: {wire_type}"),
+            };
+            fields.push((tag >> 3, field));
+        }
+        fields
+    }
+
+    fn string(field: &Field) -> String {
+        match field {
+            Field::Len(data) => String::from_utf8(data.to_vec()).unwrap(),
+            _ => panic!("String expected"),
+        }
+    }
+
+    /// Decode time series as labels, value and timestamp
+    fn series(data: &[u8]) -> (Vec<(String, String)>, f64, i64) {
+        let mut labels = Vec::new();
+        let mut sample = (0.0, 0);
+
+        for (field, value) in decode(data) {
+            let Field::Len(data) = value else {
+                panic!("Message expected");
+            };
+            match field {
+                1 => {
+                    let label = decode(data);
+                    labels.push((string(&label[0].1), string(&label[1].1)));
+                }
+                2 => {
+                    for (field, value) in decode(data) {
+                        match (field, value) {
+                            (1, Field::Fixed64(value)) => sample.0 = f64::from_le_bytes(value),
+                            (2, Field::Varint(value)) => sample.1 = value as i64,
+                            other => panic!("Unexpected field of sample: {other:?}"),
+                        }
+                    }
+                }
+                _ => panic!("Unexpected field of time series: {field}"),
+            }
+        }
+
+        (labels, sample.0, sample.1)
+    }
+
+    #[test]
+    fn encode() {
+        let registry = Registry::new();
+
+        let temperature = GaugeVec::new(
+            Opts::new("battery_temperature_celsius", "Temperatures of battery, ℃")
+                .const_label("device", "ups"),
+            &["cell"],
+        )
+        .unwrap();
+        temperature.with_label_values(&["0"]).set(23.5);
+        registry.register(Box::new(temperature)).unwrap();
+
+        let cycles =
+            Counter::with_opts(Opts::new("cycle_count_total", "Number of battery cycles")).unwrap();
+        cycles.inc_by(7.0);
+        registry.register(Box::new(cycles)).unwrap();
+
+        let mut families = registry.gather();
+        families[1].mut_metric()[0].set_timestamp_ms(1700000000000);
+
+        let mut buffer = Vec::new();
+        RemoteWriteEncoder::new()
+            .encode(&families, &mut buffer)
+            .unwrap();
+
+        let data = snap::raw::Decoder::new().decompress_vec(&buffer).unwrap();
+        let request = decode(&data);
+
+        let series = request
+            .iter()
+            .filter_map(|(field, value)| match (field, value) {
+                (1, Field::Len(data)) => Some(series(data)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(series.len(), 2);
+
+        let (labels, value, timestamp) = &series[0];
+        assert_eq!(
+            labels,
+            &[
+                ("__name__".into(), "battery_temperature_celsius".into()),
+                ("cell".into(), "0".into()),
+                ("device".into(), "ups".into()),
+            ]
+        );
+        assert_eq!(*value, 23.5);
+        assert!(*timestamp > 1700000000000);
+
+        let (labels, value, timestamp) = &series[1];
+        assert_eq!(labels, &[("__name__".into(), "cycle_count_total".into())]);
+        assert_eq!(*value, 7.0);
+        assert_eq!(*timestamp, 1700000000000);
+
+        let metadata = request
+            .iter()
+            .filter_map(|(field, value)| match (field, value) {
+                (3, Field::Len(data)) => Some(decode(data)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(metadata.len(), 2);
+        assert!(matches!(metadata[0][0], (1, Field::Varint(2))));
+        assert_eq!(string(&metadata[0][1].1), "battery_temperature_celsius");
+        assert_eq!(string(&metadata[0][3].1), "celsius");
+        assert!(matches!(metadata[1][0], (1, Field::Varint(1))));
+    }
+}
@@ -41,6 +41,9 @@ pub enum Error {
     /// Unable to resolve hostname
     #[error("Unknown hostname")]
     UnknownHostname,
+    /// Invalid configuration
+    #[error("Invalid config: {0}")]
+    BadConfig(String),
     /// Json format error
     #[cfg(feature = "json")]
     #[error("JSON format error: {0}")]
@@ -53,6 +56,56 @@ pub enum Error {
     #[cfg(feature = "toml")]
     #[error("TOML format error: {0}")]
     TomlEnc(#[from] serde_toml::ser::Error),
+    /// Toml parse error
+    #[cfg(feature = "toml")]
+    #[error("TOML parse error: {0}")]
+    TomlDec(#[from] serde_toml::de::Error),
+    /// CBOR format error
+    #[cfg(feature = "cbor")]
+    #[error("CBOR format error: {0}")]
+    CborEnc(#[from] ciborium::ser::Error<std::io::Error>),
+    /// MessagePack format error
+    #[cfg(feature = "msgpack")]
+    #[error("MessagePack format error: {0}")]
+    MsgPackEnc(#[from] rmp_serde::encode::Error),
+    /// SQLite database error
+    #[cfg(feature = "history")]
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+impl Error {
+    /// Short machine-readable kind of error
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Io(_) => "io",
+            Self::Bt(_) => "bluetooth",
+            #[cfg(feature = "metrics")]
+            Self::Prometheus(_) => "prometheus",
+            Self::Utf8(_) => "utf8",
+            Self::Timeout => "timeout",
+            Self::NotFound => "not_found",
+            Self::BadCrc => "bad_crc",
+            Self::BadRecordType => "bad_record_type",
+            Self::LostConnection => "lost_connection",
+            Self::NotEnoughData => "not_enough_data",
+            Self::NotSupported => "not_supported",
+            Self::UnknownHostname => "unknown_hostname",
+            Self::BadConfig(_) => "bad_config",
+            #[cfg(feature = "json")]
+            Self::JsonEnc(_) => "json",
+            #[cfg(feature = "yaml")]
+            Self::YamlEnc(_) => "yaml",
+            #[cfg(feature = "toml")]
+            Self::TomlEnc(_) | Self::TomlDec(_) => "toml",
+            #[cfg(feature = "cbor")]
+            Self::CborEnc(_) => "cbor",
+            #[cfg(feature = "msgpack")]
+            Self::MsgPackEnc(_) => "msgpack",
+            #[cfg(feature = "history")]
+            Self::Sqlite(_) => "sqlite",
+        }
+    }
 }
 
 impl From<tokio::time::error::Elapsed> for Error {
@@ -0,0 +1,466 @@
+use crate::{Alarm, CellData, DeviceInfo, Field, FieldKind, FieldValue, Milli};
+use std::io::{Result, Write};
+
+/// Data of device to show in table
+#[derive(Clone, Copy, Debug)]
+pub struct TableDevice<'a> {
+    /// Device alias or identifier
+    pub name: &'a str,
+    pub device_info: Option<&'a DeviceInfo>,
+    pub cell_data: Option<&'a CellData>,
+}
+
+/// Write human-readable summary and per-cell grid of devices side by side
+///
+/// Rows of summary and columns of grid follow field descriptors. Min and max
+/// cells are marked with `▼` and `▲`. When `color` is set ANSI escape
+/// sequences are used to highlight extremes and values which raise alarms
+/// (for terminals).
+pub fn write_table(devices: &[TableDevice], color: bool, output: &mut dyn Write) -> Result<()> {
+    let mut summary = Grid::default();
+
+    summary.row(
+        [Text::header("")]
+            .into_iter()
+            .chain(devices.iter().map(|device| Text::header(device.name))),
+    );
+
+    if devices.iter().any(|device| device.device_info.is_some()) {
+        let columns: Vec<_> = devices
+            .iter()
+            .map(|device| device.device_info.map(|info| texts(info.values(), &[])))
+            .collect();
+        summary.fields(DeviceInfo::FIELDS, &columns);
+    }
+
+    if devices.iter().any(|device| device.cell_data.is_some()) {
+        let columns: Vec<_> = devices
+            .iter()
+            .map(|device| {
+                device.cell_data.map(|data| {
+                    let values = data
+                        .values()
+                        .filter(|(field, _)| field.kind != FieldKind::Cells);
+                    texts(values, &data.alarms())
+                })
+            })
+            .collect();
+        summary.fields(
+            CellData::FIELDS
+                .iter()
+                .filter(|field| field.kind != FieldKind::Cells),
+            &columns,
+        );
+    }
+
+    summary.write(color, output)?;
+
+    let cells = devices
+        .iter()
+        .filter_map(|device| device.cell_data)
+        .flat_map(|data| data.values().map(|(_, value)| value.cells()))
+        .max()
+        .unwrap_or_default();
+
+    if cells == 0 {
+        return Ok(());
+    }
+
+    let devices: Vec<_> = devices
+        .iter()
+        .filter_map(|device| device.cell_data.map(|data| (device.name, data)))
+        .collect();
+
+    let fields: Vec<_> = CellData::FIELDS
+        .iter()
+        .filter(|field| field.kind == FieldKind::Cells)
+        .collect();
+
+    let mut grid = Grid::default();
+
+    grid.row(
+        [Text::header("Cell")]
+            .into_iter()
+            .chain(devices.iter().flat_map(|(name, _)| {
+                [Text::header(*name)]
+                    .into_iter()
+                    .chain(fields.iter().map(|_| Text::default()))
+                    .chain([Text::default()])
+            })),
+    );
+    grid.row(
+        [Text::default()]
+            .into_iter()
+            .chain(devices.iter().flat_map(|_| {
+                fields
+                    .iter()
+                    .map(|field| Text::label(field.unit))
+                    .chain([Text::label("ΔmV"), Text::default()])
+            })),
+    );
+
+    let devices: Vec<_> = devices
+        .iter()
+        .map(|(_, data)| (data, extremes(&data.cell_voltage), data.alarms()))
+        .collect();
+
+    for cell in 0..cells {
+        grid.row(
+            [Text::label(cell.to_string())]
+                .into_iter()
+                .chain(devices.iter().flat_map(|(data, extremes, alarms)| {
+                    let voltage = data.cell_voltage.get(cell).copied();
+                    let average = if data.average_cell_voltage.is_finite() {
+                        data.average_cell_voltage.to_f64()
+                    } else {
+                        mean(&data.cell_voltage)
+                    };
+
+                    let mark = match extremes {
+                        Some((min, _)) if *min == cell => Some(Style::Min),
+                        Some((_, max)) if *max == cell => Some(Style::Max),
+                        _ => None,
+                    };
+                    let style = mark.unwrap_or(Style::Plain);
+
+                    let values = data
+                        .values()
+                        .filter(|(field, _)| field.kind == FieldKind::Cells)
+                        .map(move |(field, value)| {
+                            let style = if alarms.iter().any(|alarm| {
+                                alarm.field() == field.name && alarm.sensor() == Some(cell)
+                            }) {
+                                Style::Warn
+                            } else if field.name == "cell_voltage" {
+                                style
+                            } else {
+                                Style::Plain
+                            };
+                            Text::new(value.cell(cell).map(|value| number(field, value)), style)
+                        });
+
+                    values.chain([
+                        Text::new(
+                            voltage.map(|value| signed((value.to_f64() - average) * 1e3, 0)),
+                            style,
+                        ),
+                        Text::new(
+                            mark.map(|mark| if mark == Style::Min { "▼" } else { "▲" }),
+                            style,
+                        ),
+                    ])
+                })),
+        );
+    }
+
+    writeln!(output)?;
+    grid.write(color, output)
+}
+
+/// Summary texts of field values (values which caused alarms are highlighted)
+fn texts<'a>(
+    values: impl Iterator<Item = (&'static Field, FieldValue<'a>)>,
+    alarms: &[Alarm],
+) -> Vec<Text> {
+    values
+        .map(|(field, value)| {
+            let text = match value {
+                FieldValue::Label(value) => value.into(),
+                FieldValue::Integer(value) if field.unit == "s" => duration(value),
+                value if field.unit.is_empty() => number(field, value),
+                value if value.is_finite() => format!("{} {}", number(field, value), field.unit),
+                _ => "-".into(),
+            };
+            if alarms.iter().any(|alarm| alarm.field() == field.name) {
+                Text::warn(text)
+            } else {
+                Text::plain(text)
+            }
+        })
+        .collect()
+}
+
+/// Indexes of min and max values (none when all values are equal)
+fn extremes(values: &[Milli]) -> Option<(usize, usize)> {
+    let finite = || {
+        values
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| value.is_finite())
+    };
+    let min = finite().min_by_key(|(_, value)| value.raw())?;
+    let max = finite().max_by_key(|(_, value)| value.raw())?;
+    (min.1 != max.1).then_some((min.0, max.0))
+}
+
+fn mean(values: &[Milli]) -> f64 {
+    values.iter().map(|value| value.to_f64()).sum::<f64>() / values.len() as f64
+}
+
+/// Format value with precision of field
+fn number(field: &Field, value: FieldValue) -> String {
+    if value.is_finite() {
+        format!("{value:.precision$}", precision = field.scale as usize)
+    } else {
+        "-".into()
+    }
+}
+
+fn signed(value: f64, precision: usize) -> String {
+    if value.is_finite() {
+        // avoid negative zero
+        let value = if format!("{value:.precision$}")
+            .trim_start_matches(['-', '0', '.'])
+            .is_empty()
+        {
+            0.0
+        } else {
+            value
+        };
+        format!("{value:+.precision$}")
+    } else {
+        "-".into()
+    }
+}
+
+fn duration(seconds: u64) -> String {
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+    let (hours, seconds) = (seconds / 3600, seconds % 3600);
+    let minutes = seconds / 60;
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m")
+    } else {
+        format!("{hours}h {minutes}m")
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Style {
+    #[default]
+    Plain,
+    Header,
+    Label,
+    Warn,
+    Min,
+    Max,
+}
+
+impl Style {
+    /// ANSI escape sequence
+    fn escape(&self) -> &'static str {
+        match self {
+            Self::Plain => "",
+            Self::Header => "\x1b[1m",
+            Self::Label => "\x1b[2m",
+            Self::Warn => "\x1b[33m",
+            Self::Min => "\x1b[34m",
+            Self::Max => "\x1b[31m",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Text {
+    text: String,
+    style: Style,
+}
+
+impl Text {
+    fn new(text: Option<impl Into<String>>, style: Style) -> Self {
+        Self {
+            text: text.map(Into::into).unwrap_or_default(),
+            style,
+        }
+    }
+
+    fn plain(text: impl Into<String>) -> Self {
+        Self::new(Some(text), Style::Plain)
+    }
+
+    fn header(text: impl Into<String>) -> Self {
+        Self::new(Some(text), Style::Header)
+    }
+
+    fn label(text: impl Into<String>) -> Self {
+        Self::new(Some(text), Style::Label)
+    }
+
+    fn warn(text: impl Into<String>) -> Self {
+        Self::new(Some(text), Style::Warn)
+    }
+
+    fn width(&self) -> usize {
+        self.text.chars().count()
+    }
+}
+
+/// Table with first column aligned left and others aligned right
+#[derive(Default)]
+struct Grid {
+    rows: Vec<Vec<Text>>,
+}
+
+impl Grid {
+    /// Rows of fields with texts of devices (empty for devices without data)
+    fn fields<'a>(
+        &mut self,
+        fields: impl IntoIterator<Item = &'a Field>,
+        columns: &[Option<Vec<Text>>],
+    ) {
+        for (index, field) in fields.into_iter().enumerate() {
+            self.row(
+                [Text::label(field.description)]
+                    .into_iter()
+                    .chain(columns.iter().map(|column| {
+                        column
+                            .as_ref()
+                            .and_then(|column| column.get(index))
+                            .cloned()
+                            .unwrap_or_default()
+                    })),
+            );
+        }
+    }
+
+    fn row(&mut self, row: impl IntoIterator<Item = Text>) {
+        self.rows.push(row.into_iter().collect());
+    }
+
+    fn write(&self, color: bool, output: &mut dyn Write) -> Result<()> {
+        let columns = self.rows.iter().map(Vec::len).max().unwrap_or_default();
+        let widths: Vec<_> = (0..columns)
+            .map(|column| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.get(column))
+                    .map(Text::width)
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        for row in &self.rows {
+            let mut line = String::new();
+            for (column, text) in row.iter().enumerate() {
+                let padding = " ".repeat(widths[column] - text.width());
+                if column > 0 {
+                    line.push_str("  ");
+                    line.push_str(&padding);
+                }
+                if color && text.style != Style::Plain && !text.text.is_empty() {
+                    line.push_str(text.style.escape());
+                    line.push_str(&text.text);
+                    line.push_str("\x1b[0m");
+                } else {
+                    line.push_str(&text.text);
+                }
+                if column == 0 {
+                    line.push_str(&padding);
+                }
+            }
+            writeln!(output, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Deci;
+
+    #[test]
+    fn devices() {
+        let ups_info = DeviceInfo {
+            device_model: "JK_BD4A8S4P".into(),
+            serial_number: "40531310629".into(),
+            hardware_version: "11.XW".into(),
+            software_version: "11.26".into(),
+            ..Default::default()
+        };
+        let ups_data = CellData {
+            cell_voltage: [3.301, 3.312, 3.305].map(Milli::from_f64).to_vec(),
+            cell_resistance: [0.053, 0.054, 0.052].map(Milli::from_f64).to_vec(),
+            average_cell_voltage: Milli::from_f64(3.306),
+            delta_cell_voltage: Milli::from_f64(0.011),
+            balance_current: Milli::from_f64(0.012),
+            battery_voltage: Milli::from_f64(9.918),
+            battery_current: Milli::from_f64(-1.5),
+            battery_power: Milli::from_f64(14.877),
+            battery_temperature: [20.3, 55.0].map(Deci::from_f64).to_vec(),
+            mosfet_temperature: Deci::from_f64(21.0),
+            remain_percent: 5,
+            remain_capacity: Milli::from_f64(0.45),
+            nominal_capacity: Milli::from_f64(3.0),
+            cycle_count: 2,
+            cycle_capacity: Milli::from_f64(6.2),
+            up_time: 1204213,
+        };
+        let solar_data = CellData {
+            cell_voltage: [3.3, 3.3].map(Milli::from_f64).to_vec(),
+            average_cell_voltage: Milli::NAN,
+            battery_voltage: Milli::from_f64(6.6),
+            remain_percent: 90,
+            mosfet_temperature: Deci::NAN,
+            up_time: 3700,
+            ..Default::default()
+        };
+
+        let devices = [
+            TableDevice {
+                name: "ups",
+                device_info: Some(&ups_info),
+                cell_data: Some(&ups_data),
+            },
+            TableDevice {
+                name: "solar",
+                device_info: None,
+                cell_data: Some(&solar_data),
+            },
+        ];
+
+        let mut output = Vec::new();
+        write_table(&devices, false, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "                                     ups      solar
+Model name                   JK_BD4A8S4P
+Hardware version                   11.XW
+Firmware version                   11.26
+Serial number                40531310629
+Manufacturing date
+Device name
+Number of poweron cycles               0
+Average voltage of cells         3.306 V          -
+Delta voltage of cells           0.011 V    0.000 V
+Cells balance current            0.012 A    0.000 A
+Voltage of battery               9.918 V    6.600 V
+Power of battery                14.877 W    0.000 W
+Current of battery              -1.500 A    0.000 A
+Temperature of mosfet             21.0 ℃          -
+Remain capacity of battery           5 %       90 %
+Remain capacity of battery     0.450 A·h  0.000 A·h
+Nominal capacity of battery    3.000 A·h  0.000 A·h
+Number of battery cycles               2          0
+Cycle capacity                 6.200 A·h  0.000 A·h
+Time since last poweron      13d 22h 30m      1h 1m
+
+Cell    ups                       solar
+          V      Ω     ℃  ΔmV         V  Ω  ℃  ΔmV
+0     3.301  0.053  20.3   -5  ▼  3.300         +0
+1     3.312  0.054  55.0   +6  ▲  3.300         +0
+2     3.305  0.052         -1
+"
+        );
+
+        let mut output = Vec::new();
+        write_table(&devices[..1], true, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\x1b[33m5 %\x1b[0m"));
+        assert!(output.contains("\x1b[33m55.0\x1b[0m"));
+        assert!(output.contains("\x1b[34m3.301\x1b[0m"));
+        assert!(output.contains("\x1b[31m▲\x1b[0m"));
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{Error, Result};
+use std::{fs::File, io::BufReader, path::Path, sync::Arc};
+use tokio_rustls::rustls::pki_types::CertificateDer;
+
+#[cfg(feature = "pull")]
+use tokio_rustls::{rustls::ServerConfig, TlsAcceptor};
+
+#[cfg(feature = "push")]
+use crate::log;
+#[cfg(feature = "push")]
+use tokio_rustls::{
+    rustls::{ClientConfig, RootCertStore},
+    TlsConnector,
+};
+
+/// Create TLS acceptor using certificate chain and private key files in PEM format
+#[cfg(feature = "pull")]
+pub fn acceptor(cert: &Path, key: &Path) -> Result<TlsAcceptor> {
+    let certs = load_certs(cert)?;
+
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key)?))?
+        .ok_or_else(|| Error::BadConfig(format!("No private key in '{}'", key.display())))?;
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|error| Error::BadConfig(format!("Invalid TLS certificate or key: {error}")))?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Create TLS connector using system root certificates and optional custom CA certificates
+#[cfg(feature = "push")]
+pub fn connector(ca_cert: Option<&Path>) -> Result<TlsConnector> {
+    let mut roots = RootCertStore::empty();
+
+    let native = rustls_native_certs::load_native_certs();
+    for error in &native.errors {
+        log::warn!("Error while loading system root certificates: {error}");
+    }
+    let (added, ignored) = roots.add_parsable_certificates(native.certs);
+    log::debug!("Use {added} system root certificates ({ignored} ignored)");
+
+    if let Some(path) = ca_cert {
+        for cert in load_certs(path)? {
+            roots
+                .add(cert)
+                .map_err(|error| Error::BadConfig(format!("Invalid CA certificate: {error}")))?;
+        }
+    }
+
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// Load certificates in PEM format
+pub fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?))
+        .collect::<core::result::Result<Vec<_>, _>>()?;
+
+    if certs.is_empty() {
+        return Err(Error::BadConfig(format!(
+            "No certificates in '{}'",
+            path.display()
+        )));
+    }
+
+    Ok(certs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "pull")]
+    #[tokio::test]
+    async fn self_signed() {
+        use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+        use tokio_rustls::{
+            rustls::{pki_types::ServerName, ClientConfig, RootCertStore},
+            TlsConnector,
+        };
+
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("ubmsc-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+        std::fs::write(&cert_path, certified.cert.pem()).unwrap();
+        std::fs::write(&key_path, certified.signing_key.serialize_pem()).unwrap();
+
+        let acceptor = acceptor(&cert_path, &key_path).unwrap();
+
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(&cert_path).unwrap() {
+            roots.add(cert).unwrap();
+        }
+        let connector = TlsConnector::from(Arc::new(
+            ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let (client, server) = duplex(4096);
+
+        let (client, server) = tokio::join!(
+            connector.connect(ServerName::try_from("localhost").unwrap(), client),
+            acceptor.accept(server),
+        );
+        let (mut client, mut server) = (client.unwrap(), server.unwrap());
+
+        client.write_all(b"ping").await.unwrap();
+        client.flush().await.unwrap();
+
+        let mut buffer = [0u8; 4];
+        server.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"ping");
+    }
+
+    #[test]
+    fn missing_certs() {
+        let dir = std::env::temp_dir().join(format!("ubmsc-tls-empty-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("empty.pem");
+        std::fs::write(&path, "").unwrap();
+
+        assert!(matches!(load_certs(&path), Err(Error::BadConfig(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
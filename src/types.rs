@@ -1,4 +1,4 @@
-use crate::{Error, MacAddr, Result};
+use crate::{Deci, Error, MacAddr, Milli, Result};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -49,13 +49,33 @@ impl core::fmt::Display for DeviceId {
     }
 }
 
+#[cfg(feature = "serde")]
+impl Serialize for DeviceId {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for DeviceId {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> core::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 /// BMS device information
 #[derive(Clone, Default, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DeviceInfo {
     /// Model name
     pub device_model: String,
-    /// Hadrware version
+    /// Hardware version
     pub hardware_version: String,
     /// Firmware version
     pub software_version: String,
@@ -86,35 +106,109 @@ pub struct DeviceInfo {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CellData {
     /// Cell voltages in Volts
-    pub cell_voltage: Vec<f32>,
-    /// Averate cell voltage in Volts
-    pub average_cell_voltage: f32,
+    pub cell_voltage: Vec<Milli>,
+    /// Average cell voltage in Volts
+    pub average_cell_voltage: Milli,
     /// Maximum voltage difference between cells in Volts
-    pub delta_cell_voltage: f32,
+    pub delta_cell_voltage: Milli,
     /// Balance current in Amperes
-    pub balance_current: f32,
+    pub balance_current: Milli,
     /// Cell resistances in Ohms
-    pub cell_resistance: Vec<f32>,
+    pub cell_resistance: Vec<Milli>,
     /// Amount battery voltage between terminals in Volts
-    pub battery_voltage: f32,
+    pub battery_voltage: Milli,
     /// Amount battery power in Watts
-    pub battery_power: f32,
+    pub battery_power: Milli,
     /// Battery current in Amperes
-    pub battery_current: f32,
+    pub battery_current: Milli,
     /// Battery temperatures in Celsius degrees
-    pub battery_temperature: Vec<f32>,
+    pub battery_temperature: Vec<Deci>,
     /// BMS power mosfet temperature in Celsius degrees
-    pub mosfet_temperature: f32,
+    pub mosfet_temperature: Deci,
     /// Remain battery capacity in percents
     pub remain_percent: u8,
     /// Remain battery capacity in Amperes*Hours
-    pub remain_capacity: f32,
+    pub remain_capacity: Milli,
     /// Nominal battery capacity in Amperes*Hours
-    pub nominal_capacity: f32,
+    pub nominal_capacity: Milli,
     /// Number of battery cycles
     pub cycle_count: usize,
-    /// Cicle battery capacity in Amperes*Hours
-    pub cycle_capacity: f32,
+    /// Cycle battery capacity in Amperes*Hours
+    pub cycle_capacity: Milli,
     /// Time in seconds since last poweron
     pub up_time: usize,
 }
+
+/// BMS settings
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Settings {
+    /// Smart sleep voltage in Volts
+    pub smart_sleep_voltage: Milli,
+    /// Cell undervoltage protection in Volts
+    pub cell_uvp: Milli,
+    /// Cell undervoltage protection recovery in Volts
+    pub cell_uvpr: Milli,
+    /// Cell overvoltage protection in Volts
+    pub cell_ovp: Milli,
+    /// Cell overvoltage protection recovery in Volts
+    pub cell_ovpr: Milli,
+    /// Balance trigger voltage difference in Volts
+    pub balance_trigger_voltage: Milli,
+    /// Cell voltage of fully charged battery in Volts
+    pub soc100_voltage: Milli,
+    /// Cell voltage of fully discharged battery in Volts
+    pub soc0_voltage: Milli,
+    /// Requested charge voltage of cell in Volts
+    pub request_charge_voltage: Milli,
+    /// Requested float voltage of cell in Volts
+    pub request_float_voltage: Milli,
+    /// Cell voltage to power off in Volts
+    pub power_off_voltage: Milli,
+    /// Maximum charge current in Amperes
+    pub max_charge_current: Milli,
+    /// Charge overcurrent protection delay in seconds
+    pub charge_ocp_delay: usize,
+    /// Charge overcurrent protection recovery time in seconds
+    pub charge_ocp_recovery: usize,
+    /// Maximum discharge current in Amperes
+    pub max_discharge_current: Milli,
+    /// Discharge overcurrent protection delay in seconds
+    pub discharge_ocp_delay: usize,
+    /// Discharge overcurrent protection recovery time in seconds
+    pub discharge_ocp_recovery: usize,
+    /// Short circuit protection recovery time in seconds
+    pub scp_recovery: usize,
+    /// Maximum balance current in Amperes
+    pub max_balance_current: Milli,
+    /// Charge overtemperature protection in Celsius degrees
+    pub charge_otp: Deci,
+    /// Charge overtemperature protection recovery in Celsius degrees
+    pub charge_otp_recovery: Deci,
+    /// Discharge overtemperature protection in Celsius degrees
+    pub discharge_otp: Deci,
+    /// Discharge overtemperature protection recovery in Celsius degrees
+    pub discharge_otp_recovery: Deci,
+    /// Charge undertemperature protection in Celsius degrees
+    pub charge_utp: Deci,
+    /// Charge undertemperature protection recovery in Celsius degrees
+    pub charge_utp_recovery: Deci,
+    /// Mosfet overtemperature protection in Celsius degrees
+    pub mosfet_otp: Deci,
+    /// Mosfet overtemperature protection recovery in Celsius degrees
+    pub mosfet_otp_recovery: Deci,
+    /// Number of cells
+    pub cell_count: usize,
+    /// Charging enabled
+    pub charge: bool,
+    /// Discharging enabled
+    pub discharge: bool,
+    /// Balancing enabled
+    pub balance: bool,
+    /// Nominal battery capacity in Amperes*Hours
+    pub nominal_capacity: Milli,
+    /// Short circuit protection delay in microseconds
+    pub scp_delay: usize,
+    /// Cell voltage to start balancing in Volts
+    pub start_balance_voltage: Milli,
+}
@@ -0,0 +1,198 @@
+use crate::CellData;
+use uom::si::{
+    electric_charge::ampere_hour, electric_current::ampere, electric_potential::volt,
+    electrical_resistance::ohm, power::watt, thermodynamic_temperature::degree_celsius,
+    time::second,
+};
+
+#[cfg(feature = "serde")]
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+
+pub use uom::si::f64::{
+    ElectricCharge, ElectricCurrent, ElectricPotential, ElectricalResistance, Power,
+    ThermodynamicTemperature, Time,
+};
+
+/// Cell data as typed physical quantities
+///
+/// Missing values are kept as NaN quantities.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantities {
+    /// Cell voltages
+    pub cell_voltage: Vec<ElectricPotential>,
+    /// Average cell voltage
+    pub average_cell_voltage: ElectricPotential,
+    /// Maximum voltage difference between cells
+    pub delta_cell_voltage: ElectricPotential,
+    /// Balance current
+    pub balance_current: ElectricCurrent,
+    /// Cell resistances
+    pub cell_resistance: Vec<ElectricalResistance>,
+    /// Amount battery voltage between terminals
+    pub battery_voltage: ElectricPotential,
+    /// Amount battery power
+    pub battery_power: Power,
+    /// Battery current
+    pub battery_current: ElectricCurrent,
+    /// Battery temperatures
+    pub battery_temperature: Vec<ThermodynamicTemperature>,
+    /// BMS power mosfet temperature
+    pub mosfet_temperature: ThermodynamicTemperature,
+    /// Remain battery capacity
+    pub remain_capacity: ElectricCharge,
+    /// Nominal battery capacity
+    pub nominal_capacity: ElectricCharge,
+    /// Cycle battery capacity
+    pub cycle_capacity: ElectricCharge,
+    /// Time since last poweron
+    pub up_time: Time,
+}
+
+impl CellData {
+    /// Values as typed physical quantities
+    pub fn quantities(&self) -> Quantities {
+        Quantities {
+            cell_voltage: self
+                .cell_voltage
+                .iter()
+                .map(|value| ElectricPotential::new::<volt>(value.to_f64()))
+                .collect(),
+            average_cell_voltage: ElectricPotential::new::<volt>(
+                self.average_cell_voltage.to_f64(),
+            ),
+            delta_cell_voltage: ElectricPotential::new::<volt>(self.delta_cell_voltage.to_f64()),
+            balance_current: ElectricCurrent::new::<ampere>(self.balance_current.to_f64()),
+            cell_resistance: self
+                .cell_resistance
+                .iter()
+                .map(|value| ElectricalResistance::new::<ohm>(value.to_f64()))
+                .collect(),
+            battery_voltage: ElectricPotential::new::<volt>(self.battery_voltage.to_f64()),
+            battery_power: Power::new::<watt>(self.battery_power.to_f64()),
+            battery_current: ElectricCurrent::new::<ampere>(self.battery_current.to_f64()),
+            battery_temperature: self
+                .battery_temperature
+                .iter()
+                .map(|value| ThermodynamicTemperature::new::<degree_celsius>(value.to_f64()))
+                .collect(),
+            mosfet_temperature: ThermodynamicTemperature::new::<degree_celsius>(
+                self.mosfet_temperature.to_f64(),
+            ),
+            remain_capacity: ElectricCharge::new::<ampere_hour>(self.remain_capacity.to_f64()),
+            nominal_capacity: ElectricCharge::new::<ampere_hour>(self.nominal_capacity.to_f64()),
+            cycle_capacity: ElectricCharge::new::<ampere_hour>(self.cycle_capacity.to_f64()),
+            up_time: Time::new::<second>(self.up_time as f64),
+        }
+    }
+
+    /// Serialize values together with units
+    #[cfg(feature = "serde")]
+    pub fn with_units(&self) -> WithUnits<'_> {
+        WithUnits(self)
+    }
+}
+
+/// Cell data serialized with units
+///
+/// Each physical value is serialized as `{ value, unit }` structure, i.e.
+/// `{"value": 2.384, "unit": "V"}`, using units of [`CellData::FIELDS`].
+/// Values are kept in units reported by device, so they are exactly the same
+/// as in plain [`CellData`].
+#[cfg(feature = "serde")]
+#[derive(Clone, Copy, Debug)]
+pub struct WithUnits<'a>(pub &'a CellData);
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct Value<T> {
+    value: T,
+    unit: &'static str,
+}
+
+#[cfg(feature = "serde")]
+impl<T> Value<T> {
+    fn new(value: T, unit: &'static str) -> Self {
+        Self { value, unit }
+    }
+}
+
+/// Serialization of cell data using units of declared fields (device info is skipped)
+#[cfg(feature = "serde")]
+macro_rules! with_units_impl {
+    ( $info_class:ident { $($info:tt)* }
+      CellData {
+          $($name:ident: $kind:ident: $type:ident: $unit:literal: $unit_name:literal:
+            $sensitivity:literal: $description:literal;)*
+      } ) => {
+        impl Serialize for WithUnits<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let data = self.0;
+                let mut state = serializer.serialize_struct("CellData", CellData::FIELDS.len())?;
+                $(if $unit.is_empty() {
+                    state.serialize_field(stringify!($name), &data.$name)?;
+                } else {
+                    state.serialize_field(stringify!($name), &Value::new(&data.$name, $unit))?;
+                })*
+                state.end()
+            }
+        }
+    };
+}
+
+#[cfg(feature = "serde")]
+data_fields!(with_units_impl);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Deci, Milli};
+    use uom::si::{electric_charge::coulomb, thermodynamic_temperature::kelvin};
+
+    fn cell_data() -> CellData {
+        CellData {
+            cell_voltage: [2.384, 2.383].map(Milli::from_f64).to_vec(),
+            battery_voltage: Milli::from_f64(4.767),
+            battery_temperature: [23.2].map(Deci::from_f64).to_vec(),
+            mosfet_temperature: Deci::NAN,
+            remain_capacity: Milli::from_f64(12.0),
+            up_time: 60,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn quantities() {
+        let quantities = cell_data().quantities();
+        assert_eq!(quantities.cell_voltage[1].get::<volt>(), 2.383);
+        assert_eq!(quantities.battery_voltage.get::<volt>(), 4.767);
+        assert!((quantities.battery_temperature[0].get::<kelvin>() - 296.35).abs() < 1e-9);
+        assert!(quantities.mosfet_temperature.get::<kelvin>().is_nan());
+        assert_eq!(quantities.remain_capacity.get::<coulomb>(), 43200.0);
+        assert_eq!(quantities.up_time.get::<second>(), 60.0);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn serde() {
+        let data = cell_data();
+
+        let json = serde_json::to_value(&data).unwrap();
+        assert_eq!(json["battery_voltage"], 4.767);
+
+        let json = serde_json::to_value(data.with_units()).unwrap();
+        assert_eq!(
+            json["cell_voltage"].to_string(),
+            r#"{"unit":"V","value":[2.384,2.383]}"#
+        );
+        assert_eq!(
+            json["battery_temperature"].to_string(),
+            r#"{"unit":"℃","value":[23.2]}"#
+        );
+        assert_eq!(
+            json["remain_capacity"].to_string(),
+            r#"{"unit":"A·h","value":12.0}"#
+        );
+        assert!(json["mosfet_temperature"]["value"].is_null());
+        assert_eq!(json["cycle_count"], 0);
+    }
+}
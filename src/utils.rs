@@ -1,4 +1,4 @@
-use crate::{log, Result};
+use crate::{log, Fixed, Result};
 use pretty_hex::PrettyHex;
 
 pub fn checksum(init: Option<u8>, data: impl AsRef<[u8]>) -> u8 {
@@ -23,23 +23,25 @@ pub fn ascii_to_string_safe(field: &str, ascii: impl AsRef<[u8]>) -> String {
         .unwrap_or_default()
 }
 
-pub fn i16le_to_value(raw: &[u8; 2], mul: f32) -> f32 {
-    i16::from_le_bytes(*raw) as f32 * mul
+pub fn i16le_to_value<const DIGITS: u32>(raw: &[u8; 2]) -> Fixed<DIGITS> {
+    Fixed::from_raw(i16::from_le_bytes(*raw) as _)
 }
 
-pub fn i16les_to_values<const N: usize>(raw: &[[u8; 2]; N], mul: f32) -> Vec<f32> {
+pub fn i16les_to_values<const N: usize, const DIGITS: u32>(
+    raw: &[[u8; 2]; N],
+) -> Vec<Fixed<DIGITS>> {
     raw.iter()
         .filter(|raw| *raw != &[0; 2])
-        .map(|raw| i16le_to_value(raw, mul))
+        .map(i16le_to_value)
         .collect()
 }
 
-pub fn i32le_to_value(raw: &[u8; 4], mul: f32) -> f32 {
-    i32::from_le_bytes(*raw) as f32 * mul
+pub fn i32le_to_value<const DIGITS: u32>(raw: &[u8; 4]) -> Fixed<DIGITS> {
+    Fixed::from_raw(i32::from_le_bytes(*raw))
 }
 
-pub fn u32le_to_value(raw: &[u8; 4], mul: f32) -> f32 {
-    u32::from_le_bytes(*raw) as f32 * mul
+pub fn u32le_to_value<const DIGITS: u32>(raw: &[u8; 4]) -> Fixed<DIGITS> {
+    Fixed::from_raw(u32::from_le_bytes(*raw).min(i32::MAX as u32) as _)
 }
 
 pub fn u32le_to_count(raw: &[u8; 4]) -> usize {
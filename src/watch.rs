@@ -0,0 +1,368 @@
+use crate::{
+    cmdline::unix_time, log, CellData, Client, CsvColumns, DeviceConfig, DeviceInfo, Error, Format,
+    Main, Result,
+};
+use std::{
+    fs::{remove_file, rename, File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+use tokio::{select, time::interval};
+
+#[cfg(any(feature = "json", feature = "cbor", feature = "msgpack"))]
+use crate::cmdline::Record;
+
+/// Append-only output of records
+///
+/// Writes to stdout when no path is given. The file is rotated when it
+/// reaches maximum size: `path` becomes `path.1`, `path.1` becomes `path.2`
+/// and so on, the oldest one is removed.
+struct Output {
+    path: Option<PathBuf>,
+    file: Option<File>,
+    /// Number of bytes written to current file
+    size: u64,
+    /// Header line of current file
+    header: Vec<u8>,
+    max_size: Option<u64>,
+    keep: usize,
+}
+
+impl Output {
+    fn open(path: Option<&Path>, max_size: Option<u64>, keep: usize) -> Result<Self> {
+        let mut output = Self {
+            path: path.map(From::from),
+            file: None,
+            size: 0,
+            header: Vec::new(),
+            max_size,
+            keep,
+        };
+        output.reopen()?;
+        Ok(output)
+    }
+
+    fn reopen(&mut self) -> Result<()> {
+        if let Some(path) = &self.path {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            self.size = file.metadata()?.len();
+            self.header.clear();
+            if self.size > 0 {
+                BufReader::new(File::open(path)?).read_until(b'\n', &mut self.header)?;
+            }
+            self.file = Some(file);
+        }
+        Ok(())
+    }
+
+    /// Make sure that records go after given header
+    ///
+    /// File which starts with different header (i.e. written by previous run
+    /// or before layout was widened) is rotated to keep single header per
+    /// file, to stdout the new header is just written.
+    fn write_header(&mut self, header: &[u8]) -> Result<()> {
+        if self.header == header {
+            return Ok(());
+        }
+
+        if !self.is_empty() && self.path.is_some() {
+            // keep previous records even when rotated files aren't kept
+            self.rotate(self.keep.max(1))?;
+        }
+
+        self.write(header)?;
+        self.header = header.into();
+
+        Ok(())
+    }
+
+    /// Nothing written to current file yet
+    fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Rotate file if it reached maximum size
+    fn rotate_if_full(&mut self) -> Result<()> {
+        let (Some(_), Some(max_size)) = (&self.path, self.max_size) else {
+            return Ok(());
+        };
+
+        if self.size < max_size {
+            return Ok(());
+        }
+
+        self.rotate(self.keep)
+    }
+
+    /// Rotate file keeping given number of previous files
+    fn rotate(&mut self, keep: usize) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        log::debug!("Rotate output file: {}", path.display());
+
+        self.file = None;
+
+        if keep > 0 {
+            for index in (1..keep).rev() {
+                ignore_not_found(rename(
+                    rotated_path(path, index),
+                    rotated_path(path, index + 1),
+                ))?;
+            }
+            rename(path, rotated_path(path, 1))?;
+        } else {
+            remove_file(path)?;
+        }
+
+        self.reopen()
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        if let Some(file) = &mut self.file {
+            file.write_all(data)?;
+            file.flush()?;
+        } else {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(data)?;
+            stdout.flush()?;
+        }
+        self.size += data.len() as u64;
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{index}"));
+    path.into()
+}
+
+fn ignore_not_found(result: std::io::Result<()>) -> std::io::Result<()> {
+    match result {
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+impl Main {
+    /// Check that watch mode can be run
+    pub fn check_watch(&self) -> Result<()> {
+        if self.has_service() {
+            return Err(Error::BadConfig(
+                "Watch mode cannot be run together with exporter, push client or MQTT publisher"
+                    .into(),
+            ));
+        }
+
+        if self.watch.is_some_and(|period| period.is_zero()) {
+            return Err(Error::BadConfig("Zero watch period".into()));
+        }
+
+        match self.format {
+            Format::Csv => (),
+            #[cfg(feature = "json")]
+            Format::NdJson => (),
+            #[cfg(feature = "cbor")]
+            Format::Cbor => (),
+            #[cfg(feature = "msgpack")]
+            Format::MsgPack => (),
+            _ => {
+                return Err(Error::BadConfig(
+                    "Watch mode supports csv, ndjson, cbor and msgpack formats only".into(),
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn run_watch(&self) -> Result<()> {
+        let mut output = Output::open(
+            self.output.as_deref(),
+            self.rotate_size(),
+            self.rotate_keep(),
+        )?;
+
+        let mut columns = CsvColumns::default();
+
+        let mut poller = interval(self.watch.unwrap_or_default());
+
+        log::info!("Start watching {} devices", self.clients.len());
+
+        loop {
+            select! {
+                _ = poller.tick() => (),
+                _ = self.intr.notified() => break,
+            }
+
+            for (client, device) in self.clients.iter().zip(self.devices.iter()) {
+                let Some(sample) = self.watch_sample(client, device).await else {
+                    continue;
+                };
+
+                output.rotate_if_full()?;
+
+                let mut record = Vec::new();
+
+                if matches!(self.format, Format::Csv) {
+                    // layout is widened when device with more cells or sensors responds
+                    columns.fit(&sample.cell_data);
+                    let mut header = Vec::new();
+                    columns.write_header(&mut header)?;
+                    output.write_header(&header)?;
+                    columns.write_record(
+                        sample.timestamp,
+                        &sample.device,
+                        &sample.cell_data,
+                        &mut record,
+                    )?;
+                }
+
+                #[cfg(any(feature = "json", feature = "cbor", feature = "msgpack"))]
+                if !matches!(self.format, Format::Csv) {
+                    let sample = Record {
+                        timestamp: sample.timestamp,
+                        device: &sample.device,
+                        device_info: sample.device_info.as_ref(),
+                        cell_data: Some(&sample.cell_data),
+                    };
+                    match self.format {
+                        #[cfg(feature = "json")]
+                        Format::NdJson => sample.write(&mut record)?,
+                        // binary records are self-delimiting
+                        _ => self.format.format_value(&sample, &mut record)?,
+                    }
+                }
+
+                output.write(&record)?;
+            }
+        }
+
+        log::info!("Stop watching");
+
+        Ok(())
+    }
+
+    async fn watch_sample(&self, client: &Client, device: &DeviceConfig) -> Option<Sample> {
+        let device = device.name();
+
+        if let Err(error) = client.open().await {
+            log::error!("Error while connecting to device '{device}': {error}");
+            return None;
+        }
+
+        let device_info = if self.device_info {
+            client
+                .device_info()
+                .await
+                .map_err(|error| log::error!("Error while fetching device info: {error}"))
+                .ok()
+        } else {
+            None
+        };
+
+        let cell_data = client
+            .cell_data()
+            .await
+            .map_err(|error| log::error!("Error while fetching cell data: {error}"))
+            .ok();
+
+        if let Err(error) = client.close().await {
+            log::error!("Error while disconnecting from device '{device}': {error}");
+        }
+
+        let cell_data = cell_data?;
+
+        Some(Sample {
+            timestamp: unix_time().as_secs_f64(),
+            device,
+            device_info,
+            cell_data,
+        })
+    }
+}
+
+/// Single sample of device
+struct Sample {
+    /// Unix time in seconds
+    timestamp: f64,
+    device: String,
+    #[cfg_attr(
+        not(any(feature = "json", feature = "cbor", feature = "msgpack")),
+        allow(dead_code)
+    )]
+    device_info: Option<DeviceInfo>,
+    cell_data: CellData,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::{read_to_string, remove_dir_all};
+
+    #[test]
+    fn rotation() {
+        let dir = std::env::temp_dir().join(format!("ubmsc-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bench.csv");
+
+        let mut output = Output::open(Some(&path), Some(8), 2).unwrap();
+        assert!(output.is_empty());
+
+        for record in ["first\n", "second\n", "third\n", "fourth\n", "fifth\n"] {
+            output.rotate_if_full().unwrap();
+            output.write(record.as_bytes()).unwrap();
+        }
+        assert!(!output.is_empty());
+
+        assert_eq!(read_to_string(&path).unwrap(), "fifth\n");
+        assert_eq!(
+            read_to_string(rotated_path(&path, 1)).unwrap(),
+            "third\nfourth\n"
+        );
+        assert_eq!(
+            read_to_string(rotated_path(&path, 2)).unwrap(),
+            "first\nsecond\n"
+        );
+        assert!(!rotated_path(&path, 3).exists());
+
+        // appending to existing file
+        let output = Output::open(Some(&path), None, 0).unwrap();
+        assert!(!output.is_empty());
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn header() {
+        let dir = std::env::temp_dir().join(format!("ubmsc-header-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bench.csv");
+
+        let mut output = Output::open(Some(&path), None, 0).unwrap();
+        for (header, record) in [("a,b\n", "1,2\n"), ("a,b\n", "3,4\n")] {
+            output.write_header(header.as_bytes()).unwrap();
+            output.write(record.as_bytes()).unwrap();
+        }
+        assert_eq!(read_to_string(&path).unwrap(), "a,b\n1,2\n3,4\n");
+
+        // the same layout is appended to existing file
+        let mut output = Output::open(Some(&path), None, 0).unwrap();
+        output.write_header(b"a,b\n").unwrap();
+        output.write(b"5,6\n").unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "a,b\n1,2\n3,4\n5,6\n");
+
+        // widened layout goes to new file
+        output.write_header(b"a,b,c\n").unwrap();
+        output.write(b"7,8,9\n").unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "a,b,c\n7,8,9\n");
+        assert_eq!(
+            read_to_string(rotated_path(&path, 1)).unwrap(),
+            "a,b\n1,2\n3,4\n5,6\n"
+        );
+
+        remove_dir_all(&dir).unwrap();
+    }
+}